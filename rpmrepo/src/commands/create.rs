@@ -1,14 +1,17 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{convert::TryInto, env};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use dialoguer::Confirm;
-use rpm;
-use rpmrepo_metadata::{Package, Repository, RepositoryOptions};
+use rpmrepo_metadata::{
+    verify_package_signature, ChecksumType, Package, PackageSignaturePolicy,
+    PackageSignatureSummary, Repository, RepositoryOptions, VerifyingKey,
+};
 // use crate::utils::copy_dir;
 
+use super::sign::load_signing_key;
 use super::CreateCommand;
 
 pub fn create(config: CreateCommand) -> Result<()> {
@@ -36,34 +39,80 @@ pub fn create(config: CreateCommand) -> Result<()> {
             .for_each(|t| repo.add_repo_tag(t.to_owned()));
     }
 
-    // if let Some(add_package_list) = config.add_package_list {
-    //     let pkglist_path = Path::new(&add_package_list);
-    //     let pkglist_file = File::open(&pkglist_path)?; // TODO pretty error handling
-    //     for pkg_path in BufReader::new(pkglist_file).lines() {
-    //         let rpm_file = File::open(pkg_path)?;
-    //         let mut buf_reader = std::io::BufReader::new(rpm_file);
-    //         let pkg = rpm::RPMPackage::parse(&mut buf_reader)?;
-    //         let package: Package = pkg.into();
-    //     }
-    // }
-
-    // TODO: enumerate RPMs, add to repo
-
     let mut options = RepositoryOptions::default();
 
     if let Some(compression_type) = config.metadata_compression_type {
         options = options.metadata_compression_type(compression_type.as_str().try_into()?);
     }
 
-    // TODO: this is messy, also list valid compression options when user types invalid one
+    if let Some(checksum_type) = config.metadata_checksum_type {
+        options = options.metadata_checksum_type(checksum_type.as_str().try_into()?);
+    }
 
-    // let options = if let Some(checksum_type) = config.metadata_compression_type {
-    //     options.metadata_checksum_type(checksum_type.as_str().try_into()?)
-    // } else {
-    //     unreachable!()
-    // };
+    // TODO: list valid compression and checksum options when user types invalid one
+    let package_checksum_type = match config.package_checksum_type {
+        Some(checksum_type) => checksum_type.as_str().try_into()?,
+        None => ChecksumType::Sha256,
+    };
+    options = options.package_checksum_type(package_checksum_type);
+
+    // load the key before doing any work, so that a wrong passphrase fails early
+    let signing_key = match config.signing_key {
+        Some(key_path) => Some(load_signing_key(Path::new(&key_path))?),
+        None => None,
+    };
+
+    let package_keys = config
+        .gpgkey
+        .iter()
+        .map(|key_path| VerifyingKey::from_file(Path::new(key_path)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let signature_policy = match config.signature_policy {
+        Some(policy) => policy.parse().map_err(anyhow::Error::msg)?,
+        None => PackageSignaturePolicy::Reject,
+    };
+    let mut signature_summary = PackageSignatureSummary::default();
+
+    // the packages are copied into the repository, next to the metadata which points at them
+    let mut package_files: Vec<(PathBuf, String)> = Vec::new();
+
+    if let Some(add_package_list) = config.add_package_list {
+        let pkglist_path = Path::new(&add_package_list);
+        let pkglist_file = File::open(&pkglist_path)?; // TODO pretty error handling
+        for pkg_path in BufReader::new(pkglist_file).lines() {
+            let pkg_path = PathBuf::from(pkg_path?);
+            let file_name = pkg_path
+                .file_name()
+                .ok_or_else(|| anyhow!("not a package file: {}", pkg_path.display()))?;
+            let location_href = format!("Packages/{}", file_name.to_string_lossy());
+
+            if !package_keys.is_empty() {
+                let status = verify_package_signature(&pkg_path, &package_keys)?;
+                signature_summary.add(&location_href, status);
+            }
+            let package = Package::from_rpm_file(&pkg_path, package_checksum_type, &location_href)?;
+            repo.add_package(package)?;
+            package_files.push((pkg_path, location_href));
+        }
+    }
 
-    // repo.to_directory(&temp_dir, options)?;
+    if !package_keys.is_empty() {
+        match signature_policy {
+            _ if signature_summary.all_valid() => {
+                println!("Package signatures: {}", signature_summary)
+            }
+            PackageSignaturePolicy::Reject => {
+                bail!("packages without a valid signature: {}", signature_summary)
+            }
+            PackageSignaturePolicy::Warn => eprintln!(
+                "Warning: packages without a valid signature: {}",
+                signature_summary
+            ),
+            PackageSignaturePolicy::Allow => {
+                println!("Package signatures: {}", signature_summary)
+            }
+        }
+    }
 
     if repository_path.exists() {
         if Confirm::new()
@@ -76,7 +125,19 @@ pub fn create(config: CreateCommand) -> Result<()> {
         }
     }
 
-    repo.write_to_directory(&repository_path, options)?;
+    if !package_files.is_empty() {
+        std::fs::create_dir_all(repository_path.join("Packages"))?;
+    }
+    for (pkg_path, location_href) in &package_files {
+        std::fs::copy(pkg_path, repository_path.join(location_href))?;
+    }
+
+    match &signing_key {
+        Some(signing_key) => {
+            repo.write_to_directory_signed(&repository_path, options, signing_key)?
+        }
+        None => repo.write_to_directory(&repository_path, options)?,
+    }
 
     // copy_dir(&temp_dir, &repository_path)?;
 
@@ -61,7 +61,10 @@ pub fn download(config: DownloadCommand) -> Result<()> {
 
     let cachedir = TempDir::new_in(repo_destination, ".rpmrepo_cache_")?;
 
-    RepoDownloader::new(url, download_config).download_to(&cachedir)?;
+    let summary = RepoDownloader::new(url, download_config).download_to(&cachedir)?;
+    for warning in &summary.warnings {
+        eprintln!("Warning: {}", warning);
+    }
 
     std::fs::remove_dir_all(&repo_destination)?;
     fs::rename(cachedir.into_path(), &repo_destination)?;
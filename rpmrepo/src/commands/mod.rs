@@ -1,4 +1,7 @@
 pub mod create;
+pub mod modify;
+pub mod sign;
+pub mod verify;
 // pub mod download;
 // pub mod sync;
 
@@ -13,6 +16,9 @@ pub fn handle_command() -> Result<()> {
     match execution_config.subcommand {
         // Subcommands::Download(c) => download::download(c),
         Subcommands::Create(c) => create::create(c),
+        Subcommands::Modify(c) => modify::modify(c),
+        Subcommands::Sign(c) => sign::sign(c),
+        Subcommands::Verify(c) => verify::verify(c),
         // Subcommands::Sync(c) => sync::sync(c),
     }
 }
@@ -30,7 +36,9 @@ enum Subcommands {
     // Download(DownloadCommand),
     Create(CreateCommand),
     // Sync(SyncCommand),
-    // Modify(ModifyCommand),
+    Modify(ModifyCommand),
+    Sign(SignCommand),
+    Verify(VerifyCommand),
     // Merge(MergeCommand),
 }
 
@@ -116,7 +124,7 @@ pub struct CreateCommand {
     #[argh(option)]
     repo_tags: Option<String>,
 
-    /// metadata compression type
+    /// metadata compression type (none, gzip, bz2, xz, zstd)
     #[argh(option)]
     metadata_compression_type: Option<String>,
 
@@ -131,4 +139,75 @@ pub struct CreateCommand {
     /// path to a list of RPM packages to add to the repo
     #[argh(option)]
     add_package_list: Option<String>,
+
+    /// ASCII-armored secret key to sign repomd.xml with (passphrase read from $RPMREPO_KEY_PASSPHRASE)
+    #[argh(option)]
+    signing_key: Option<OsString>,
+
+    /// ASCII-armored public key to check the package signatures against (can be repeated)
+    #[argh(option)]
+    gpgkey: Vec<OsString>,
+
+    /// what to do with unsigned or badly signed packages: reject (default), warn or allow
+    #[argh(option)]
+    signature_policy: Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Add or remove custom metadata files of an existing repository
+#[argh(subcommand, name = "modify")]
+pub struct ModifyCommand {
+    /// directory containing the repository
+    #[argh(positional)]
+    destination: OsString,
+
+    /// file to add to the repodata/ directory
+    #[argh(option)]
+    add: Option<OsString>,
+
+    /// metadata type of the added file (default: its filename up to the first ".")
+    #[argh(option)]
+    mdtype: Option<String>,
+
+    /// metadata type to remove
+    #[argh(option)]
+    remove: Option<String>,
+
+    /// compression type of the added file (none, gzip, bz2, xz, zstd)
+    #[argh(option)]
+    compression_type: Option<String>,
+
+    /// checksum type of the added file
+    #[argh(option)]
+    checksum_type: Option<String>,
+
+    /// don't prefix the filename of the added file with its checksum
+    #[argh(switch)]
+    simple_md_filenames: bool,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Sign the repomd.xml of an existing repository, writing repomd.xml.asc
+#[argh(subcommand, name = "sign")]
+pub struct SignCommand {
+    /// directory containing the repository
+    #[argh(positional)]
+    destination: OsString,
+
+    /// ASCII-armored secret key file (passphrase read from $RPMREPO_KEY_PASSPHRASE)
+    #[argh(option)]
+    key: OsString,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Check the files of an existing repository against its metadata
+#[argh(subcommand, name = "verify")]
+pub struct VerifyCommand {
+    /// directory containing the repository
+    #[argh(positional)]
+    destination: OsString,
+
+    /// ASCII-armored public key to check the repomd.xml signature against (can be repeated)
+    #[argh(option)]
+    gpgkey: Vec<OsString>,
 }
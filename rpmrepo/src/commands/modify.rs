@@ -1,26 +1,50 @@
+use std::convert::TryInto;
 use std::env;
+use std::path::Path;
 
-use anyhow::Result;
-use dialoguer::Confirm;
+use anyhow::{bail, Result};
+use rpmrepo_metadata::{RepomdXml, Repository, RepositoryOptions};
 
-use super::CreateCommand;
-
-pub fn create(config: CreateCommand) -> Result<()> {
+use super::ModifyCommand;
 
+pub fn modify(config: ModifyCommand) -> Result<()> {
     let repository_path = env::current_dir()?.join(config.destination);
 
-    // TODO: Create a whole repository object, enumerate RPMs, populate it with RPMs (including parsing),
-    // write metadata
-
-    if repository_path.exists() {
-        if Confirm::new()
-            .with_prompt("A directory with this name already exists. Overwrite it?")
-            .interact()?
-        {
-            std::fs::remove_dir_all(&repository_path)?;
-        } else {
-            std::process::exit(0);
+    if config.add.is_none() && config.remove.is_none() {
+        bail!("nothing to do, use --add and/or --remove");
+    }
+
+    // only repomd.xml is rewritten, there's no need to parse the package metadata
+    let mut repo = Repository::new();
+    repo.load_metadata_file::<RepomdXml>(&repository_path.join("repodata/repomd.xml"))?;
+
+    if let Some(mdtype) = config.remove {
+        repo.remove_metadata_file(&repository_path, &mdtype)?;
+    }
+
+    if let Some(add) = config.add {
+        let file_path = Path::new(&add);
+        let mdtype = match config.mdtype {
+            Some(mdtype) => mdtype,
+            None => file_path
+                .file_name()
+                .and_then(|f| f.to_str())
+                .and_then(|f| f.split('.').next())
+                .filter(|f| !f.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("can't derive a metadata type from {:?}", add))?
+                .to_owned(),
+        };
+
+        let mut options =
+            RepositoryOptions::default().simple_metadata_filenames(config.simple_md_filenames);
+        if let Some(compression_type) = config.compression_type {
+            options = options.metadata_compression_type(compression_type.as_str().try_into()?);
         }
+        if let Some(checksum_type) = config.checksum_type {
+            options = options.metadata_checksum_type(checksum_type.as_str().try_into()?);
+        }
+
+        repo.add_metadata_file(&repository_path, file_path, &mdtype, options)?;
     }
 
     Ok(())
@@ -0,0 +1,25 @@
+use std::env;
+use std::path::Path;
+
+use anyhow::Result;
+use rpmrepo_metadata::{Repository, SigningKey};
+
+use super::SignCommand;
+
+/// Environment variable holding the passphrase of the secret key, if it has one
+pub const PASSPHRASE_ENV_VAR: &str = "RPMREPO_KEY_PASSPHRASE";
+
+pub fn sign(config: SignCommand) -> Result<()> {
+    let repository_path = env::current_dir()?.join(config.destination);
+    let signing_key = load_signing_key(Path::new(&config.key))?;
+
+    let signature_path = Repository::sign_repomd(&repository_path, &signing_key)?;
+    println!("Wrote {}", signature_path.display());
+
+    Ok(())
+}
+
+pub fn load_signing_key(path: &Path) -> Result<SigningKey> {
+    let passphrase = env::var(PASSPHRASE_ENV_VAR).ok();
+    Ok(SigningKey::from_file(path, passphrase.as_deref())?)
+}
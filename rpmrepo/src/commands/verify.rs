@@ -0,0 +1,29 @@
+use std::env;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use rpmrepo_metadata::{verify_repository, Repository, VerifyingKey};
+
+use super::VerifyCommand;
+
+pub fn verify(config: VerifyCommand) -> Result<()> {
+    let repository_path = env::current_dir()?.join(config.destination);
+
+    if !config.gpgkey.is_empty() {
+        let keys = config
+            .gpgkey
+            .iter()
+            .map(|key_path| VerifyingKey::from_file(Path::new(key_path)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Repository::verify_repomd(&repository_path, &keys)?;
+        println!("repomd.xml signature: OK");
+    }
+
+    let report = verify_repository(&repository_path)?;
+    if !report.is_ok() {
+        bail!("{}", report);
+    }
+    println!("{}", report);
+
+    Ok(())
+}
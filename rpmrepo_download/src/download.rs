@@ -1,7 +1,7 @@
 use std::fs::{self, File};
 use std::io::Write;
 use std::io::{self, Read};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -14,13 +14,15 @@ use rustls::{
     self,
     internal::pemfile::{certs, rsa_private_keys},
 };
-use rustls_native_certs;
 use thiserror::Error;
-use ureq::{self, AgentBuilder};
 use url::Url;
 
 // use crate::metadata::RpmMetadata;
-use rpmrepo_metadata::{Checksum, MetadataError, PrimaryXml, RepomdXml, Repository};
+use rpmrepo_metadata::{
+    verify_package_signature, verify_signature, Checksum, MetadataError, PackageSignaturePolicy,
+    PackageSignatureSummary, PrestodeltaXml, PrimaryXml, RepomdXml, Repository, Treeinfo,
+    VerifyingKey,
+};
 
 pub const DEFAULT_CONCURRENCY: u8 = 5;
 
@@ -46,6 +48,22 @@ pub enum RepoDownloadError {
     DownloadError(#[from] ureq::Error),
     #[error(transparent)]
     MetadataError(#[from] MetadataError),
+    #[error("Checksum mismatch for {0}")]
+    ChecksumMismatchError(String),
+    #[error("Refusing to download to {0}, which is outside of the destination directory")]
+    UnsafePathError(String),
+    #[error("Rejected packages without a valid signature: {0}")]
+    PackageSignatureError(PackageSignatureSummary),
+}
+
+/// What a successful download leaves for the caller to report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownloadSummary {
+    /// The packages whose signatures were checked, by their path relative to the destination.
+    /// Empty unless package verification keys are set.
+    pub package_signatures: PackageSignatureSummary,
+    /// Problems which didn't stop the download, e.g. a .treeinfo which can't be parsed.
+    pub warnings: Vec<String>,
 }
 
 pub struct RepoDownloader {
@@ -58,6 +76,11 @@ pub struct DownloadConfig {
     concurrency: u8,
     verify_tls: bool,
     only_metadata: bool,
+    follow_treeinfo: bool,
+    download_deltas: bool,
+    verification_keys: Vec<VerifyingKey>,
+    package_verification_keys: Vec<VerifyingKey>,
+    package_signature_policy: PackageSignaturePolicy,
     client_cert_path: Option<PathBuf>,
     client_key_path: Option<PathBuf>,
     ca_cert_path: Option<PathBuf>,
@@ -69,6 +92,11 @@ impl Default for DownloadConfig {
             concurrency: DEFAULT_CONCURRENCY,
             verify_tls: true,
             only_metadata: false,
+            follow_treeinfo: true,
+            download_deltas: false,
+            verification_keys: Vec::new(),
+            package_verification_keys: Vec::new(),
+            package_signature_policy: PackageSignaturePolicy::Reject,
             client_cert_path: None,
             client_key_path: None,
             ca_cert_path: None,
@@ -151,6 +179,50 @@ impl DownloadConfig {
         }
     }
 
+    /// If the URL points at an installable tree (it has a .treeinfo file), download the images
+    /// and the repository of every variant of the tree instead of a single repository.
+    pub fn follow_treeinfo(self, val: bool) -> Self {
+        DownloadConfig {
+            follow_treeinfo: val,
+            ..self
+        }
+    }
+
+    /// Also download the deltarpms listed in prestodelta.xml, if the repository has one.
+    pub fn download_deltas(self, val: bool) -> Self {
+        DownloadConfig {
+            download_deltas: val,
+            ..self
+        }
+    }
+
+    /// Refuse to download a repository unless its repomd.xml has a detached signature
+    /// (repomd.xml.asc) made by one of `keys`.
+    pub fn verification_keys(self, keys: Vec<VerifyingKey>) -> Self {
+        DownloadConfig {
+            verification_keys: keys,
+            ..self
+        }
+    }
+
+    /// Check the signatures of the downloaded packages against `keys`.
+    pub fn package_verification_keys(self, keys: Vec<VerifyingKey>) -> Self {
+        DownloadConfig {
+            package_verification_keys: keys,
+            ..self
+        }
+    }
+
+    /// What to do with packages which are unsigned or badly signed (default: reject them).
+    /// Rejected packages are removed again, and the download fails once all of the packages have
+    /// been checked.
+    pub fn package_signature_policy(self, policy: PackageSignaturePolicy) -> Self {
+        DownloadConfig {
+            package_signature_policy: policy,
+            ..self
+        }
+    }
+
     pub fn with_concurrency(self, threads: u8) -> Self {
         assert_eq!(
             threads.clamp(1, 10),
@@ -190,14 +262,177 @@ impl RepoDownloader {
 
     // }
 
-    pub fn download_to<P: AsRef<Path>>(&self, repository_path: P) -> Result<(), RepoDownloadError> {
-        let base_url = &self.base_url;
+    /// Download the repository, or the whole tree if it has a .treeinfo, to `repository_path`.
+    ///
+    /// Nothing is printed about packages without a valid signature which are let through, or
+    /// about other problems which don't stop the download, they are listed in the returned
+    /// summary instead.
+    pub fn download_to<P: AsRef<Path>>(
+        &self,
+        repository_path: P,
+    ) -> Result<DownloadSummary, RepoDownloadError> {
         let repository_path = repository_path.as_ref();
+        let mut summary = DownloadSummary::default();
+
+        let treeinfo = if self.download_config.follow_treeinfo {
+            self.download_treeinfo(repository_path, &mut summary.warnings)?
+        } else {
+            None
+        };
+        summary.package_signatures = match treeinfo {
+            Some(treeinfo) => self.download_tree(&treeinfo, repository_path)?,
+            None => self.download_repository(&self.base_url, repository_path)?,
+        };
+
+        Ok(summary)
+    }
+
+    /// Download the .treeinfo file of the tree, if there is one.
+    fn download_treeinfo(
+        &self,
+        tree_path: &Path,
+        warnings: &mut Vec<String>,
+    ) -> Result<Option<Treeinfo>, RepoDownloadError> {
+        // older trees only have the file without the leading dot
+        for filename in &[".treeinfo", "treeinfo"] {
+            let url = self.base_url.join(filename)?;
+            if let Some(treeinfo_bytes) = try_download_file(&self.agent, &url)? {
+                let treeinfo = std::str::from_utf8(&treeinfo_bytes)
+                    .map_err(MetadataError::from)
+                    .and_then(str::parse::<Treeinfo>);
+                // a broken .treeinfo shouldn't prevent downloading the repository next to it
+                let treeinfo = match treeinfo {
+                    Ok(treeinfo) => treeinfo,
+                    Err(e) => {
+                        warnings.push(format!("ignoring {}, which can't be parsed: {}", url, e));
+                        return Ok(None);
+                    }
+                };
+                save_metadata_file(&treeinfo_bytes, &tree_path.join(filename))?;
+                return Ok(Some(treeinfo));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Download the images of the tree and the repositories of all of its variants and addons.
+    fn download_tree(
+        &self,
+        treeinfo: &Treeinfo,
+        tree_path: &Path,
+    ) -> Result<PackageSignatureSummary, RepoDownloadError> {
+        // the paths come from the server, check all of them before writing anything
+        let image_paths = treeinfo.image_paths();
+        let repository_paths = treeinfo.repository_paths();
+        for path in image_paths.iter().chain(repository_paths.iter()) {
+            check_relative_path(path)?;
+        }
+
+        let begin = Instant::now();
+        for image_path in image_paths {
+            let url = self.base_url.join(image_path)?;
+            let image_bytes = download_file(&self.agent, &url)?;
+
+            if let Some(checksum) = treeinfo.checksums.get(image_path) {
+                if !checksum_matches(&mut image_bytes.as_slice(), checksum)? {
+                    return Err(RepoDownloadError::ChecksumMismatchError(
+                        image_path.to_owned(),
+                    ));
+                }
+            }
+            save_metadata_file(&image_bytes, &tree_path.join(image_path))?;
+        }
+        let end = Instant::now();
+
+        println!(
+            "Images downloaded in {} seconds",
+            (end - begin).as_secs_f32()
+        );
+
+        let mut summary = PackageSignatureSummary::default();
+        for repository in repository_paths {
+            // the URL needs a trailing slash, or joining relative paths would replace the last
+            // path segment instead of appending to it
+            let repository = repository.trim_end_matches('/');
+            let repository_url = self.base_url.join(&format!("{}/", repository))?;
+            let repository_summary =
+                self.download_repository(&repository_url, &tree_path.join(repository))?;
+
+            // the packages of the variants are listed relative to the tree
+            let tree_href = |location_href: String| format!("{}/{}", repository, location_href);
+            summary.checked += repository_summary.checked;
+            summary
+                .unsigned
+                .extend(repository_summary.unsigned.into_iter().map(tree_href));
+            summary.invalid.extend(
+                repository_summary
+                    .invalid
+                    .into_iter()
+                    .map(|(location_href, reason)| (tree_href(location_href), reason)),
+            );
+        }
+
+        Ok(summary)
+    }
 
+    /// Download repomd.xml.asc and check it against `repomd_xml`, if signature verification is
+    /// enabled.
+    fn download_repomd_signature(
+        &self,
+        base_url: &Url,
+        repomd_xml: &[u8],
+    ) -> Result<Option<Vec<u8>>, RepoDownloadError> {
+        let keys = &self.download_config.verification_keys;
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        let signature_url = base_url.join("repodata/repomd.xml.asc")?;
+        let signature = try_download_file(&self.agent, &signature_url)?.ok_or_else(|| {
+            MetadataError::SignatureVerificationError(format!("{} is missing", signature_url))
+        })?;
+        verify_signature(repomd_xml, &signature, keys)?;
+
+        Ok(Some(signature))
+    }
+
+    /// Remove the packages without a valid signature if they are rejected.
+    fn apply_package_signature_policy(
+        &self,
+        summary: PackageSignatureSummary,
+        repository_path: &Path,
+    ) -> Result<PackageSignatureSummary, RepoDownloadError> {
+        if summary.all_valid() {
+            return Ok(summary);
+        }
+
+        match self.download_config.package_signature_policy {
+            PackageSignaturePolicy::Reject => {
+                let rejected = summary.unsigned.iter().chain(
+                    summary
+                        .invalid
+                        .iter()
+                        .map(|(location_href, _)| location_href),
+                );
+                for location_href in rejected {
+                    fs::remove_file(repository_path.join(location_href))?;
+                }
+                Err(RepoDownloadError::PackageSignatureError(summary))
+            }
+            PackageSignaturePolicy::Warn | PackageSignaturePolicy::Allow => Ok(summary),
+        }
+    }
+
+    fn download_repository(
+        &self,
+        base_url: &Url,
+        repository_path: &Path,
+    ) -> Result<PackageSignatureSummary, RepoDownloadError> {
         let mut repo = Repository::new();
 
         let repomd_url = base_url.join("repodata/repomd.xml")?;
         let repomd_xml = &download_file(&self.agent, &repomd_url)?;
+        let repomd_signature = self.download_repomd_signature(base_url, repomd_xml)?;
         repo.load_metadata_bytes::<RepomdXml>(repomd_xml)?;
 
         let repodata_path = repository_path.join("repodata");
@@ -205,6 +440,9 @@ impl RepoDownloader {
 
         let repomd_path = repodata_path.join("repomd.xml");
         save_metadata_file(&repomd_xml, &repomd_path)?;
+        if let Some(repomd_signature) = repomd_signature {
+            save_metadata_file(&repomd_signature, &repodata_path.join("repomd.xml.asc"))?;
+        }
 
         let pool = rayon::ThreadPoolBuilder::new()
             .num_threads(self.download_config.concurrency.into())
@@ -213,16 +451,16 @@ impl RepoDownloader {
 
         let begin = Instant::now();
         pool.scope(|_| {
-            repo.metadata_files.par_iter().for_each(|md| {
-                let relative_path = md.location_href.as_str();
-                let url = base_url.join(relative_path).unwrap();
-
-                let fs_location = &repository_path.join(relative_path);
-                let metadata_bytes = download_file(&self.agent, &url).unwrap();
-                save_metadata_file(&metadata_bytes, fs_location).unwrap();
-                verify_checksum(&fs_location, &md.checksum).unwrap();
-            });
-        });
+            repo.metadata_files.par_iter().try_for_each(|md| {
+                self.download_verified_file(
+                    base_url,
+                    repository_path,
+                    &md.location_href,
+                    &md.checksum,
+                )
+                .map(|_| ())
+            })
+        })?;
         let end = Instant::now();
 
         println!(
@@ -231,7 +469,7 @@ impl RepoDownloader {
         );
 
         if self.download_config.only_metadata {
-            return Ok(());
+            return Ok(PackageSignatureSummary::default());
         }
 
         let primary_href = repo.get_primary_data().location_href.as_str();
@@ -241,21 +479,33 @@ impl RepoDownloader {
         // let mut package_pb =
         //     ProgressBar::new(repo.packages().len() as u64).with_style(ProgressStyle::default_bar());
 
+        let check_signatures = !self.download_config.package_verification_keys.is_empty();
+
         let begin = Instant::now();
-        pool.scope(|_| {
+        let signature_statuses: Vec<_> = pool.scope(|_| {
             repo.packages()
                 .par_iter()
                 // .progress_with(package_pb)
-                .for_each(|(_, package)| {
+                .map(|(_, package)| -> Result<_, RepoDownloadError> {
                     let relative_path = package.location_href();
-                    let url = base_url.join(relative_path).unwrap();
-
-                    let fs_location = &repository_path.join(relative_path);
-                    let package_bytes = download_file(&self.agent, &url).unwrap();
-                    save_metadata_file(&package_bytes, fs_location).unwrap();
-                    assert!(verify_checksum(&fs_location, package.checksum()).unwrap());
-                });
-        });
+                    let fs_location = &self.download_verified_file(
+                        base_url,
+                        repository_path,
+                        relative_path,
+                        package.checksum(),
+                    )?;
+
+                    if !check_signatures {
+                        return Ok(None);
+                    }
+                    let status = verify_package_signature(
+                        fs_location,
+                        &self.download_config.package_verification_keys,
+                    )?;
+                    Ok(Some((relative_path, status)))
+                })
+                .collect::<Result<_, _>>()
+        })?;
         let end = Instant::now();
 
         println!(
@@ -263,7 +513,85 @@ impl RepoDownloader {
             (end - begin).as_secs_f32()
         );
 
-        Ok(())
+        let mut summary = PackageSignatureSummary::default();
+        for (relative_path, status) in signature_statuses.into_iter().flatten() {
+            summary.add(relative_path, status);
+        }
+        let summary = self.apply_package_signature_policy(summary, repository_path)?;
+
+        if !self.download_config.download_deltas {
+            return Ok(summary);
+        }
+
+        let prestodelta_href = match repo.get_record("prestodelta") {
+            Some(record) => record.location_href.clone(),
+            None => return Ok(summary),
+        };
+        repo.load_metadata_file::<PrestodeltaXml>(&repository_path.join(prestodelta_href))?;
+
+        let deltas: Vec<_> = repo
+            .deltas
+            .iter()
+            .flat_map(|package| package.deltas.iter())
+            .collect();
+        for delta in &deltas {
+            check_relative_path(&delta.filename)?;
+        }
+
+        let begin = Instant::now();
+        pool.scope(|_| {
+            deltas.par_iter().try_for_each(|delta| {
+                self.download_verified_file(
+                    base_url,
+                    repository_path,
+                    &delta.filename,
+                    &delta.checksum,
+                )
+                .map(|_| ())
+            })
+        })?;
+        let end = Instant::now();
+
+        println!(
+            "Deltarpms downloaded in {} seconds",
+            (end - begin).as_secs_f32()
+        );
+
+        Ok(summary)
+    }
+
+    /// Download the file at `relative_path` of the repository to the same location under
+    /// `repository_path`, returning where it was saved. Nothing is saved unless the contents
+    /// match `checksum` and the location is inside `repository_path`.
+    fn download_verified_file(
+        &self,
+        base_url: &Url,
+        repository_path: &Path,
+        relative_path: &str,
+        checksum: &Checksum,
+    ) -> Result<PathBuf, RepoDownloadError> {
+        check_relative_path(relative_path)?;
+
+        let url = base_url.join(relative_path)?;
+        let bytes = download_file(&self.agent, &url)?;
+        if !checksum_matches(&mut bytes.as_slice(), checksum)? {
+            return Err(RepoDownloadError::ChecksumMismatchError(
+                relative_path.to_owned(),
+            ));
+        }
+
+        let fs_location = repository_path.join(relative_path);
+        save_metadata_file(&bytes, &fs_location)?;
+        Ok(fs_location)
+    }
+}
+
+/// Download a file which may legitimately not exist, returning `None` if the server says so.
+fn try_download_file(agent: &ureq::Agent, url: &Url) -> Result<Option<Vec<u8>>, RepoDownloadError> {
+    match download_file(agent, url) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(RepoDownloadError::DownloadError(ureq::Error::Status(404, _))) => Ok(None),
+        Err(e) => Err(e),
     }
 }
 
@@ -283,15 +611,18 @@ fn download_file(agent: &ureq::Agent, url: &Url) -> Result<Vec<u8>, RepoDownload
     Ok(bytes)
 }
 
-fn verify_checksum(path: &Path, checksum: &Checksum) -> Result<bool, RepoDownloadError> {
-    let reader = &mut BufReader::new(File::open(path).unwrap());
-
+fn checksum_matches<R: Read>(
+    reader: &mut R,
+    checksum: &Checksum,
+) -> Result<bool, RepoDownloadError> {
     let (expected_checksum, mut context) = match checksum {
         Checksum::Sha1(chk) => (chk, digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY)),
         Checksum::Sha256(chk) => (chk, digest::Context::new(&digest::SHA256)),
         Checksum::Sha384(chk) => (chk, digest::Context::new(&digest::SHA384)),
         Checksum::Sha512(chk) => (chk, digest::Context::new(&digest::SHA512)),
-        Checksum::Unknown => unreachable!(),
+        Checksum::Unknown => {
+            return Err(MetadataError::UnsupportedChecksumTypeError("unknown".to_owned()).into())
+        }
     };
     let mut buffer = [0; 4096];
 
@@ -303,7 +634,14 @@ fn verify_checksum(path: &Path, checksum: &Checksum) -> Result<bool, RepoDownloa
         context.update(&buffer[..count]);
     }
     let actual_checksum = context.finish();
-    Ok(actual_checksum.as_ref() == decode_hex(expected_checksum).unwrap())
+    // a value which isn't even valid hex can't match anything
+    if expected_checksum.len() % 2 != 0 || !expected_checksum.is_ascii() {
+        return Ok(false);
+    }
+    Ok(
+        decode_hex(expected_checksum)
+            .map_or(false, |expected| actual_checksum.as_ref() == expected),
+    )
 }
 
 pub fn decode_hex(s: &str) -> Result<Vec<u8>, std::num::ParseIntError> {
@@ -313,6 +651,18 @@ pub fn decode_hex(s: &str) -> Result<Vec<u8>, std::num::ParseIntError> {
         .collect()
 }
 
+/// Check that `path`, which was read from metadata served by the remote, is a relative path
+/// which stays inside of the directory it is relative to.
+fn check_relative_path(path: &str) -> Result<(), RepoDownloadError> {
+    let is_safe = Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !is_safe || path.is_empty() {
+        return Err(RepoDownloadError::UnsafePathError(path.to_owned()));
+    }
+    Ok(())
+}
+
 fn save_metadata_file(bytes: &[u8], path: &Path) -> Result<(), RepoDownloadError> {
     let prefix = path.parent().unwrap();
     std::fs::create_dir_all(prefix)?;
@@ -322,3 +672,20 @@ fn save_metadata_file(bytes: &[u8], path: &Path) -> Result<(), RepoDownloadError
         .expect("Failed to write bytes to file");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_relative_path() {
+        assert!(check_relative_path("images/boot.iso").is_ok());
+        assert!(check_relative_path("./BaseOS").is_ok());
+        assert!(check_relative_path(".").is_ok());
+
+        assert!(check_relative_path("").is_err());
+        assert!(check_relative_path("/etc/passwd").is_err());
+        assert!(check_relative_path("../outside").is_err());
+        assert!(check_relative_path("images/../../outside").is_err());
+    }
+}
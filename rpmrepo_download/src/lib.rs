@@ -1,3 +1,3 @@
-// mod download;
+mod download;
 
-// pub use download::{DownloadConfig, RepoDownloadError, RepoDownloader};
+pub use download::{DownloadConfig, DownloadSummary, RepoDownloadError, RepoDownloader};
@@ -0,0 +1,641 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+use super::metadata::{
+    Comps, CompsCategory, CompsEnvironment, CompsEnvironmentOption, CompsGroup, CompsLangpack,
+    CompsPackageReq, CompsPackageType, CompsXml, RpmMetadata,
+};
+use super::{utils, MetadataError, Repository};
+
+const TAG_COMPS: &[u8] = b"comps";
+const TAG_GROUP: &[u8] = b"group";
+const TAG_CATEGORY: &[u8] = b"category";
+const TAG_ENVIRONMENT: &[u8] = b"environment";
+const TAG_LANGPACKS: &[u8] = b"langpacks";
+const TAG_MATCH: &[u8] = b"match";
+const TAG_ID: &[u8] = b"id";
+const TAG_NAME: &[u8] = b"name";
+const TAG_DESCRIPTION: &[u8] = b"description";
+const TAG_DEFAULT: &[u8] = b"default";
+const TAG_USERVISIBLE: &[u8] = b"uservisible";
+const TAG_BIARCHONLY: &[u8] = b"biarchonly";
+const TAG_DISPLAY_ORDER: &[u8] = b"display_order";
+const TAG_LANGONLY: &[u8] = b"langonly";
+const TAG_PACKAGELIST: &[u8] = b"packagelist";
+const TAG_PACKAGEREQ: &[u8] = b"packagereq";
+const TAG_GROUPLIST: &[u8] = b"grouplist";
+const TAG_OPTIONLIST: &[u8] = b"optionlist";
+const TAG_GROUPID: &[u8] = b"groupid";
+
+// quick-xml writes the doctype content verbatim after "<!DOCTYPE"
+const COMPS_DOCTYPE: &str = r#" comps PUBLIC "-//Red Hat, Inc.//DTD Comps info//EN" "comps.dtd""#;
+
+impl RpmMetadata for CompsXml {
+    fn filename() -> &'static str {
+        "comps.xml"
+    }
+
+    fn load_metadata<R: BufRead>(
+        repository: &mut Repository,
+        reader: &mut Reader<R>,
+    ) -> Result<(), MetadataError> {
+        repository.comps = Some(read_comps_xml(reader)?);
+        Ok(())
+    }
+
+    fn write_metadata<W: Write>(
+        repository: &Repository,
+        writer: Writer<W>,
+    ) -> Result<(), MetadataError> {
+        let mut writer = CompsXml::new_writer(writer);
+        writer.write_header()?;
+
+        if let Some(comps) = &repository.comps {
+            for group in &comps.groups {
+                writer.write_group(group)?;
+            }
+            for category in &comps.categories {
+                writer.write_category(category)?;
+            }
+            for environment in &comps.environments {
+                writer.write_environment(environment)?;
+            }
+            writer.write_langpacks(&comps.langpacks)?;
+        }
+
+        writer.finish()
+    }
+}
+
+impl CompsXml {
+    pub fn new_writer<W: Write>(writer: Writer<W>) -> CompsXmlWriter<W> {
+        CompsXmlWriter { writer }
+    }
+}
+
+fn read_comps_xml<R: BufRead>(reader: &mut Reader<R>) -> Result<Comps, MetadataError> {
+    let mut comps = Comps::default();
+    let mut buf = Vec::new();
+    let mut found_metadata_tag = false;
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(e) => match e.name() {
+                TAG_COMPS => {
+                    found_metadata_tag = true;
+                }
+                TAG_GROUP => {
+                    comps.groups.push(parse_group(reader)?);
+                }
+                TAG_CATEGORY => {
+                    comps.categories.push(parse_category(reader)?);
+                }
+                TAG_ENVIRONMENT => {
+                    comps.environments.push(parse_environment(reader)?);
+                }
+                TAG_LANGPACKS => {
+                    comps.langpacks = parse_langpacks(reader)?;
+                }
+                _ => (),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    if !found_metadata_tag {
+        return Err(MetadataError::MissingHeaderError);
+    }
+    Ok(comps)
+}
+
+pub struct CompsXmlWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl<W: Write> CompsXmlWriter<W> {
+    pub fn write_header(&mut self) -> Result<(), MetadataError> {
+        // <?xml version="1.0" encoding="UTF-8"?>
+        self.writer
+            .write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+
+        // <!DOCTYPE comps PUBLIC "-//Red Hat, Inc.//DTD Comps info//EN" "comps.dtd">
+        self.writer
+            .write_event(Event::DocType(BytesText::from_escaped_str(COMPS_DOCTYPE)))?;
+
+        // <comps>
+        self.writer
+            .write_event(Event::Start(BytesStart::borrowed_name(TAG_COMPS)))?;
+
+        Ok(())
+    }
+
+    pub fn write_group(&mut self, group: &CompsGroup) -> Result<(), MetadataError> {
+        let writer = &mut self.writer;
+
+        // <group>
+        writer.write_event(Event::Start(BytesStart::borrowed_name(TAG_GROUP)))?;
+
+        // <id>core</id>
+        // <name>Core</name>
+        // <name xml:lang="de">Kern</name>
+        // <description>Smallest possible installation</description>
+        write_text_element(writer, TAG_ID, &group.id)?;
+        write_translated_elements(writer, TAG_NAME, &group.name, &group.name_by_lang)?;
+        write_translated_elements(
+            writer,
+            TAG_DESCRIPTION,
+            &group.description,
+            &group.desc_by_lang,
+        )?;
+
+        // <default>false</default>
+        // <uservisible>true</uservisible>
+        // <biarchonly>true</biarchonly> (maybe)
+        write_text_element(writer, TAG_DEFAULT, bool_to_str(group.default))?;
+        write_text_element(writer, TAG_USERVISIBLE, bool_to_str(group.uservisible))?;
+        if group.biarchonly {
+            write_text_element(writer, TAG_BIARCHONLY, bool_to_str(group.biarchonly))?;
+        }
+
+        // <display_order>1024</display_order> (maybe)
+        if let Some(display_order) = group.display_order {
+            write_text_element(writer, TAG_DISPLAY_ORDER, &display_order.to_string())?;
+        }
+
+        // <langonly>de</langonly> (maybe)
+        if let Some(langonly) = &group.langonly {
+            write_text_element(writer, TAG_LANGONLY, langonly)?;
+        }
+
+        // <packagelist>
+        let packagelist_tag = BytesStart::borrowed_name(TAG_PACKAGELIST);
+        writer.write_event(Event::Start(packagelist_tag.to_borrowed()))?;
+
+        for package in &group.packages {
+            // <packagereq type="conditional" requires="gimp">gimp-help</packagereq>
+            let mut packagereq_tag = BytesStart::borrowed_name(TAG_PACKAGEREQ);
+            packagereq_tag.push_attribute(("type", package.reqtype.to_values()));
+            if let Some(requires) = &package.requires {
+                packagereq_tag.push_attribute(("requires", requires.as_str()));
+            }
+            if package.basearchonly {
+                packagereq_tag.push_attribute(("basearchonly", "true"));
+            }
+            writer.write_event(Event::Start(packagereq_tag.to_borrowed()))?;
+            writer.write_event(Event::Text(BytesText::from_plain_str(&package.name)))?;
+            writer.write_event(Event::End(packagereq_tag.to_end()))?;
+        }
+
+        // </packagelist>
+        writer.write_event(Event::End(packagelist_tag.to_end()))?;
+
+        // </group>
+        writer.write_event(Event::End(BytesEnd::borrowed(TAG_GROUP)))?;
+
+        Ok(())
+    }
+
+    pub fn write_category(&mut self, category: &CompsCategory) -> Result<(), MetadataError> {
+        let writer = &mut self.writer;
+
+        // <category>
+        writer.write_event(Event::Start(BytesStart::borrowed_name(TAG_CATEGORY)))?;
+
+        write_text_element(writer, TAG_ID, &category.id)?;
+        write_translated_elements(writer, TAG_NAME, &category.name, &category.name_by_lang)?;
+        write_translated_elements(
+            writer,
+            TAG_DESCRIPTION,
+            &category.description,
+            &category.desc_by_lang,
+        )?;
+        if let Some(display_order) = category.display_order {
+            write_text_element(writer, TAG_DISPLAY_ORDER, &display_order.to_string())?;
+        }
+
+        // <grouplist>
+        //   <groupid>admin-tools</groupid>
+        // </grouplist>
+        write_grouplist(writer, TAG_GROUPLIST, &category.group_ids)?;
+
+        // </category>
+        writer.write_event(Event::End(BytesEnd::borrowed(TAG_CATEGORY)))?;
+
+        Ok(())
+    }
+
+    pub fn write_environment(
+        &mut self,
+        environment: &CompsEnvironment,
+    ) -> Result<(), MetadataError> {
+        let writer = &mut self.writer;
+
+        // <environment>
+        writer.write_event(Event::Start(BytesStart::borrowed_name(TAG_ENVIRONMENT)))?;
+
+        write_text_element(writer, TAG_ID, &environment.id)?;
+        write_translated_elements(
+            writer,
+            TAG_NAME,
+            &environment.name,
+            &environment.name_by_lang,
+        )?;
+        write_translated_elements(
+            writer,
+            TAG_DESCRIPTION,
+            &environment.description,
+            &environment.desc_by_lang,
+        )?;
+        if let Some(display_order) = environment.display_order {
+            write_text_element(writer, TAG_DISPLAY_ORDER, &display_order.to_string())?;
+        }
+
+        write_grouplist(writer, TAG_GROUPLIST, &environment.group_ids)?;
+
+        // <optionlist>
+        let optionlist_tag = BytesStart::borrowed_name(TAG_OPTIONLIST);
+        writer.write_event(Event::Start(optionlist_tag.to_borrowed()))?;
+
+        for option in &environment.options {
+            // <groupid default="true">mail-server</groupid>
+            let mut groupid_tag = BytesStart::borrowed_name(TAG_GROUPID);
+            if option.default {
+                groupid_tag.push_attribute(("default", "true"));
+            }
+            writer.write_event(Event::Start(groupid_tag.to_borrowed()))?;
+            writer.write_event(Event::Text(BytesText::from_plain_str(&option.group_id)))?;
+            writer.write_event(Event::End(groupid_tag.to_end()))?;
+        }
+
+        // </optionlist>
+        writer.write_event(Event::End(optionlist_tag.to_end()))?;
+
+        // </environment>
+        writer.write_event(Event::End(BytesEnd::borrowed(TAG_ENVIRONMENT)))?;
+
+        Ok(())
+    }
+
+    pub fn write_langpacks(&mut self, langpacks: &[CompsLangpack]) -> Result<(), MetadataError> {
+        if langpacks.is_empty() {
+            return Ok(());
+        }
+
+        // <langpacks>
+        let langpacks_tag = BytesStart::borrowed_name(TAG_LANGPACKS);
+        self.writer
+            .write_event(Event::Start(langpacks_tag.to_borrowed()))?;
+
+        for langpack in langpacks {
+            // <match install="aspell-%s" name="aspell"/>
+            self.writer
+                .create_element(TAG_MATCH)
+                .with_attribute(("install", langpack.install.as_str()))
+                .with_attribute(("name", langpack.name.as_str()))
+                .write_empty()?;
+        }
+
+        // </langpacks>
+        self.writer
+            .write_event(Event::End(langpacks_tag.to_end()))?;
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), MetadataError> {
+        // </comps>
+        self.writer
+            .write_event(Event::End(BytesEnd::borrowed(TAG_COMPS)))?;
+
+        // trailing newline
+        self.writer
+            .write_event(Event::Text(BytesText::from_plain_str("\n")))?;
+
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> Writer<W> {
+        self.writer
+    }
+}
+
+fn write_text_element<W: Write>(
+    writer: &mut Writer<W>,
+    tag: &[u8],
+    text: &str,
+) -> Result<(), MetadataError> {
+    writer
+        .create_element(tag)
+        .write_text_content(BytesText::from_plain_str(text))?;
+    Ok(())
+}
+
+// <name>Core</name>
+// <name xml:lang="de">Kern</name>
+fn write_translated_elements<W: Write>(
+    writer: &mut Writer<W>,
+    tag: &[u8],
+    text: &str,
+    translations: &BTreeMap<String, String>,
+) -> Result<(), MetadataError> {
+    write_text_element(writer, tag, text)?;
+    for (lang, translation) in translations {
+        writer
+            .create_element(tag)
+            .with_attribute(("xml:lang", lang.as_str()))
+            .write_text_content(BytesText::from_plain_str(translation))?;
+    }
+    Ok(())
+}
+
+fn write_grouplist<W: Write>(
+    writer: &mut Writer<W>,
+    tag: &[u8],
+    group_ids: &[String],
+) -> Result<(), MetadataError> {
+    let grouplist_tag = BytesStart::borrowed_name(tag);
+    writer.write_event(Event::Start(grouplist_tag.to_borrowed()))?;
+    for group_id in group_ids {
+        write_text_element(writer, TAG_GROUPID, group_id)?;
+    }
+    writer.write_event(Event::End(grouplist_tag.to_end()))?;
+    Ok(())
+}
+
+// <group>
+//   <id>core</id>
+//   <name>Core</name>
+//   ...
+//   <packagelist>
+//     <packagereq type="mandatory">bash</packagereq>
+//   </packagelist>
+// </group>
+fn parse_group<R: BufRead>(reader: &mut Reader<R>) -> Result<CompsGroup, MetadataError> {
+    let mut group = CompsGroup {
+        uservisible: true,
+        ..CompsGroup::default()
+    };
+    let mut buf = Vec::new();
+    let mut text_buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_GROUP => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_GROUP)),
+            Event::Start(e) => match e.name() {
+                TAG_ID => {
+                    group.id = reader.read_text(TAG_ID, &mut text_buf)?;
+                }
+                TAG_NAME => {
+                    parse_translated_text(reader, &e, &mut group.name, &mut group.name_by_lang)?;
+                }
+                TAG_DESCRIPTION => {
+                    parse_translated_text(
+                        reader,
+                        &e,
+                        &mut group.description,
+                        &mut group.desc_by_lang,
+                    )?;
+                }
+                TAG_DEFAULT => {
+                    group.default =
+                        utils::parse_bool(&reader.read_text(TAG_DEFAULT, &mut text_buf)?);
+                }
+                TAG_USERVISIBLE => {
+                    group.uservisible =
+                        utils::parse_bool(&reader.read_text(TAG_USERVISIBLE, &mut text_buf)?);
+                }
+                TAG_BIARCHONLY => {
+                    group.biarchonly =
+                        utils::parse_bool(&reader.read_text(TAG_BIARCHONLY, &mut text_buf)?);
+                }
+                TAG_DISPLAY_ORDER => {
+                    group.display_order = Some(
+                        reader
+                            .read_text(TAG_DISPLAY_ORDER, &mut text_buf)?
+                            .parse()?,
+                    );
+                }
+                TAG_LANGONLY => {
+                    group.langonly = Some(reader.read_text(TAG_LANGONLY, &mut text_buf)?);
+                }
+                TAG_PACKAGEREQ => {
+                    let reqtype = match utils::parse_optional_attribute(reader, &e, "type")? {
+                        Some(reqtype) => CompsPackageType::try_create(reqtype)?,
+                        None => CompsPackageType::default(),
+                    };
+                    let requires = utils::parse_optional_attribute(reader, &e, "requires")?;
+                    let basearchonly = utils::parse_optional_attribute(reader, &e, "basearchonly")?
+                        .is_some_and(|v| utils::parse_bool(&v));
+                    group.packages.push(CompsPackageReq {
+                        name: reader.read_text(TAG_PACKAGEREQ, &mut text_buf)?,
+                        reqtype,
+                        requires,
+                        basearchonly,
+                    });
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        buf.clear();
+        text_buf.clear();
+    }
+
+    Ok(group)
+}
+
+// <category>
+//   <id>development</id>
+//   <name>Development</name>
+//   ...
+//   <grouplist>
+//     <groupid>c-development</groupid>
+//   </grouplist>
+// </category>
+fn parse_category<R: BufRead>(reader: &mut Reader<R>) -> Result<CompsCategory, MetadataError> {
+    let mut category = CompsCategory::default();
+    let mut buf = Vec::new();
+    let mut text_buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_CATEGORY => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_CATEGORY)),
+            Event::Start(e) => match e.name() {
+                TAG_ID => {
+                    category.id = reader.read_text(TAG_ID, &mut text_buf)?;
+                }
+                TAG_NAME => {
+                    parse_translated_text(
+                        reader,
+                        &e,
+                        &mut category.name,
+                        &mut category.name_by_lang,
+                    )?;
+                }
+                TAG_DESCRIPTION => {
+                    parse_translated_text(
+                        reader,
+                        &e,
+                        &mut category.description,
+                        &mut category.desc_by_lang,
+                    )?;
+                }
+                TAG_DISPLAY_ORDER => {
+                    category.display_order = Some(
+                        reader
+                            .read_text(TAG_DISPLAY_ORDER, &mut text_buf)?
+                            .parse()?,
+                    );
+                }
+                TAG_GROUPID => {
+                    category
+                        .group_ids
+                        .push(reader.read_text(TAG_GROUPID, &mut text_buf)?);
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        buf.clear();
+        text_buf.clear();
+    }
+
+    Ok(category)
+}
+
+// <environment>
+//   <id>server-product-environment</id>
+//   <name>Fedora Server Edition</name>
+//   ...
+//   <grouplist>
+//     <groupid>core</groupid>
+//   </grouplist>
+//   <optionlist>
+//     <groupid default="true">mail-server</groupid>
+//   </optionlist>
+// </environment>
+fn parse_environment<R: BufRead>(
+    reader: &mut Reader<R>,
+) -> Result<CompsEnvironment, MetadataError> {
+    let mut environment = CompsEnvironment::default();
+    let mut buf = Vec::new();
+    let mut text_buf = Vec::new();
+    let mut in_optionlist = false;
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_ENVIRONMENT => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_ENVIRONMENT)),
+            Event::End(e) if e.name() == TAG_OPTIONLIST => in_optionlist = false,
+            Event::Start(e) => match e.name() {
+                TAG_ID => {
+                    environment.id = reader.read_text(TAG_ID, &mut text_buf)?;
+                }
+                TAG_NAME => {
+                    parse_translated_text(
+                        reader,
+                        &e,
+                        &mut environment.name,
+                        &mut environment.name_by_lang,
+                    )?;
+                }
+                TAG_DESCRIPTION => {
+                    parse_translated_text(
+                        reader,
+                        &e,
+                        &mut environment.description,
+                        &mut environment.desc_by_lang,
+                    )?;
+                }
+                TAG_DISPLAY_ORDER => {
+                    environment.display_order = Some(
+                        reader
+                            .read_text(TAG_DISPLAY_ORDER, &mut text_buf)?
+                            .parse()?,
+                    );
+                }
+                TAG_OPTIONLIST => in_optionlist = true,
+                TAG_GROUPID if in_optionlist => {
+                    let default = utils::parse_optional_attribute(reader, &e, "default")?
+                        .is_some_and(|v| utils::parse_bool(&v));
+                    environment.options.push(CompsEnvironmentOption {
+                        group_id: reader.read_text(TAG_GROUPID, &mut text_buf)?,
+                        default,
+                    });
+                }
+                TAG_GROUPID => {
+                    environment
+                        .group_ids
+                        .push(reader.read_text(TAG_GROUPID, &mut text_buf)?);
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        buf.clear();
+        text_buf.clear();
+    }
+
+    Ok(environment)
+}
+
+// <langpacks>
+//   <match install="aspell-%s" name="aspell"/>
+// </langpacks>
+fn parse_langpacks<R: BufRead>(
+    reader: &mut Reader<R>,
+) -> Result<Vec<CompsLangpack>, MetadataError> {
+    let mut langpacks = Vec::new();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_LANGPACKS => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_LANGPACKS)),
+            Event::Start(e) if e.name() == TAG_MATCH => {
+                langpacks.push(CompsLangpack {
+                    name: utils::parse_optional_attribute(reader, &e, "name")?.unwrap_or_default(),
+                    install: utils::parse_optional_attribute(reader, &e, "install")?
+                        .unwrap_or_default(),
+                });
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(langpacks)
+}
+
+/// Read the text of a <name> or <description> element, which is either the untranslated text or
+/// a translation if the element has an xml:lang attribute.
+fn parse_translated_text<R: BufRead>(
+    reader: &mut Reader<R>,
+    open_tag: &BytesStart,
+    text: &mut String,
+    translations: &mut BTreeMap<String, String>,
+) -> Result<(), MetadataError> {
+    let lang = utils::parse_optional_attribute(reader, open_tag, "xml:lang")?;
+    let value = reader.read_text(open_tag.name(), &mut Vec::new())?;
+    match lang {
+        Some(lang) => {
+            translations.insert(lang, value);
+        }
+        None => *text = value,
+    }
+    Ok(())
+}
+
+fn bool_to_str(value: bool) -> &'static str {
+    if value {
+        "true"
+    } else {
+        "false"
+    }
+}
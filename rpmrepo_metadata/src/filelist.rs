@@ -4,7 +4,7 @@ use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::{Reader, Writer};
 
 use super::metadata::{
-    FileType, FilelistsXml, Package, PackageFile, RpmMetadata, XML_NS_FILELISTS,
+    Checksum, FileType, FilelistsXml, Package, PackageFile, RpmMetadata, XML_NS_FILELISTS,
 };
 use super::{MetadataError, Repository, EVR};
 
@@ -47,14 +47,12 @@ impl FilelistsXml {
         }
     }
 
-    // pub fn new_reader<'a, R: BufRead>(reader: &'a mut Reader<R>) -> FilelistsXmlReader<'a, R> {
-    //     FilelistsXmlReader {
-    //         reader,
-    //         num_packages: 0,
-    //         packages_read: 0,
-    //         buffer: Vec::new(),
-    //     }
-    // }
+    pub fn new_reader<R: BufRead>(reader: Reader<R>) -> FilelistsXmlReader<R> {
+        FilelistsXmlReader {
+            reader,
+            buffer: Vec::new(),
+        }
+    }
 }
 
 pub struct FilelistsXmlWriter<W: Write> {
@@ -140,70 +138,112 @@ impl<W: Write> FilelistsXmlWriter<W> {
     pub fn into_inner(self) -> W {
         self.writer.into_inner()
     }
+
+    pub fn inner_mut(&mut self) -> &mut W {
+        self.writer.inner()
+    }
 }
 
-// pub struct FilelistsXmlReader<'a, R: BufRead> {
-//     reader: &'a mut Reader<R>,
-//     num_packages: usize,
-//     packages_read: usize,
-//     buffer: Vec<u8>,
-// }
-
-// impl<'a, R: BufRead> FilelistsXmlReader<'a, R> {
-//     pub fn read_header(&mut self) -> Result<(), MetadataError> {
-//         let mut found_metadata_tag = false;
-
-//         loop {
-//             match self.reader.read_event(&mut self.buffer)? {
-//                 Event::Start(e) => match e.name() {
-//                     TAG_FILELISTS => {
-//                         found_metadata_tag = true;
-//                         self.num_packages = e
-//                             .try_get_attribute("packages")?
-//                             .ok_or_else(|| MetadataError::MissingAttributeError("packages"))?
-//                             .unescape_and_decode_value(self.reader)?
-//                             .parse()?;
-//                     }
-//                     _ => (),
-//                 },
-//                 Event::Eof => break,
-//                 Event::Decl(_) => (),
-//                 _ => break,
-//             }
-//         }
-//         if !found_metadata_tag {
-//             return Err(MetadataError::MissingHeaderError)
-//         }
-
-//         self.buffer.clear();
-//         Ok(())
-//     }
-
-//     pub fn read_into_package(&mut self, package: &mut Package) -> Result<(), MetadataError> {
-//         loop {
-//             match self.reader.read_event(&mut self.buffer)? {
-//                 Event::Start(e) => match e.name() {
-//                     TAG_PACKAGE => {
-//                         found_metadata_tag = true;
-//                         self.num_packages = e
-//                             .try_get_attribute("packages")?
-//                             .ok_or_else(|| MetadataError::MissingAttributeError("packages"))?
-//                             .unescape_and_decode_value(self.reader)?
-//                             .parse()?;
-//                     }
-//                     _ => (),
-//                 },
-//                 Event::Eof => break,
-//                 Event::Decl(_) => (),
-//                 _ => break,
-//             }
-//         // TAG_PACKAGE => {
-//         //     self.current_element = Some(e)
-//         // }
-//         parse_package(repository, self.reader, &self.current_package_element);
-//         Ok(())
-//     }
-// }
+pub struct FilelistsXmlReader<R: BufRead> {
+    reader: Reader<R>,
+    buffer: Vec<u8>,
+}
+
+impl<R: BufRead> FilelistsXmlReader<R> {
+    /// Read the <filelists> tag and return the number of packages declared by it.
+    pub fn read_header(&mut self) -> Result<usize, MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_FILELISTS => {
+                    let num_packages = e
+                        .try_get_attribute("packages")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("packages"))?
+                        .unescape_and_decode_value(&self.reader)?
+                        .parse()?;
+                    self.buffer.clear();
+                    return Ok(num_packages);
+                }
+                Event::Eof => return Err(MetadataError::MissingHeaderError),
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+    }
+
+    /// Read the files of the next package into `package`.
+    ///
+    /// If `package` is `None`, a new package is created from the name and arch listed in filelists.xml.
+    /// Otherwise the pkgid of the next entry must match the checksum of `package`.
+    pub fn read_package(&mut self, package: &mut Option<Package>) -> Result<(), MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_PACKAGE => {
+                    let pkgid = e
+                        .try_get_attribute("pkgid")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
+                        .unescape_and_decode_value(&self.reader)?;
+
+                    let package = package.get_or_insert_with(Package::default);
+                    if *package.checksum() != Checksum::Unknown {
+                        let (_, expected_pkgid) = package.checksum().to_values()?;
+                        if expected_pkgid != pkgid {
+                            return Err(MetadataError::InconsistentMetadataError(format!(
+                                "filelists.xml entry {} does not match primary.xml entry {}",
+                                pkgid, expected_pkgid
+                            )));
+                        }
+                    }
+
+                    parse_package(package, &mut self.reader, &e)?;
+                    break;
+                }
+                Event::End(e) if e.name() != TAG_FILELISTS => (),
+                Event::End(_) | Event::Eof => {
+                    // running out of entries is only fine if no package was expected
+                    if let Some(package) = package {
+                        return Err(MetadataError::InconsistentMetadataError(format!(
+                            "filelists.xml has no entry for package {}",
+                            package.name()
+                        )));
+                    }
+                    break;
+                }
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Read the next package entry without matching it against a package from primary.xml.
+    ///
+    /// Returns the pkgid of the entry and a package populated only with the data present in filelists.xml.
+    pub fn read_entry(&mut self) -> Result<Option<(String, Package)>, MetadataError> {
+        let mut entry = None;
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_PACKAGE => {
+                    let pkgid = e
+                        .try_get_attribute("pkgid")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
+                        .unescape_and_decode_value(&self.reader)?;
+
+                    let mut package = Package::default();
+                    parse_package(&mut package, &mut self.reader, &e)?;
+                    entry = Some((pkgid, package));
+                    break;
+                }
+                Event::End(e) if e.name() == TAG_FILELISTS => break,
+                Event::Eof => break,
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+        self.buffer.clear();
+        Ok(entry)
+    }
+}
 
 // <?xml version="1.0" encoding="UTF-8"?>
 // <filelists xmlns="http://linux.duke.edu/metadata/filelists" packages="1">
@@ -229,7 +269,14 @@ fn read_filelists_xml<R: BufRead>(
                     found_metadata_tag = true;
                 }
                 TAG_PACKAGE => {
-                    parse_package(repository, reader, &e)?;
+                    let pkgid = e
+                        .try_get_attribute("pkgid")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
+                        .unescape_and_decode_value(reader)?;
+
+                    let package = repository.packages_mut().entry(pkgid).or_default();
+
+                    parse_package(package, reader, &e)?;
                 }
                 _ => (),
             },
@@ -251,16 +298,12 @@ fn read_filelists_xml<R: BufRead>(
 //     <file>/etc/fonts/conf.avail/10-autohint.conf</file>
 //   </package>
 pub fn parse_package<R: BufRead>(
-    repository: &mut Repository,
+    package: &mut Package,
     reader: &mut Reader<R>,
     open_tag: &BytesStart,
 ) -> Result<(), MetadataError> {
     let mut buf = Vec::new();
 
-    let pkgid = open_tag
-        .try_get_attribute("pkgid")?
-        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
-        .unescape_and_decode_value(reader)?;
     let name = open_tag
         .try_get_attribute("name")?
         .ok_or_else(|| MetadataError::MissingAttributeError("name"))?
@@ -270,11 +313,6 @@ pub fn parse_package<R: BufRead>(
         .ok_or_else(|| MetadataError::MissingAttributeError("arch"))?
         .unescape_and_decode_value(reader)?;
 
-    let package = repository
-        .packages_mut()
-        .entry(pkgid)
-        .or_insert(Package::default()); // TODO
-
     // TODO: using empty strings as null value is slightly questionable
     if package.name().is_empty() {
         package.set_name(&name);
@@ -287,6 +325,12 @@ pub fn parse_package<R: BufRead>(
     loop {
         match reader.read_event(&mut buf)? {
             Event::End(e) if e.name() == TAG_PACKAGE => break,
+            Event::Eof => {
+                return Err(MetadataError::InconsistentMetadataError(format!(
+                    "filelists.xml ended in the middle of the entry for package {}",
+                    name
+                )))
+            }
 
             Event::Start(e) => match e.name() {
                 TAG_VERSION => {
@@ -332,8 +376,10 @@ pub fn parse_file<R: BufRead>(
     reader: &mut Reader<R>,
     open_tag: &BytesStart,
 ) -> Result<PackageFile, MetadataError> {
-    let mut file = PackageFile::default();
-    file.path = reader.read_text(open_tag.name(), &mut Vec::new())?;
+    let mut file = PackageFile {
+        path: reader.read_text(open_tag.name(), &mut Vec::new())?,
+        ..PackageFile::default()
+    };
 
     if let Some(filetype) = open_tag.try_get_attribute("type")? {
         file.filetype = FileType::try_create(filetype.value.as_ref())?;
@@ -1,15 +1,52 @@
 mod common;
+mod comps;
 mod filelist;
 mod metadata;
+mod modules;
 mod other;
+mod prestodelta;
 mod primary;
 mod repomd;
 mod repository;
+mod signing;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod treeinfo;
 mod updateinfo;
+mod utils;
+mod verify;
+mod zchunk;
 
 pub use common::EVR;
+pub use comps::CompsXmlWriter;
+pub use filelist::FilelistsXmlWriter;
 pub use metadata::{
-    Checksum, ChecksumType, FileType, FilelistsXml, MetadataError, OtherXml, Package, PackageFile,
-    PrimaryXml, RepomdXml, Requirement, UpdateinfoXml,
+    Checksum, ChecksumType, CompressionType, Comps, CompsCategory, CompsEnvironment,
+    CompsEnvironmentOption, CompsGroup, CompsLangpack, CompsPackageReq, CompsPackageType, CompsXml,
+    Delta, DeltaPackage, FileType, FilelistsXml, MetadataError, OtherXml, Package, PackageFile,
+    PrestodeltaXml, PrimaryXml, RepoMdRecord, RepomdXml, Requirement, RequirementType,
+    UpdateCollection, UpdateCollectionModule, UpdateCollectionPackage, UpdateRecord,
+    UpdateReference, UpdateinfoXml,
 };
-pub use repository::{Repository, RepositoryOptions, RepositoryWriter};
+pub use modules::{
+    ModuleArtifacts, ModuleComponentModule, ModuleComponentRpm, ModuleComponents, ModuleDefaults,
+    ModuleDependencies, ModuleLicense, ModuleObsoletedBy, ModuleObsoletes, ModuleProfile,
+    ModuleReferences, ModuleRpmList, ModuleServiceLevel, ModuleStream, Modules,
+};
+pub use other::OtherXmlWriter;
+pub use prestodelta::PrestodeltaXmlWriter;
+pub use primary::PrimaryXmlWriter;
+pub use repository::{
+    PackageIterator, PackageMetadataSource, Repository, RepositoryLoadOptions, RepositoryOptions,
+    RepositoryWriter,
+};
+pub use signing::{
+    verify_package_signature, verify_signature, PackageSignaturePolicy, PackageSignatureStatus,
+    PackageSignatureSummary, SigningKey, VerifyingKey,
+};
+pub use treeinfo::{
+    Treeinfo, TreeinfoMedia, TreeinfoProduct, TreeinfoStage2, TreeinfoTree, TreeinfoVariant,
+};
+pub use updateinfo::UpdateinfoXmlWriter;
+pub use verify::{verify_repository, CorruptFile, RepositoryVerificationReport};
+pub use zchunk::{ZchunkReader, ZchunkWriter};
@@ -1,11 +1,16 @@
 use std::cmp::Ordering;
-use std::io::{BufRead, Write};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
 
 use niffler;
 use quick_xml;
 use quick_xml::{Reader, Writer};
 use thiserror::Error;
 
+use crate::utils;
+use crate::zchunk;
 use crate::{Repository, EVR};
 
 pub struct RepomdXml;
@@ -13,6 +18,8 @@ pub struct PrimaryXml;
 pub struct FilelistsXml;
 pub struct OtherXml;
 pub struct UpdateinfoXml;
+pub struct CompsXml;
+pub struct PrestodeltaXml;
 
 pub const METADATA_PRIMARY: &str = "primary";
 pub const METADATA_FILELISTS: &str = "filelists";
@@ -23,6 +30,11 @@ pub const METADATA_OTHER_DB: &str = "other_db";
 pub const METADATA_PRIMARY_ZCK: &str = "primary_zck";
 pub const METADATA_FILELISTS_ZCK: &str = "filelists_zck";
 pub const METADATA_OTHER_ZCK: &str = "other_zck";
+pub const METADATA_UPDATEINFO: &str = "updateinfo";
+pub const METADATA_GROUP: &str = "group";
+pub const METADATA_GROUP_GZ: &str = "group_gz";
+pub const METADATA_MODULES: &str = "modules";
+pub const METADATA_PRESTODELTA: &str = "prestodelta";
 
 #[derive(Error, Debug)]
 pub enum MetadataError {
@@ -44,6 +56,38 @@ pub enum MetadataError {
     MissingAttributeError(&'static str), // TODO: support multiple missing attributes?
     #[error("Missing metadata header")]
     MissingHeaderError,
+    #[error("repomd.xml has no {0} record")]
+    MissingRecordError(String),
+    #[error("Invalid .treeinfo: {0}")]
+    TreeinfoParseError(String),
+    #[error("Unknown comps package type {0}")]
+    UnknownCompsPackageTypeError(String),
+    #[error("Inconsistent metadata: {0}")]
+    InconsistentMetadataError(String),
+    #[error("More than {0} packages were buffered while matching out-of-order metadata")]
+    BufferLimitExceededError(usize),
+    #[error(transparent)]
+    YamlError(#[from] serde_yaml::Error),
+    #[error("Failed to read RPM package: {0}")]
+    RpmReadError(String),
+    #[error(transparent)]
+    PgpError(#[from] pgp::errors::Error),
+    #[error("Signature verification failed: {0}")]
+    SignatureVerificationError(String),
+    #[error("Checksum of {0} doesn't match repomd.xml")]
+    ChecksumMismatchError(String),
+    #[error("The package metadata of the repository isn't loaded")]
+    PackagesNotLoadedError,
+    #[cfg(feature = "sqlite")]
+    #[error(transparent)]
+    SqliteError(#[from] rusqlite::Error),
+}
+
+// rpm::RPMError can't be sent between threads, so only its message is kept
+impl From<rpm::RPMError> for MetadataError {
+    fn from(error: rpm::RPMError) -> Self {
+        MetadataError::RpmReadError(error.to_string())
+    }
 }
 
 /// Default namespace for primary.xml
@@ -79,6 +123,19 @@ pub enum CompressionType {
     Gzip,
     Xz,
     Bz2,
+    Zstd,
+}
+
+impl CompressionType {
+    pub fn to_file_extension(&self) -> &'static str {
+        match self {
+            CompressionType::None => "",
+            CompressionType::Gzip => ".gz",
+            CompressionType::Xz => ".xz",
+            CompressionType::Bz2 => ".bz2",
+            CompressionType::Zstd => ".zst",
+        }
+    }
 }
 
 impl TryInto<CompressionType> for &str {
@@ -89,12 +146,27 @@ impl TryInto<CompressionType> for &str {
             "gzip" => Ok(CompressionType::Gzip),
             "bz2" => Ok(CompressionType::Bz2),
             "xz" => Ok(CompressionType::Xz),
+            "zstd" => Ok(CompressionType::Zstd),
             "none" => Ok(CompressionType::None),
             _ => Err(MetadataError::UnsupportedChecksumTypeError(self.to_owned())),
         }
     }
 }
 
+impl TryInto<ChecksumType> for &str {
+    type Error = MetadataError;
+
+    fn try_into(self) -> Result<ChecksumType, Self::Error> {
+        match self {
+            "sha1" => Ok(ChecksumType::Sha1),
+            "sha256" => Ok(ChecksumType::Sha256),
+            "sha384" => Ok(ChecksumType::Sha384),
+            "sha512" => Ok(ChecksumType::Sha512),
+            _ => Err(MetadataError::UnsupportedChecksumTypeError(self.to_owned())),
+        }
+    }
+}
+
 // impl Ord for Package {
 //     #[inline]
 //     fn cmp(&self, other: &Package) -> Ordering {
@@ -137,6 +209,36 @@ pub struct Package {
 }
 
 impl Package {
+    /// Read a package from an RPM file.
+    ///
+    /// On top of the contents of the RPM header, this fills in the fields which depend on the
+    /// file itself: the package checksum (of type `checksum_type`), the package size and the file
+    /// modification time.
+    pub fn from_rpm_file(
+        path: &Path,
+        checksum_type: ChecksumType,
+        location_href: &str,
+    ) -> Result<Package, MetadataError> {
+        let (checksum, package_size) = utils::checksum_file(path, checksum_type)?;
+        let file_time = utils::file_mtime(path)?;
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let rpm_package = rpm::RPMPackage::parse(&mut reader)?;
+        let mut package: Package = rpm_package.try_into()?;
+
+        // <rpm:header-range start="4504" end="17328"/>
+        let mut reader = BufReader::new(File::open(path)?);
+        let (header_start, header_end) = utils::rpm_header_range(&mut reader)?;
+        package.set_rpm_header_range(header_start, header_end);
+
+        package.checksum = checksum;
+        package.location_href = location_href.to_owned();
+        package.time.file = file_time;
+        package.size.package = package_size;
+
+        Ok(package)
+    }
+
     pub fn new(
         name: &str,
         version: &EVR,
@@ -336,6 +438,33 @@ impl Package {
         &self.rpm_provides
     }
 
+    /// Whether this package satisfies `requirement`, either through one of its provides, the
+    /// implicit "name = EVR" provide every package has, or (for file requirements) one of its
+    /// files.
+    pub fn satisfies(&self, requirement: &Requirement) -> bool {
+        if requirement.name.starts_with('/')
+            && self.rpm_files.iter().any(|f| f.path == requirement.name)
+        {
+            return true;
+        }
+
+        let (epoch, version, release) = self.evr.values();
+        let self_provide = Requirement {
+            name: self.name.clone(),
+            flags: Some("EQ".to_owned()),
+            epoch: Some(epoch.to_owned()).filter(|e| !e.is_empty()),
+            version: Some(version.to_owned()),
+            release: Some(release.to_owned()),
+            ..Requirement::default()
+        };
+
+        requirement.is_satisfied_by(&self_provide)
+            || self
+                .rpm_provides
+                .iter()
+                .any(|provide| requirement.is_satisfied_by(provide))
+    }
+
     pub fn set_conflicts(&mut self, conflicts: Vec<Requirement>) -> &mut Self {
         self.rpm_conflicts = conflicts;
         self
@@ -441,50 +570,51 @@ pub enum ChecksumType {
     Unknown,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub enum Checksum {
     Sha1(String),
     Sha256(String),
     Sha384(String),
     Sha512(String),
+    #[default]
     Unknown,
 }
 
-impl Default for Checksum {
-    fn default() -> Self {
-        Checksum::Unknown
-    }
-}
-
 impl Checksum {
     pub fn try_create<N: AsRef<[u8]> + Sized>(
         checksum_type: N,
         checksum: N,
     ) -> Result<Self, MetadataError> {
-        let bytes_to_str = |value| std::str::from_utf8(value).unwrap().to_owned();
+        let bytes_to_str = |value: &[u8]| -> Result<String, MetadataError> {
+            Ok(std::str::from_utf8(value)?.to_owned())
+        };
 
         let checksum = match checksum_type.as_ref() {
-            b"sha" => Checksum::Sha1(bytes_to_str(checksum.as_ref())),
-            b"sha1" => Checksum::Sha1(bytes_to_str(checksum.as_ref())),
-            b"sha256" => Checksum::Sha256(bytes_to_str(checksum.as_ref())),
-            b"sha384" => Checksum::Sha384(bytes_to_str(checksum.as_ref())),
-            b"sha512" => Checksum::Sha512(bytes_to_str(checksum.as_ref())),
+            b"sha" => Checksum::Sha1(bytes_to_str(checksum.as_ref())?),
+            b"sha1" => Checksum::Sha1(bytes_to_str(checksum.as_ref())?),
+            b"sha256" => Checksum::Sha256(bytes_to_str(checksum.as_ref())?),
+            b"sha384" => Checksum::Sha384(bytes_to_str(checksum.as_ref())?),
+            b"sha512" => Checksum::Sha512(bytes_to_str(checksum.as_ref())?),
             _ => {
                 return Err(MetadataError::UnsupportedChecksumTypeError(bytes_to_str(
                     checksum_type.as_ref(),
-                )))
+                )?))
             }
         };
         Ok(checksum)
     }
 
-    pub fn to_values<'a>(&'a self) -> Result<(&str, &'a str), MetadataError> {
+    pub fn to_values(&self) -> Result<(&'static str, &str), MetadataError> {
         let values = match self {
             Checksum::Sha1(c) => ("sha1", c.as_str()),
             Checksum::Sha256(c) => ("sha256", c.as_str()),
             Checksum::Sha384(c) => ("sha384", c.as_str()),
             Checksum::Sha512(c) => ("sha512", c.as_str()),
-            Checksum::Unknown => panic!("Cannot take value of a checksum of unknown type"),
+            Checksum::Unknown => {
+                return Err(MetadataError::UnsupportedChecksumTypeError(
+                    "unknown".to_owned(),
+                ))
+            }
         };
         Ok(values)
     }
@@ -527,6 +657,71 @@ pub struct Requirement {
     pub preinstall: Option<bool>,
 }
 
+impl Requirement {
+    /// Whether the range of versions described by this requirement overlaps with the range
+    /// described by `provide`, following the semantics of rpm.
+    ///
+    /// Unversioned requirements are satisfied by any provide of the same name, and unversioned
+    /// provides satisfy any requirement of the same name. A missing epoch is treated as 0, and if
+    /// either side has no release, only the epoch and version are compared.
+    pub fn is_satisfied_by(&self, provide: &Requirement) -> bool {
+        if self.name != provide.name {
+            return false;
+        }
+
+        let (required_type, required_evr) = match (self.requirement_type(), self.evr()) {
+            (Some(rtype), Some(evr)) => (rtype, evr),
+            _ => return true,
+        };
+        let (provided_type, provided_evr) = match (provide.requirement_type(), provide.evr()) {
+            (Some(rtype), Some(evr)) => (rtype, evr),
+            _ => return true,
+        };
+
+        let ordering = if required_evr.release.is_empty() || provided_evr.release.is_empty() {
+            EVR::new(&provided_evr.epoch, &provided_evr.version, "").cmp(&EVR::new(
+                &required_evr.epoch,
+                &required_evr.version,
+                "",
+            ))
+        } else {
+            provided_evr.cmp(&required_evr)
+        };
+
+        match ordering {
+            Ordering::Less => provided_type.includes_greater() || required_type.includes_less(),
+            Ordering::Greater => provided_type.includes_less() || required_type.includes_greater(),
+            Ordering::Equal => {
+                (provided_type.includes_equal() && required_type.includes_equal())
+                    || (provided_type.includes_less() && required_type.includes_less())
+                    || (provided_type.includes_greater() && required_type.includes_greater())
+            }
+        }
+    }
+
+    /// The comparison operator, or `None` if the requirement is unversioned
+    fn requirement_type(&self) -> Option<RequirementType> {
+        match self.flags.as_deref()? {
+            "LT" => Some(RequirementType::LT),
+            "GT" => Some(RequirementType::GT),
+            "EQ" => Some(RequirementType::EQ),
+            "LE" => Some(RequirementType::LE),
+            "GE" => Some(RequirementType::GE),
+            _ => None,
+        }
+    }
+
+    fn evr(&self) -> Option<EVR> {
+        let version = self.version.as_deref().filter(|v| !v.is_empty())?;
+        Some(EVR::new(
+            self.epoch.as_deref().unwrap_or("0"),
+            version,
+            self.release.as_deref().unwrap_or_default(),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RequirementType {
     LT,
     GT,
@@ -535,6 +730,23 @@ pub enum RequirementType {
     GE,
 }
 
+impl RequirementType {
+    fn includes_less(self) -> bool {
+        matches!(self, RequirementType::LT | RequirementType::LE)
+    }
+
+    fn includes_equal(self) -> bool {
+        matches!(
+            self,
+            RequirementType::EQ | RequirementType::LE | RequirementType::GE
+        )
+    }
+
+    fn includes_greater(self) -> bool {
+        matches!(self, RequirementType::GT | RequirementType::GE)
+    }
+}
+
 impl From<RequirementType> for &str {
     fn from(rtype: RequirementType) -> &'static str {
         match rtype {
@@ -547,8 +759,9 @@ impl From<RequirementType> for &str {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
 pub enum FileType {
+    #[default]
     File,
     Dir,
     Ghost,
@@ -575,12 +788,6 @@ impl FileType {
     }
 }
 
-impl Default for FileType {
-    fn default() -> Self {
-        FileType::File
-    }
-}
-
 #[derive(Debug, PartialEq, Default)]
 pub struct PackageFile {
     pub filetype: FileType,
@@ -601,6 +808,15 @@ pub enum MetadataType {
     FilelistsDb,
     OtherDb,
 
+    Updateinfo,
+
+    Group,
+    GroupGz,
+
+    Modules,
+
+    Prestodelta,
+
     Unknown,
 }
 
@@ -619,6 +835,15 @@ impl From<&str> for MetadataType {
             METADATA_FILELISTS_ZCK => MetadataType::FilelistsZck,
             METADATA_OTHER_ZCK => MetadataType::OtherZck,
 
+            METADATA_UPDATEINFO => MetadataType::Updateinfo,
+
+            METADATA_GROUP => MetadataType::Group,
+            METADATA_GROUP_GZ => MetadataType::GroupGz,
+
+            METADATA_MODULES => MetadataType::Modules,
+
+            METADATA_PRESTODELTA => MetadataType::Prestodelta,
+
             _ => MetadataType::Unknown,
         }
     }
@@ -664,6 +889,39 @@ pub struct RepoMdRecord {
     pub database_version: Option<u32>,
 }
 
+impl RepoMdRecord {
+    /// Create a record describing a metadata file in the repodata/ directory of a repository.
+    ///
+    /// The open-checksum and open-size are only provided if the file is compressed, the
+    /// header-checksum and header-size only if it is a zchunk file.
+    pub fn new(
+        mdtype: &str,
+        path: &Path,
+        checksum_type: ChecksumType,
+    ) -> Result<Self, MetadataError> {
+        let filename = utils::file_name(path)?;
+        let (checksum, size) = utils::checksum_file(path, checksum_type)?;
+        let open_values = utils::checksum_file_contents(path, checksum_type)?;
+        let header_values = match zchunk::read_header(path)? {
+            Some(header) => Some(utils::checksum_bytes(&header, checksum_type)?),
+            None => None,
+        };
+
+        Ok(RepoMdRecord {
+            mdtype: mdtype.to_owned(),
+            location_href: format!("repodata/{}", filename),
+            timestamp: utils::file_mtime(path)?,
+            size: Some(size),
+            checksum,
+            open_size: open_values.as_ref().map(|(_, size)| *size),
+            open_checksum: open_values.map(|(checksum, _)| checksum),
+            header_size: header_values.as_ref().map(|(_, size)| *size),
+            header_checksum: header_values.map(|(checksum, _)| checksum),
+            ..RepoMdRecord::default()
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Default)]
 pub struct UpdateRecord {
     pub from: String,
@@ -680,7 +938,7 @@ pub struct UpdateRecord {
     pub severity: String,
     pub summary: String,
     pub description: String,
-    pub solution: String,
+    pub solution: Option<String>,
     pub reboot_suggested: bool,
     pub references: Vec<UpdateReference>,
     pub pkglist: Vec<UpdateCollection>,
@@ -690,6 +948,7 @@ pub struct UpdateRecord {
 pub struct UpdateCollection {
     pub name: String,
     pub shortname: String,
+    pub module: Option<UpdateCollectionModule>,
     pub packages: Vec<UpdateCollectionPackage>,
 }
 
@@ -703,6 +962,7 @@ pub struct UpdateReference {
 
 #[derive(Debug, PartialEq, Default)]
 pub struct UpdateCollectionPackage {
+    pub arch: String,
     pub epoch: u32,
     pub filename: String,
     pub name: String,
@@ -724,51 +984,457 @@ pub struct UpdateCollectionModule {
     pub arch: String,
 }
 
-use rpm::{self, Header};
+/// Package groups, categories, environments and langpacks (comps.xml).
+#[derive(Debug, PartialEq, Default)]
+pub struct Comps {
+    pub groups: Vec<CompsGroup>,
+    pub categories: Vec<CompsCategory>,
+    pub environments: Vec<CompsEnvironment>,
+    pub langpacks: Vec<CompsLangpack>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CompsGroup {
+    pub id: String,
+    pub name: String,
+    pub name_by_lang: BTreeMap<String, String>,
+    pub description: String,
+    pub desc_by_lang: BTreeMap<String, String>,
+    pub default: bool,
+    pub uservisible: bool,
+    pub biarchonly: bool,
+    pub display_order: Option<u32>,
+    pub langonly: Option<String>,
+    pub packages: Vec<CompsPackageReq>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum CompsPackageType {
+    #[default]
+    Mandatory,
+    Default,
+    Optional,
+    Conditional,
+}
+
+impl CompsPackageType {
+    pub fn try_create<N: AsRef<[u8]> + Sized>(val: N) -> Result<Self, MetadataError> {
+        let reqtype = match val.as_ref() {
+            b"mandatory" => CompsPackageType::Mandatory,
+            b"default" => CompsPackageType::Default,
+            b"optional" => CompsPackageType::Optional,
+            b"conditional" => CompsPackageType::Conditional,
+            t => {
+                return Err(MetadataError::UnknownCompsPackageTypeError(
+                    String::from_utf8_lossy(t).into_owned(),
+                ))
+            }
+        };
+        Ok(reqtype)
+    }
+
+    pub fn to_values(&self) -> &str {
+        match self {
+            CompsPackageType::Mandatory => "mandatory",
+            CompsPackageType::Default => "default",
+            CompsPackageType::Optional => "optional",
+            CompsPackageType::Conditional => "conditional",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CompsPackageReq {
+    pub name: String,
+    pub reqtype: CompsPackageType,
+    /// For conditional packages, the package which triggers the installation
+    pub requires: Option<String>,
+    pub basearchonly: bool,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CompsCategory {
+    pub id: String,
+    pub name: String,
+    pub name_by_lang: BTreeMap<String, String>,
+    pub description: String,
+    pub desc_by_lang: BTreeMap<String, String>,
+    pub display_order: Option<u32>,
+    pub group_ids: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CompsEnvironment {
+    pub id: String,
+    pub name: String,
+    pub name_by_lang: BTreeMap<String, String>,
+    pub description: String,
+    pub desc_by_lang: BTreeMap<String, String>,
+    pub display_order: Option<u32>,
+    pub group_ids: Vec<String>,
+    pub options: Vec<CompsEnvironmentOption>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CompsEnvironmentOption {
+    pub group_id: String,
+    pub default: bool,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CompsLangpack {
+    pub name: String,
+    pub install: String,
+}
+
+/// The deltarpms which upgrade older versions of a package to this one (prestodelta.xml).
+#[derive(Debug, PartialEq, Default)]
+pub struct DeltaPackage {
+    pub name: String,
+    pub arch: String,
+    pub evr: EVR,
+    pub deltas: Vec<Delta>,
+}
+
+/// A deltarpm from `old_evr` of the package to the `evr` of its `DeltaPackage`.
+#[derive(Debug, PartialEq, Default)]
+pub struct Delta {
+    pub old_evr: EVR,
+    /// Location of the .drpm relative to the repository
+    pub filename: String,
+    /// Sequence used by applydeltarpm to check that the old package can be reconstructed
+    pub sequence: String,
+    pub size: u64,
+    pub checksum: Checksum,
+}
+
+use rpm::{self, Header, IndexSignatureTag, IndexTag};
 use std::convert::TryInto;
 
+// Dependency flags, see rpmds.h
+const RPMSENSE_LESS: i32 = 1 << 1;
+const RPMSENSE_GREATER: i32 = 1 << 2;
+const RPMSENSE_EQUAL: i32 = 1 << 3;
+const RPMSENSE_PREREQ: i32 = 1 << 6;
+const RPMSENSE_SCRIPT_PRE: i32 = 1 << 9;
+const RPMSENSE_SCRIPT_POST: i32 = 1 << 10;
+
+// File flags, see rpmfiles.h
+const RPMFILE_GHOST: i32 = 1 << 6;
+
+// File type bits of st_mode
+const S_IFMT: u16 = 0o170000;
+const S_IFDIR: u16 = 0o040000;
+
 impl TryInto<Package> for rpm::RPMPackage {
-    type Error = rpm::RPMError;
+    type Error = MetadataError;
 
     fn try_into(self) -> Result<Package, Self::Error> {
-        let pkg = Package {
-            name: self.metadata.header.get_name()?.to_owned(),
-            arch: self.metadata.header.get_arch()?.to_owned(),
-            evr: {
-                let epoch = self.metadata.header.get_epoch()?.to_string(); // TODO evaluate epoch type
-                let version = self.metadata.header.get_version()?;
-                let release = self.metadata.header.get_release()?;
-                EVR::new(epoch.as_str(), version, release)
-            },
-            checksum: todo!(),
-            location_href: todo!(),
-            summary: todo!(),
-            description: todo!(),
-            packager: todo!(),
-            url: todo!(),
-            time: todo!(),
-            size: todo!(),
-
-            rpm_license: todo!(),
-            rpm_vendor: todo!(),
-            rpm_group: todo!(),
-            rpm_buildhost: todo!(),
-            rpm_sourcerpm: todo!(),
-            rpm_header_range: todo!(),
-
-            rpm_requires: todo!(),
-            rpm_provides: todo!(),
-            rpm_conflicts: todo!(),
-            rpm_obsoletes: todo!(),
-            rpm_suggests: todo!(),
-            rpm_enhances: todo!(),
-            rpm_recommends: todo!(),
-            rpm_supplements: todo!(),
-
-            rpm_changelogs: todo!(),
-            rpm_files: todo!(),
+        let header = &self.metadata.header;
+        let signature = &self.metadata.signature;
+
+        let mut pkg = Package::default();
+
+        let epoch = optional(header.get_entry_int32_data(IndexTag::RPMTAG_EPOCH))?.unwrap_or(0);
+        pkg.set_name(header.get_name()?)
+            .set_arch(package_arch(header)?)
+            .set_evr(EVR::new(
+                &epoch.to_string(),
+                header.get_version()?,
+                header.get_release()?,
+            ));
+
+        pkg.set_summary(&optional_i18n_string(header, IndexTag::RPMTAG_SUMMARY)?)
+            .set_description(&optional_i18n_string(header, IndexTag::RPMTAG_DESCRIPTION)?)
+            .set_packager(&optional_string(header, IndexTag::RPMTAG_PACKAGER)?)
+            .set_url(&optional_string(header, IndexTag::RPMTAG_URL)?)
+            .set_rpm_license(&optional_string(header, IndexTag::RPMTAG_LICENSE)?)
+            .set_rpm_vendor(&optional_string(header, IndexTag::RPMTAG_VENDOR)?)
+            .set_rpm_group(&optional_i18n_string(header, IndexTag::RPMTAG_GROUP)?)
+            .set_rpm_buildhost(&optional_string(header, IndexTag::RPMTAG_BUILDHOST)?)
+            .set_rpm_sourcerpm(&optional_string(header, IndexTag::RPMTAG_SOURCERPM)?);
+
+        // The file time, package size and header range depend on the file itself, see
+        // Package::from_rpm_file()
+        let build_time = optional(header.get_entry_int32_data(IndexTag::RPMTAG_BUILDTIME))?;
+        pkg.set_time(0, build_time.unwrap_or(0) as u64);
+
+        let installed_size =
+            match optional(header.get_entry_int64_data(IndexTag::RPMTAG_LONGSIZE))? {
+                Some(size) => size as u64,
+                None => optional(header.get_entry_int32_data(IndexTag::RPMTAG_SIZE))?.unwrap_or(0)
+                    as u64,
+            };
+        let archive_size = match optional(
+            signature.get_entry_int64_data(IndexSignatureTag::RPMSIGTAG_LONGARCHIVESIZE),
+        )? {
+            Some(size) => size as u64,
+            None => {
+                optional(signature.get_entry_int32_data(IndexSignatureTag::RPMSIGTAG_PAYLOADSIZE))?
+                    .unwrap_or(0) as u64
+            }
         };
+        pkg.set_size(0, installed_size, archive_size);
+
+        let files = package_files(header)?;
+
+        let mut requires = package_dependencies(
+            header,
+            IndexTag::RPMTAG_REQUIRENAME,
+            IndexTag::RPMTAG_REQUIREFLAGS,
+            IndexTag::RPMTAG_REQUIREVERSION,
+            true,
+        )?;
+        // Requirements on rpmlib features and on files within the package itself are satisfied
+        // by definition, createrepo_c leaves them out.
+        let file_paths: HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        requires
+            .retain(|r| !r.name.starts_with("rpmlib(") && !file_paths.contains(r.name.as_str()));
+        pkg.set_requires(requires);
+
+        pkg.set_provides(package_dependencies(
+            header,
+            IndexTag::RPMTAG_PROVIDENAME,
+            IndexTag::RPMTAG_PROVIDEFLAGS,
+            IndexTag::RPMTAG_PROVIDEVERSION,
+            false,
+        )?)
+        .set_conflicts(package_dependencies(
+            header,
+            IndexTag::RPMTAG_CONFLICTNAME,
+            IndexTag::RPMTAG_CONFLICTFLAGS,
+            IndexTag::RPMTAG_CONFLICTVERSION,
+            false,
+        )?)
+        .set_obsoletes(package_dependencies(
+            header,
+            IndexTag::RPMTAG_OBSOLETENAME,
+            IndexTag::RPMTAG_OBSOLETEFLAGS,
+            IndexTag::RPMTAG_OBSOLETEVERSION,
+            false,
+        )?)
+        .set_suggests(package_dependencies(
+            header,
+            IndexTag::RPMTAG_SUGGESTNAME,
+            IndexTag::RPMTAG_SUGGESTFLAGS,
+            IndexTag::RPMTAG_SUGGESTVERSION,
+            false,
+        )?)
+        .set_enhances(package_dependencies(
+            header,
+            IndexTag::RPMTAG_ENHANCENAME,
+            IndexTag::RPMTAG_ENHANCEFLAGS,
+            IndexTag::RPMTAG_ENHANCEVERSION,
+            false,
+        )?)
+        .set_recommends(package_dependencies(
+            header,
+            IndexTag::RPMTAG_RECOMMENDNAME,
+            IndexTag::RPMTAG_RECOMMENDFLAGS,
+            IndexTag::RPMTAG_RECOMMENDVERSION,
+            false,
+        )?)
+        .set_supplements(package_dependencies(
+            header,
+            IndexTag::RPMTAG_SUPPLEMENTNAME,
+            IndexTag::RPMTAG_SUPPLEMENTFLAGS,
+            IndexTag::RPMTAG_SUPPLEMENTVERSION,
+            false,
+        )?);
+
+        pkg.rpm_files = files;
+        pkg.rpm_changelogs = package_changelogs(header)?;
 
         Ok(pkg)
     }
 }
+
+/// Map a missing tag to `None`, since most tags are not mandatory.
+fn optional<T>(result: Result<T, rpm::RPMError>) -> Result<Option<T>, rpm::RPMError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(rpm::RPMError::TagNotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn optional_string(header: &Header<IndexTag>, tag: IndexTag) -> Result<String, rpm::RPMError> {
+    let value = optional(header.get_entry_string_data(tag))?;
+    Ok(value.unwrap_or_default().to_owned())
+}
+
+// summary, description and group are stored as i18n strings
+fn optional_i18n_string(header: &Header<IndexTag>, tag: IndexTag) -> Result<String, rpm::RPMError> {
+    let value = optional(header.get_entry_i18n_string_data(tag))?;
+    Ok(value.unwrap_or_default().to_owned())
+}
+
+fn package_arch(header: &Header<IndexTag>) -> Result<&str, rpm::RPMError> {
+    // Source packages don't have a SOURCERPM tag, and are listed with the "src" arch (or "nosrc"
+    // if some of the sources aren't included)
+    if optional(header.get_entry_string_data(IndexTag::RPMTAG_SOURCERPM))?.is_some() {
+        return header.get_arch();
+    }
+    let nosource = optional(header.get_entry_int32_array_data(IndexTag::RPMTAG_NOSOURCE))?;
+    let nopatch = optional(header.get_entry_int32_array_data(IndexTag::RPMTAG_NOPATCH))?;
+    if nosource.is_some() || nopatch.is_some() {
+        Ok("nosrc")
+    } else {
+        Ok("src")
+    }
+}
+
+fn package_dependencies(
+    header: &Header<IndexTag>,
+    name_tag: IndexTag,
+    flags_tag: IndexTag,
+    version_tag: IndexTag,
+    track_preinstall: bool,
+) -> Result<Vec<Requirement>, rpm::RPMError> {
+    let names = match optional(header.get_entry_string_array_data(name_tag))? {
+        Some(names) => names,
+        None => return Ok(Vec::new()),
+    };
+    let flags = optional(header.get_entry_int32_array_data(flags_tag))?.unwrap_or_default();
+    let versions = optional(header.get_entry_string_array_data(version_tag))?.unwrap_or_default();
+
+    let mut requirements: Vec<Requirement> = Vec::with_capacity(names.len());
+    for (idx, name) in names.iter().enumerate() {
+        let flags = flags.get(idx).copied().unwrap_or(0);
+        let version = versions.get(idx).map(|v| v.as_str()).unwrap_or("");
+
+        let flags_str = match flags & (RPMSENSE_LESS | RPMSENSE_GREATER | RPMSENSE_EQUAL) {
+            f if f == RPMSENSE_LESS => Some("LT"),
+            f if f == RPMSENSE_GREATER => Some("GT"),
+            f if f == RPMSENSE_EQUAL => Some("EQ"),
+            f if f == RPMSENSE_LESS | RPMSENSE_EQUAL => Some("LE"),
+            f if f == RPMSENSE_GREATER | RPMSENSE_EQUAL => Some("GE"),
+            _ => None,
+        };
+
+        let (epoch, version, release) = if version.is_empty() {
+            (None, None, None)
+        } else {
+            let (epoch, vr) = match version.split_once(':') {
+                Some((epoch, vr)) => (epoch, vr),
+                None => ("0", version),
+            };
+            let (version, release) = match vr.split_once('-') {
+                Some((version, release)) => (version, Some(release.to_owned())),
+                None => (vr, None),
+            };
+            (Some(epoch.to_owned()), Some(version.to_owned()), release)
+        };
+
+        let requirement = Requirement {
+            name: name.to_owned(),
+            flags: flags_str.map(|f| f.to_owned()),
+            epoch,
+            version,
+            release,
+            preinstall: if track_preinstall
+                && flags & (RPMSENSE_PREREQ | RPMSENSE_SCRIPT_PRE | RPMSENSE_SCRIPT_POST) != 0
+            {
+                Some(true)
+            } else {
+                None
+            },
+        };
+
+        // the same dependency is often listed multiple times, e.g. once for each scriptlet
+        if let Some(existing) = requirements.iter_mut().find(|r| {
+            r.name == requirement.name
+                && r.flags == requirement.flags
+                && r.epoch == requirement.epoch
+                && r.version == requirement.version
+                && r.release == requirement.release
+        }) {
+            if requirement.preinstall == Some(true) {
+                existing.preinstall = Some(true);
+            }
+            continue;
+        }
+        requirements.push(requirement);
+    }
+
+    Ok(requirements)
+}
+
+fn package_files(header: &Header<IndexTag>) -> Result<Vec<PackageFile>, MetadataError> {
+    let basenames = match optional(header.get_entry_string_array_data(IndexTag::RPMTAG_BASENAMES))?
+    {
+        Some(basenames) => basenames,
+        None => return Ok(Vec::new()),
+    };
+    let dirnames = header.get_entry_string_array_data(IndexTag::RPMTAG_DIRNAMES)?;
+    let dirindexes = header.get_entry_int32_array_data(IndexTag::RPMTAG_DIRINDEXES)?;
+    let fileflags = optional(header.get_entry_int32_array_data(IndexTag::RPMTAG_FILEFLAGS))?
+        .unwrap_or_default();
+    let filemodes = optional(header.get_entry_int16_array_data(IndexTag::RPMTAG_FILEMODES))?
+        .unwrap_or_default();
+
+    if dirindexes.len() != basenames.len()
+        || (!fileflags.is_empty() && fileflags.len() != basenames.len())
+        || (!filemodes.is_empty() && filemodes.len() != basenames.len())
+    {
+        return Err(MetadataError::RpmReadError(
+            "file entries of the header have mismatched lengths".to_owned(),
+        ));
+    }
+
+    let mut files = Vec::with_capacity(basenames.len());
+    for (idx, basename) in basenames.iter().enumerate() {
+        let dirname = dirnames.get(dirindexes[idx] as usize).ok_or_else(|| {
+            MetadataError::RpmReadError(format!(
+                "file {} has an invalid directory index {}",
+                basename, dirindexes[idx]
+            ))
+        })?;
+
+        let filetype = if fileflags.get(idx).copied().unwrap_or(0) & RPMFILE_GHOST != 0 {
+            FileType::Ghost
+        } else if filemodes.get(idx).copied().unwrap_or(0) & S_IFMT == S_IFDIR {
+            FileType::Dir
+        } else {
+            FileType::File
+        };
+
+        files.push(PackageFile {
+            filetype,
+            path: format!("{}{}", dirname, basename),
+        });
+    }
+
+    Ok(files)
+}
+
+fn package_changelogs(header: &Header<IndexTag>) -> Result<Vec<Changelog>, MetadataError> {
+    let authors =
+        match optional(header.get_entry_string_array_data(IndexTag::RPMTAG_CHANGELOGNAME))? {
+            Some(authors) => authors,
+            None => return Ok(Vec::new()),
+        };
+    let times = header.get_entry_int32_array_data(IndexTag::RPMTAG_CHANGELOGTIME)?;
+    let texts = header.get_entry_string_array_data(IndexTag::RPMTAG_CHANGELOGTEXT)?;
+
+    if times.len() != authors.len() || texts.len() != authors.len() {
+        return Err(MetadataError::RpmReadError(
+            "changelog entries of the header have mismatched lengths".to_owned(),
+        ));
+    }
+
+    // The header lists the newest entries first, but other.xml lists them oldest first
+    let changelogs = authors
+        .iter()
+        .zip(times.iter())
+        .zip(texts.iter())
+        .rev()
+        .map(|((author, time), text)| Changelog {
+            author: author.to_owned(),
+            date: *time as u64,
+            description: text.to_owned(),
+        })
+        .collect();
+
+    Ok(changelogs)
+}
@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::MetadataError;
+
+const DOCUMENT_MODULEMD: &str = "modulemd";
+const DOCUMENT_MODULEMD_DEFAULTS: &str = "modulemd-defaults";
+const DOCUMENT_MODULEMD_OBSOLETES: &str = "modulemd-obsoletes";
+
+const MODULEMD_VERSION: u32 = 2;
+const MODULEMD_DEFAULTS_VERSION: u32 = 1;
+const MODULEMD_OBSOLETES_VERSION: u32 = 1;
+
+/// Modularity metadata (modules.yaml).
+///
+/// modules.yaml is a stream of YAML documents, each of which is tagged with its document type
+/// and version. Module streams, defaults and obsoletes are parsed into typed structs, any other
+/// documents (such as translations) are preserved as-is.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Modules {
+    pub streams: Vec<ModuleStream>,
+    pub defaults: Vec<ModuleDefaults>,
+    pub obsoletes: Vec<ModuleObsoletes>,
+    pub other_documents: Vec<serde_yaml::Value>,
+}
+
+/// A module stream (a "modulemd" version 2 document).
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleStream {
+    pub name: String,
+    pub stream: String,
+    pub version: u64,
+    pub context: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    pub summary: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub servicelevels: BTreeMap<String, ModuleServiceLevel>,
+    /// Deprecated in favour of the "rawhide" service level, but still found in older documents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eol: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub static_context: bool,
+    #[serde(default)]
+    pub license: ModuleLicense,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xmd: Option<serde_yaml::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<ModuleDependencies>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub references: Option<ModuleReferences>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ModuleProfile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api: Option<ModuleRpmList>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<ModuleRpmList>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub demodularized: Option<ModuleRpmList>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buildopts: Option<serde_yaml::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub components: Option<ModuleComponents>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<ModuleArtifacts>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleServiceLevel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eol: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleLicense {
+    #[serde(default)]
+    pub module: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content: Vec<String>,
+}
+
+/// Build-time and run-time module dependencies, mapping module names to lists of streams.
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleDependencies {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub buildrequires: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub requires: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleReferences {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub community: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub documentation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracker: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub rpms: Vec<String>,
+}
+
+/// A list of packages, used for the API, filter and demodularized packages of a module stream.
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleRpmList {
+    #[serde(default)]
+    pub rpms: Vec<String>,
+}
+
+/// The packages built for a module stream.
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleArtifacts {
+    #[serde(default)]
+    pub rpms: Vec<String>,
+    /// Details of each artifact by checksum type and checksum, kept as-is
+    #[serde(rename = "rpm-map", default, skip_serializing_if = "Option::is_none")]
+    pub rpm_map: Option<serde_yaml::Value>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleComponents {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rpms: BTreeMap<String, ModuleComponentRpm>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, ModuleComponentModule>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleComponentRpm {
+    pub rationale: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<String>,
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buildorder: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buildafter: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub buildonly: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub buildroot: bool,
+    #[serde(
+        rename = "srpm-buildroot",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub srpm_buildroot: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arches: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub multilib: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleComponentModule {
+    pub rationale: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buildorder: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buildafter: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub buildonly: bool,
+}
+
+/// The default stream and profiles of a module (a "modulemd-defaults" version 1 document).
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleDefaults {
+    pub module: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intents: Option<serde_yaml::Value>,
+}
+
+/// The end of life of a module stream, and optionally its replacement (a "modulemd-obsoletes"
+/// version 1 document).
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleObsoletes {
+    pub modified: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reset: bool,
+    pub module: String,
+    pub stream: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eol_date: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obsoleted_by: Option<ModuleObsoletedBy>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone, Serialize, Deserialize)]
+pub struct ModuleObsoletedBy {
+    pub module: String,
+    pub stream: String,
+}
+
+#[derive(Serialize)]
+struct Document<T> {
+    document: String,
+    version: u32,
+    data: T,
+}
+
+/// A document of any type, with its data parsed according to the document type and version.
+enum ParsedDocument {
+    Stream(Box<ModuleStream>),
+    Defaults(ModuleDefaults),
+    Obsoletes(ModuleObsoletes),
+    Other(serde_yaml::Value),
+}
+
+impl<'de> Deserialize<'de> for ParsedDocument {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(DocumentVisitor)
+    }
+}
+
+struct DocumentVisitor;
+
+impl<'de> Visitor<'de> for DocumentVisitor {
+    type Value = ParsedDocument;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a modulemd document")
+    }
+
+    // The data is deserialized straight into the type for the document rather than through
+    // serde_yaml::Value (or #[serde(flatten)], which buffers it in a similar way), as that would
+    // turn unquoted strings such as `stream: 8` into numbers. libmodulemd always writes the
+    // document type and version before the data, so they are known by the time it is read.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut document: Option<String> = None;
+        let mut version: Option<u32> = None;
+        let mut data = None;
+        let mut other_keys = serde_yaml::Mapping::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "document" => document = Some(map.next_value()?),
+                "version" => version = Some(map.next_value()?),
+                "data" => {
+                    let document = document
+                        .as_deref()
+                        .ok_or_else(|| de::Error::custom("data precedes the document type"))?;
+                    let version =
+                        version.ok_or_else(|| de::Error::custom("data precedes the version"))?;
+                    data = Some(match (document, version) {
+                        (DOCUMENT_MODULEMD, MODULEMD_VERSION) => {
+                            ParsedDocument::Stream(map.next_value()?)
+                        }
+                        (DOCUMENT_MODULEMD_DEFAULTS, MODULEMD_DEFAULTS_VERSION) => {
+                            ParsedDocument::Defaults(map.next_value()?)
+                        }
+                        (DOCUMENT_MODULEMD_OBSOLETES, MODULEMD_OBSOLETES_VERSION) => {
+                            ParsedDocument::Obsoletes(map.next_value()?)
+                        }
+                        _ => ParsedDocument::Other(map.next_value()?),
+                    });
+                }
+                _ => {
+                    other_keys.insert(key.into(), map.next_value()?);
+                }
+            }
+        }
+
+        let document = document.ok_or_else(|| de::Error::missing_field("document"))?;
+        let version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+        match data.ok_or_else(|| de::Error::missing_field("data"))? {
+            // documents which aren't understood are kept whole
+            ParsedDocument::Other(data) => {
+                let mut mapping = serde_yaml::Mapping::new();
+                mapping.insert("document".into(), document.into());
+                mapping.insert("version".into(), version.into());
+                mapping.insert("data".into(), data);
+                mapping.extend(other_keys);
+                Ok(ParsedDocument::Other(mapping.into()))
+            }
+            parsed => Ok(parsed),
+        }
+    }
+}
+
+impl Modules {
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, MetadataError> {
+        let mut yaml = String::new();
+        reader.read_to_string(&mut yaml)?;
+        Self::from_yaml_str(&yaml)
+    }
+
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, MetadataError> {
+        let mut modules = Modules::default();
+
+        for document in serde_yaml::Deserializer::from_str(yaml) {
+            match ParsedDocument::deserialize(document)? {
+                ParsedDocument::Stream(stream) => modules.streams.push(*stream),
+                ParsedDocument::Defaults(defaults) => modules.defaults.push(defaults),
+                ParsedDocument::Obsoletes(obsoletes) => modules.obsoletes.push(obsoletes),
+                ParsedDocument::Other(document) => modules.other_documents.push(document),
+            }
+        }
+
+        Ok(modules)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), MetadataError> {
+        writer.write_all(self.to_yaml_string()?.as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn to_yaml_string(&self) -> Result<String, MetadataError> {
+        let mut yaml = String::new();
+
+        for stream in &self.streams {
+            write_document(&mut yaml, DOCUMENT_MODULEMD, MODULEMD_VERSION, stream)?;
+        }
+        for defaults in &self.defaults {
+            write_document(
+                &mut yaml,
+                DOCUMENT_MODULEMD_DEFAULTS,
+                MODULEMD_DEFAULTS_VERSION,
+                defaults,
+            )?;
+        }
+        for obsoletes in &self.obsoletes {
+            write_document(
+                &mut yaml,
+                DOCUMENT_MODULEMD_OBSOLETES,
+                MODULEMD_OBSOLETES_VERSION,
+                obsoletes,
+            )?;
+        }
+        for document in &self.other_documents {
+            push_document(&mut yaml, serde_yaml::to_string(document)?);
+        }
+
+        Ok(yaml)
+    }
+}
+
+fn write_document<T: Serialize>(
+    yaml: &mut String,
+    document: &str,
+    version: u32,
+    data: &T,
+) -> Result<(), MetadataError> {
+    let document = Document {
+        document: document.to_owned(),
+        version,
+        data,
+    };
+    push_document(yaml, serde_yaml::to_string(&document)?);
+    Ok(())
+}
+
+/// Append a serialized document, making sure it is delimited by `---` and `...` like
+/// libmodulemd does.
+fn push_document(yaml: &mut String, document: String) {
+    if !document.starts_with("---") {
+        yaml.push_str("---\n");
+    }
+    yaml.push_str(&document);
+    if !document.ends_with('\n') {
+        yaml.push('\n');
+    }
+    yaml.push_str("...\n");
+}
@@ -17,7 +17,7 @@ use quick_xml::escape::partial_escape;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::{Reader, Writer};
 
-use super::metadata::{Changelog, OtherXml, Package, RpmMetadata, XML_NS_OTHER};
+use super::metadata::{Changelog, Checksum, OtherXml, Package, RpmMetadata, XML_NS_OTHER};
 use super::{MetadataError, Repository, EVR};
 
 const TAG_OTHERDATA: &[u8] = b"otherdata";
@@ -65,7 +65,14 @@ fn read_other_xml<R: BufRead>(
                     found_metadata_tag = true;
                 }
                 TAG_PACKAGE => {
-                    parse_package(repository, reader, &e)?;
+                    let pkgid = e
+                        .try_get_attribute("pkgid")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
+                        .unescape_and_decode_value(reader)?;
+
+                    let package = repository.packages_mut().entry(pkgid).or_default();
+
+                    parse_package(package, reader, &e)?;
                 }
                 _ => (),
             },
@@ -89,8 +96,11 @@ impl OtherXml {
         }
     }
 
-    pub fn new_reader<'a, R: BufRead>(reader: &'a mut Reader<R>) -> OtherXmlReader<'a, R> {
-        OtherXmlReader { reader }
+    pub fn new_reader<R: BufRead>(reader: Reader<R>) -> OtherXmlReader<R> {
+        OtherXmlReader {
+            reader,
+            buffer: Vec::new(),
+        }
     }
 }
 
@@ -141,7 +151,7 @@ impl<W: Write> OtherXmlWriter<W> {
                 .with_attribute(("author", changelog.author.as_str()))
                 .with_attribute(("date", format!("{}", changelog.date).as_str()))
                 .write_text_content(BytesText::from_escaped(partial_escape(
-                    &changelog.description.as_bytes(),
+                    changelog.description.as_bytes(),
                 )))?;
         }
 
@@ -173,18 +183,111 @@ impl<W: Write> OtherXmlWriter<W> {
     pub fn into_inner(self) -> W {
         self.writer.into_inner()
     }
+
+    pub fn inner_mut(&mut self) -> &mut W {
+        self.writer.inner()
+    }
 }
 
-pub struct OtherXmlReader<'a, R: BufRead> {
-    reader: &'a mut Reader<R>,
+pub struct OtherXmlReader<R: BufRead> {
+    reader: Reader<R>,
+    buffer: Vec<u8>,
 }
 
-impl<'a, R: BufRead> OtherXmlReader<'a, R> {
-    pub fn read_header(&mut self) {}
+impl<R: BufRead> OtherXmlReader<R> {
+    /// Read the <otherdata> tag and return the number of packages declared by it.
+    pub fn read_header(&mut self) -> Result<usize, MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_OTHERDATA => {
+                    let num_packages = e
+                        .try_get_attribute("packages")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("packages"))?
+                        .unescape_and_decode_value(&self.reader)?
+                        .parse()?;
+                    self.buffer.clear();
+                    return Ok(num_packages);
+                }
+                Event::Eof => return Err(MetadataError::MissingHeaderError),
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+    }
 
-    pub fn read_package(&mut self, package: &mut Package) {}
+    /// Read the changelogs of the next package into `package`.
+    ///
+    /// If `package` is `None`, a new package is created from the name and arch listed in other.xml.
+    /// Otherwise the pkgid of the next entry must match the checksum of `package`.
+    pub fn read_package(&mut self, package: &mut Option<Package>) -> Result<(), MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_PACKAGE => {
+                    let pkgid = e
+                        .try_get_attribute("pkgid")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
+                        .unescape_and_decode_value(&self.reader)?;
+
+                    let package = package.get_or_insert_with(Package::default);
+                    if *package.checksum() != Checksum::Unknown {
+                        let (_, expected_pkgid) = package.checksum().to_values()?;
+                        if expected_pkgid != pkgid {
+                            return Err(MetadataError::InconsistentMetadataError(format!(
+                                "other.xml entry {} does not match primary.xml entry {}",
+                                pkgid, expected_pkgid
+                            )));
+                        }
+                    }
+
+                    parse_package(package, &mut self.reader, &e)?;
+                    break;
+                }
+                Event::End(e) if e.name() != TAG_OTHERDATA => (),
+                Event::End(_) | Event::Eof => {
+                    // running out of entries is only fine if no package was expected
+                    if let Some(package) = package {
+                        return Err(MetadataError::InconsistentMetadataError(format!(
+                            "other.xml has no entry for package {}",
+                            package.name()
+                        )));
+                    }
+                    break;
+                }
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+        self.buffer.clear();
+        Ok(())
+    }
 
-    pub fn finish(&mut self) {}
+    /// Read the next package entry without matching it against a package from primary.xml.
+    ///
+    /// Returns the pkgid of the entry and a package populated only with the data present in other.xml.
+    pub fn read_entry(&mut self) -> Result<Option<(String, Package)>, MetadataError> {
+        let mut entry = None;
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_PACKAGE => {
+                    let pkgid = e
+                        .try_get_attribute("pkgid")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
+                        .unescape_and_decode_value(&self.reader)?;
+
+                    let mut package = Package::default();
+                    parse_package(&mut package, &mut self.reader, &e)?;
+                    entry = Some((pkgid, package));
+                    break;
+                }
+                Event::End(e) if e.name() == TAG_OTHERDATA => break,
+                Event::Eof => break,
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+        self.buffer.clear();
+        Ok(entry)
+    }
 }
 
 //   <package pkgid="6a915b6e1ad740994aa9688d70a67ff2b6b72e0ced668794aeb27b2d0f2e237b" name="fontconfig" arch="x86_64">
@@ -194,16 +297,12 @@ impl<'a, R: BufRead> OtherXmlReader<'a, R> {
 //     <changelog author="Behdad Esfahbod &lt;besfahbo@redhat.com&gt; - 2.8.0-1" date="1259841600">- Update to 2.8.0</changelog>
 //   </package>
 pub fn parse_package<R: BufRead>(
-    repository: &mut Repository,
+    package: &mut Package,
     reader: &mut Reader<R>,
     open_tag: &BytesStart,
 ) -> Result<(), MetadataError> {
     let mut buf = Vec::new();
 
-    let pkgid = open_tag
-        .try_get_attribute("pkgid")?
-        .ok_or_else(|| MetadataError::MissingAttributeError("pkgid"))?
-        .unescape_and_decode_value(reader)?;
     let name = open_tag
         .try_get_attribute("name")?
         .ok_or_else(|| MetadataError::MissingAttributeError("name"))?
@@ -213,11 +312,6 @@ pub fn parse_package<R: BufRead>(
         .ok_or_else(|| MetadataError::MissingAttributeError("arch"))?
         .unescape_and_decode_value(reader)?;
 
-    let mut package = repository
-        .packages_mut()
-        .entry(pkgid)
-        .or_insert(Package::default()); // TODO
-
     // TODO: using empty strings as null value is slightly questionable
     if package.name().is_empty() {
         package.set_name(&name);
@@ -230,6 +324,12 @@ pub fn parse_package<R: BufRead>(
     loop {
         match reader.read_event(&mut buf)? {
             Event::End(e) if e.name() == TAG_PACKAGE => break,
+            Event::Eof => {
+                return Err(MetadataError::InconsistentMetadataError(format!(
+                    "other.xml ended in the middle of the entry for package {}",
+                    name
+                )))
+            }
 
             Event::Start(e) => match e.name() {
                 TAG_VERSION => {
@@ -279,19 +379,20 @@ pub fn parse_changelog<R: BufRead>(
     reader: &mut Reader<R>,
     open_tag: &BytesStart,
 ) -> Result<Changelog, MetadataError> {
-    let mut changelog = Changelog::default();
-
-    changelog.author = open_tag
+    let author = open_tag
         .try_get_attribute("author")?
-        .unwrap()
+        .ok_or_else(|| MetadataError::MissingAttributeError("author"))?
         .unescape_and_decode_value(reader)?;
-    changelog.date = open_tag
+    let date = open_tag
         .try_get_attribute("date")?
-        .unwrap()
+        .ok_or_else(|| MetadataError::MissingAttributeError("date"))?
         .unescape_and_decode_value(reader)?
         .parse()?;
+    let description = reader.read_text(open_tag.name(), &mut Vec::new())?;
 
-    changelog.description = reader.read_text(open_tag.name(), &mut Vec::new())?;
-
-    Ok(changelog)
+    Ok(Changelog {
+        author,
+        date,
+        description,
+    })
 }
@@ -0,0 +1,268 @@
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::io::{BufRead, Write};
+
+use super::metadata::{Checksum, Delta, DeltaPackage, PrestodeltaXml, RpmMetadata};
+use super::{utils, MetadataError, Repository, EVR};
+
+const TAG_PRESTODELTA: &[u8] = b"prestodelta";
+const TAG_NEWPACKAGE: &[u8] = b"newpackage";
+const TAG_DELTA: &[u8] = b"delta";
+const TAG_FILENAME: &[u8] = b"filename";
+const TAG_SEQUENCE: &[u8] = b"sequence";
+const TAG_SIZE: &[u8] = b"size";
+const TAG_CHECKSUM: &[u8] = b"checksum";
+
+impl RpmMetadata for PrestodeltaXml {
+    fn filename() -> &'static str {
+        "prestodelta.xml"
+    }
+
+    fn load_metadata<R: BufRead>(
+        repository: &mut Repository,
+        reader: &mut Reader<R>,
+    ) -> Result<(), MetadataError> {
+        read_prestodelta_xml(repository, reader)
+    }
+
+    fn write_metadata<W: Write>(
+        repository: &Repository,
+        writer: Writer<W>,
+    ) -> Result<(), MetadataError> {
+        let mut writer = PrestodeltaXml::new_writer(writer);
+        writer.write_header()?;
+
+        for package in &repository.deltas {
+            writer.write_delta_package(package)?;
+        }
+
+        writer.finish()
+    }
+}
+
+impl PrestodeltaXml {
+    pub fn new_writer<W: Write>(writer: Writer<W>) -> PrestodeltaXmlWriter<W> {
+        PrestodeltaXmlWriter { writer }
+    }
+}
+
+fn read_prestodelta_xml<R: BufRead>(
+    repository: &mut Repository,
+    reader: &mut Reader<R>,
+) -> Result<(), MetadataError> {
+    let mut buf = Vec::new();
+    let mut found_metadata_tag = false;
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(e) => match e.name() {
+                TAG_PRESTODELTA => {
+                    found_metadata_tag = true;
+                }
+                TAG_NEWPACKAGE => {
+                    let package = parse_delta_package(reader, &e)?;
+                    repository.deltas.push(package);
+                }
+                _ => (),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    if !found_metadata_tag {
+        return Err(MetadataError::MissingHeaderError);
+    }
+    Ok(())
+}
+
+pub struct PrestodeltaXmlWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl<W: Write> PrestodeltaXmlWriter<W> {
+    pub fn write_header(&mut self) -> Result<(), MetadataError> {
+        // <?xml version="1.0" encoding="UTF-8"?>
+        self.writer
+            .write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
+
+        // <prestodelta>
+        self.writer
+            .write_event(Event::Start(BytesStart::borrowed_name(TAG_PRESTODELTA)))?;
+
+        Ok(())
+    }
+
+    pub fn write_delta_package(&mut self, package: &DeltaPackage) -> Result<(), MetadataError> {
+        write_delta_package(package, &mut self.writer)
+    }
+
+    pub fn finish(&mut self) -> Result<(), MetadataError> {
+        // </prestodelta>
+        self.writer
+            .write_event(Event::End(BytesEnd::borrowed(TAG_PRESTODELTA)))?;
+
+        // trailing newline
+        self.writer
+            .write_event(Event::Text(BytesText::from_plain_str("\n")))?;
+
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> Writer<W> {
+        self.writer
+    }
+}
+
+fn write_delta_package<W: Write>(
+    package: &DeltaPackage,
+    writer: &mut Writer<W>,
+) -> Result<(), MetadataError> {
+    // <newpackage name="nano" epoch="0" version="4.9.3" release="1.fc32" arch="x86_64">
+    let (epoch, version, release) = package.evr.values();
+    let mut newpackage_tag = BytesStart::borrowed_name(TAG_NEWPACKAGE);
+    newpackage_tag.push_attribute(("name", package.name.as_str()));
+    newpackage_tag.push_attribute(("epoch", epoch));
+    newpackage_tag.push_attribute(("version", version));
+    newpackage_tag.push_attribute(("release", release));
+    newpackage_tag.push_attribute(("arch", package.arch.as_str()));
+    writer.write_event(Event::Start(newpackage_tag.to_borrowed()))?;
+
+    for delta in &package.deltas {
+        // <delta oldepoch="0" oldversion="4.9.2" oldrelease="1.fc32">
+        let (old_epoch, old_version, old_release) = delta.old_evr.values();
+        let mut delta_tag = BytesStart::borrowed_name(TAG_DELTA);
+        delta_tag.push_attribute(("oldepoch", old_epoch));
+        delta_tag.push_attribute(("oldversion", old_version));
+        delta_tag.push_attribute(("oldrelease", old_release));
+        writer.write_event(Event::Start(delta_tag.to_borrowed()))?;
+
+        // <filename>drpms/nano-4.9.2-1.fc32_4.9.3-1.fc32.x86_64.drpm</filename>
+        writer
+            .create_element(TAG_FILENAME)
+            .write_text_content(BytesText::from_plain_str(delta.filename.as_str()))?;
+
+        // <sequence>nano-4.9.2-1.fc32-4a4d1f7fc4e2fb7a3e4e6b1d23ac4c9c</sequence>
+        writer
+            .create_element(TAG_SEQUENCE)
+            .write_text_content(BytesText::from_plain_str(delta.sequence.as_str()))?;
+
+        // <size>123017</size>
+        writer
+            .create_element(TAG_SIZE)
+            .write_text_content(BytesText::from_plain_str(&delta.size.to_string()))?;
+
+        // <checksum type="sha256">5ce1a3b9ad0d1fbb4a3e5f1fc4c09c4cbd1a7b3e26f0b1dd9b5d07ecc6cb7d11</checksum>
+        let (checksum_type, checksum_value) = delta.checksum.to_values()?;
+        writer
+            .create_element(TAG_CHECKSUM)
+            .with_attribute(("type", checksum_type))
+            .write_text_content(BytesText::from_plain_str(checksum_value))?;
+
+        // </delta>
+        writer.write_event(Event::End(delta_tag.to_end()))?;
+    }
+
+    // </newpackage>
+    writer.write_event(Event::End(newpackage_tag.to_end()))?;
+
+    Ok(())
+}
+
+// <newpackage name="nano" epoch="0" version="4.9.3" release="1.fc32" arch="x86_64">
+//   <delta oldepoch="0" oldversion="4.9.2" oldrelease="1.fc32">
+//     ...
+//   </delta>
+// </newpackage>
+fn parse_delta_package<R: BufRead>(
+    reader: &mut Reader<R>,
+    open_tag: &BytesStart,
+) -> Result<DeltaPackage, MetadataError> {
+    let mut package = DeltaPackage {
+        name: parse_required_attribute(reader, open_tag, "name")?,
+        arch: parse_required_attribute(reader, open_tag, "arch")?,
+        evr: EVR::new(
+            &utils::parse_optional_attribute(reader, open_tag, "epoch")?.unwrap_or_default(),
+            &parse_required_attribute(reader, open_tag, "version")?,
+            &parse_required_attribute(reader, open_tag, "release")?,
+        ),
+        ..DeltaPackage::default()
+    };
+
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_NEWPACKAGE => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_NEWPACKAGE)),
+            Event::Start(e) if e.name() == TAG_DELTA => {
+                package.deltas.push(parse_delta(reader, &e)?);
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(package)
+}
+
+// <delta oldepoch="0" oldversion="4.9.2" oldrelease="1.fc32">
+//   <filename>drpms/nano-4.9.2-1.fc32_4.9.3-1.fc32.x86_64.drpm</filename>
+//   <sequence>nano-4.9.2-1.fc32-4a4d1f7fc4e2fb7a3e4e6b1d23ac4c9c</sequence>
+//   <size>123017</size>
+//   <checksum type="sha256">...</checksum>
+// </delta>
+fn parse_delta<R: BufRead>(
+    reader: &mut Reader<R>,
+    open_tag: &BytesStart,
+) -> Result<Delta, MetadataError> {
+    let mut delta = Delta {
+        old_evr: EVR::new(
+            &utils::parse_optional_attribute(reader, open_tag, "oldepoch")?.unwrap_or_default(),
+            &parse_required_attribute(reader, open_tag, "oldversion")?,
+            &parse_required_attribute(reader, open_tag, "oldrelease")?,
+        ),
+        ..Delta::default()
+    };
+
+    let mut buf = Vec::new();
+    let mut text_buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_DELTA => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_DELTA)),
+            Event::Start(e) => match e.name() {
+                TAG_FILENAME => {
+                    delta.filename = reader.read_text(TAG_FILENAME, &mut text_buf)?;
+                }
+                TAG_SEQUENCE => {
+                    delta.sequence = reader.read_text(TAG_SEQUENCE, &mut text_buf)?;
+                }
+                TAG_SIZE => {
+                    delta.size = reader.read_text(TAG_SIZE, &mut text_buf)?.parse()?;
+                }
+                TAG_CHECKSUM => {
+                    let checksum_type = parse_required_attribute(reader, &e, "type")?;
+                    let checksum_value = reader.read_text(TAG_CHECKSUM, &mut text_buf)?;
+                    delta.checksum = Checksum::try_create(checksum_type, checksum_value)?;
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        buf.clear();
+        text_buf.clear();
+    }
+
+    Ok(delta)
+}
+
+fn parse_required_attribute<R: BufRead>(
+    reader: &Reader<R>,
+    tag: &BytesStart,
+    name: &'static str,
+) -> Result<String, MetadataError> {
+    utils::parse_optional_attribute(reader, tag, name)?
+        .ok_or(MetadataError::MissingAttributeError(name))
+}
@@ -7,7 +7,7 @@ use super::metadata::{
     Checksum, MetadataError, Package, PrimaryXml, Requirement, RpmMetadata, XML_NS_COMMON,
     XML_NS_RPM,
 };
-use super::{FileType, PackageFile, Repository, EVR};
+use super::{utils, FileType, PackageFile, Repository, EVR};
 
 const TAG_METADATA: &[u8] = b"metadata";
 const TAG_PACKAGE: &[u8] = b"package";
@@ -51,7 +51,9 @@ impl RpmMetadata for PrimaryXml {
         repository: &mut Repository,
         reader: &mut Reader<R>,
     ) -> Result<(), MetadataError> {
-        read_primary_xml(repository, reader)
+        read_primary_xml(repository, reader)?;
+        repository.packages_loaded = true;
+        Ok(())
     }
 
     fn write_metadata<W: Write>(
@@ -76,8 +78,11 @@ impl PrimaryXml {
         }
     }
 
-    pub fn new_reader<'a, R: BufRead>(reader: &'a mut Reader<R>) -> PrimaryXmlReader<'a, R> {
-        PrimaryXmlReader { reader }
+    pub fn new_reader<R: BufRead>(reader: Reader<R>) -> PrimaryXmlReader<R> {
+        PrimaryXmlReader {
+            reader,
+            buffer: Vec::new(),
+        }
     }
 }
 
@@ -133,18 +138,60 @@ impl<W: Write> PrimaryXmlWriter<W> {
     pub fn into_inner(self) -> W {
         self.writer.into_inner()
     }
-}
 
-pub struct PrimaryXmlReader<'a, R: BufRead> {
-    reader: &'a mut Reader<R>,
+    pub fn inner_mut(&mut self) -> &mut W {
+        self.writer.inner()
+    }
 }
 
-impl<'a, R: BufRead> PrimaryXmlReader<'a, R> {
-    pub fn read_header(&mut self) {}
+pub struct PrimaryXmlReader<R: BufRead> {
+    reader: Reader<R>,
+    buffer: Vec<u8>,
+}
 
-    pub fn read_package(&mut self, package: &mut Package) {}
+impl<R: BufRead> PrimaryXmlReader<R> {
+    /// Read the <metadata> tag and return the number of packages declared by it.
+    pub fn read_header(&mut self) -> Result<usize, MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_METADATA => {
+                    let num_packages = e
+                        .try_get_attribute("packages")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("packages"))?
+                        .unescape_and_decode_value(&self.reader)?
+                        .parse()?;
+                    self.buffer.clear();
+                    return Ok(num_packages);
+                }
+                Event::Eof => return Err(MetadataError::MissingHeaderError),
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+    }
 
-    pub fn finish(&mut self) {}
+    /// Read the next package from primary.xml, or leave `package` untouched if there are no more.
+    ///
+    /// primary.xml is the only source of the package checksum (pkgid), so it must be read before
+    /// filelists.xml and other.xml.
+    pub fn read_package(&mut self, package: &mut Option<Package>) -> Result<(), MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_PACKAGE => {
+                    let mut new_package = Package::default();
+                    parse_package(&mut new_package, &mut self.reader)?;
+                    *package = Some(new_package);
+                    break;
+                }
+                Event::End(e) if e.name() == TAG_METADATA => break,
+                Event::Eof => break,
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+        self.buffer.clear();
+        Ok(())
+    }
 }
 
 fn read_primary_xml<R: BufRead>(
@@ -164,10 +211,15 @@ fn read_primary_xml<R: BufRead>(
                 TAG_PACKAGE => {
                     let ptype = e
                         .try_get_attribute(b"type")?
-                        .unwrap()
+                        .ok_or_else(|| MetadataError::MissingAttributeError("type"))?
                         .unescape_and_decode_value(reader)?;
 
-                    assert_eq!(&ptype, "rpm"); // TODO: better error handling
+                    if ptype != "rpm" {
+                        return Err(MetadataError::InconsistentMetadataError(format!(
+                            "unsupported package type {}",
+                            ptype
+                        )));
+                    }
 
                     // TODO: in theory, other or filelists could be parsed first, and in that case this is wrong
                     // need to at least enforce order w/ a state machine, or just handle it.
@@ -324,14 +376,7 @@ pub fn write_package<W: Write>(
     // <file>/usr/bin/bash</file>
     for file in package.files() {
         // TODO: check this logic
-        let include = |f: &PackageFile| -> bool {
-            // strange algorithm, but it's what the original uses
-            f.path.starts_with("/etc/")
-                || f.path.contains("bin/")
-                || f.path.starts_with("/usr/lib/sendmail")
-        };
-
-        if file.filetype == FileType::File && include(file) {
+        if file.filetype == FileType::File && is_primary_file(file) {
             writer
                 .create_element(TAG_FILE)
                 .write_text_content(BytesText::from_plain_str(&file.path))?;
@@ -347,6 +392,14 @@ pub fn write_package<W: Write>(
     Ok(())
 }
 
+/// Whether `file` belongs to the subset of files listed in primary.xml (and primary.sqlite).
+pub(crate) fn is_primary_file(file: &PackageFile) -> bool {
+    // strange algorithm, but it's what the original uses
+    file.path.starts_with("/etc/")
+        || file.path.contains("bin/")
+        || file.path.starts_with("/usr/lib/sendmail")
+}
+
 // <rpm:supplements>
 //   <rpm:entry name="horse" flags="EQ" epoch="0" ver="4.1" rel="1"/>
 // </rpm:supplements>
@@ -404,6 +457,7 @@ pub fn parse_package<R: BufRead>(
     loop {
         match reader.read_event(&mut buf)? {
             Event::End(e) if e.name() == TAG_PACKAGE => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_PACKAGE)),
             Event::Start(e) => match e.name() {
                 TAG_NAME => {
                     package.set_name(reader.read_text(TAG_NAME, &mut text_buf)?.as_str());
@@ -503,6 +557,7 @@ pub fn parse_package<R: BufRead>(
                     loop {
                         match reader.read_event(&mut format_buf)? {
                             Event::End(e) if e.name() == TAG_FORMAT => break,
+                            Event::Eof => return Err(utils::unexpected_eof(TAG_FORMAT)),
                             Event::Start(e) => match e.name() {
                                 TAG_RPM_LICENSE => {
                                     package.set_rpm_license(
@@ -633,10 +688,8 @@ pub fn parse_requirement_list<R: BufRead>(
                     .try_get_attribute("rel")?
                     .and_then(|attr| attr.unescape_and_decode_value(reader).ok());
 
-                let preinstall = e.try_get_attribute("pre")?.map_or(None, |a| {
-                    let val = a.unescape_and_decode_value(reader).unwrap(); // TODO
-                    Some(val != "0" || !val.eq_ignore_ascii_case("false"))
-                });
+                let preinstall = utils::parse_optional_attribute(reader, &e, "pre")?
+                    .map(|val| utils::parse_bool(&val));
 
                 list.push(Requirement {
                     name,
@@ -648,6 +701,7 @@ pub fn parse_requirement_list<R: BufRead>(
                 });
             }
             Event::End(e) if e.name() == open_tag.name() => break,
+            Event::Eof => return Err(utils::unexpected_eof(open_tag.name())),
             _ => (), // TODO
         }
     }
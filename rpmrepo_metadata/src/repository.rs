@@ -1,20 +1,35 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryInto;
 use std::io::{BufRead, BufReader};
-use std::io::{BufWriter, Cursor, Write};
+use std::io::{BufWriter, Cursor};
 use std::path::Path;
-use std::{collections::BTreeMap, path::PathBuf};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use std::{fs::File, io::Read};
 
 use quick_xml::{Reader, Writer};
+use tempdir::TempDir;
 
-use super::filelist::FilelistsXmlWriter;
+use super::filelist::{FilelistsXmlReader, FilelistsXmlWriter};
 use super::metadata::{
-    ChecksumType, CompressionType, DistroTag, FilelistsXml, MetadataType, OtherXml, Package,
-    PrimaryXml, RepoMdRecord, RepomdXml, RpmMetadata, UpdateRecord, METADATA_FILELISTS,
-    METADATA_OTHER, METADATA_PRIMARY,
+    ChecksumType, CompressionType, Comps, CompsXml, DeltaPackage, DistroTag, FilelistsXml,
+    MetadataType, OtherXml, Package, PrestodeltaXml, PrimaryXml, RepoMdRecord, RepomdXml,
+    RpmMetadata, UpdateRecord, UpdateinfoXml, METADATA_FILELISTS, METADATA_FILELISTS_DB,
+    METADATA_FILELISTS_ZCK, METADATA_GROUP, METADATA_GROUP_GZ, METADATA_MODULES, METADATA_OTHER,
+    METADATA_OTHER_DB, METADATA_OTHER_ZCK, METADATA_PRESTODELTA, METADATA_PRIMARY,
+    METADATA_PRIMARY_DB, METADATA_PRIMARY_ZCK, METADATA_UPDATEINFO,
 };
-use super::other::OtherXmlWriter;
-use super::primary::PrimaryXmlWriter;
-use super::{repomd, MetadataError};
+use super::modules::Modules;
+use super::other::{OtherXmlReader, OtherXmlWriter};
+use super::primary::{PrimaryXmlReader, PrimaryXmlWriter};
+use super::signing::{self, SigningKey, VerifyingKey};
+#[cfg(feature = "sqlite")]
+use super::sqlite::{self, SqliteMetadataWriter, DATABASE_VERSION};
+use super::treeinfo::Treeinfo;
+use super::utils::{self, CompressedWriter};
+use super::verify;
+use super::zchunk::{self, ZchunkReader, ZchunkWriter};
+use super::MetadataError;
 
 fn configure_reader<R: BufRead>(reader: &mut Reader<R>) {
     reader.expand_empty_elements(true).trim_text(true);
@@ -32,6 +47,13 @@ pub struct Repository {
     pub distro_tags: Vec<DistroTag>,
 
     pub advisories: Vec<UpdateRecord>,
+    pub comps: Option<Comps>,
+    pub modules: Option<Modules>,
+    pub deltas: Vec<DeltaPackage>,
+
+    // whether `packages` holds the packages of primary.xml, as opposed to being empty because it
+    // was never read
+    pub(crate) packages_loaded: bool,
 }
 
 impl Repository {
@@ -44,7 +66,7 @@ impl Repository {
     }
 
     pub fn get_record(&self, rectype: &str) -> Option<&RepoMdRecord> {
-        self.records().iter().find(|r| &r.mdtype == rectype)
+        self.records().iter().find(|r| r.mdtype == rectype)
     }
 
     pub fn records(&self) -> &Vec<RepoMdRecord> {
@@ -52,7 +74,7 @@ impl Repository {
     }
 
     pub fn remove_record(&mut self, rectype: &str) {
-        self.metadata_files.retain(|r| &r.mdtype != rectype);
+        self.metadata_files.retain(|r| r.mdtype != rectype);
     }
 
     pub fn add_repo_tag(&mut self, repo: String) {
@@ -93,10 +115,15 @@ impl Repository {
                 MetadataType::PrimaryZck => 7,
                 MetadataType::FilelistsZck => 8,
                 MetadataType::OtherZck => 9,
-                MetadataType::Unknown => 10,
+                MetadataType::Updateinfo => 10,
+                MetadataType::Group => 11,
+                MetadataType::GroupGz => 12,
+                MetadataType::Modules => 13,
+                MetadataType::Prestodelta => 14,
+                MetadataType::Unknown => 15,
             }
         }
-        self.metadata_files.sort_by(|a, b| value(a).cmp(&value(b)));
+        self.metadata_files.sort_by_key(value);
     }
 
     pub fn get_primary_data(&self) -> &RepoMdRecord {
@@ -123,60 +150,482 @@ impl Repository {
         &mut self.packages
     }
 
+    /// Add a package to the repository, replacing any package with the same pkgid.
+    pub fn add_package(&mut self, package: Package) -> Result<(), MetadataError> {
+        let (_, pkgid) = package.checksum().to_values()?;
+        self.packages.insert(pkgid.to_owned(), package);
+        Ok(())
+    }
+
     pub fn load_from_directory(path: &Path) -> Result<Self, MetadataError> {
+        Self::load_from_directory_with_options(path, RepositoryLoadOptions::default())
+    }
+
+    pub fn load_from_directory_with_options(
+        path: &Path,
+        options: RepositoryLoadOptions,
+    ) -> Result<Self, MetadataError> {
         let mut repo = Repository::new();
 
-        repo.load_metadata_file::<RepomdXml>(&path.join("repodata/repomd.xml"))?;
+        // once repomd.xml is trusted, the files it describes are only trusted if they match it
+        let verify = !options.verification_keys.is_empty();
+
+        let repomd_path = path.join("repodata").join(RepomdXml::filename());
+        if !verify {
+            repo.load_metadata_file::<RepomdXml>(&repomd_path)?;
+        } else {
+            // verify exactly the bytes which are parsed afterwards
+            let repomd_xml = std::fs::read(&repomd_path)?;
+            verify_repomd_signature(&repomd_path, &repomd_xml, &options.verification_keys)?;
+            repo.load_metadata_bytes::<RepomdXml>(&repomd_xml)?;
+        }
 
-        let primary_href = path.join(
-            repo.get_record(METADATA_PRIMARY)
-                .unwrap()
-                .location_href
-                .as_str(),
-        );
-        let filelists_href = path.join(
-            repo.get_record(METADATA_FILELISTS)
-                .unwrap()
-                .location_href
-                .as_str(),
-        );
-        let other_href = path.join(
-            repo.get_record(METADATA_OTHER)
-                .unwrap()
-                .location_href
-                .as_str(),
-        );
+        match options.package_metadata_source {
+            PackageMetadataSource::Xml => repo.load_packages_from_xml(path, verify)?,
+            #[cfg(feature = "sqlite")]
+            PackageMetadataSource::Sqlite => repo.load_packages_from_sqlite(path, verify)?,
+            PackageMetadataSource::Auto => {
+                let result = repo.load_packages_from_xml(path, verify);
+                #[cfg(feature = "sqlite")]
+                let result = match result {
+                    Err(xml_error) if repo.get_record(METADATA_PRIMARY_DB).is_some() => {
+                        // don't keep whatever was parsed before the XML turned out to be broken
+                        repo.packages.clear();
+                        // if the databases can't be used either, the XML is what was asked for
+                        repo.load_packages_from_sqlite(path, verify)
+                            .map_err(|_| xml_error)
+                    }
+                    result => result,
+                };
+                result?
+            }
+        }
+
+        if repo.get_record(METADATA_UPDATEINFO).is_some() {
+            let updateinfo_href = repo.record_path(path, METADATA_UPDATEINFO, verify)?;
+            repo.load_metadata_file::<UpdateinfoXml>(&updateinfo_href)?;
+        }
+
+        let group_mdtype = [METADATA_GROUP, METADATA_GROUP_GZ]
+            .iter()
+            .find(|mdtype| repo.get_record(mdtype).is_some());
+        if let Some(group_mdtype) = group_mdtype {
+            let group_href = repo.record_path(path, group_mdtype, verify)?;
+            repo.load_metadata_file::<CompsXml>(&group_href)?;
+        }
 
-        repo.load_metadata_file::<PrimaryXml>(&primary_href)?;
-        repo.load_metadata_file::<FilelistsXml>(&filelists_href)?;
-        repo.load_metadata_file::<OtherXml>(&other_href)?;
+        if repo.get_record(METADATA_MODULES).is_some() {
+            let modules_href = repo.record_path(path, METADATA_MODULES, verify)?;
+            repo.load_modules_file(&modules_href)?;
+        }
+
+        if repo.get_record(METADATA_PRESTODELTA).is_some() {
+            let prestodelta_href = repo.record_path(path, METADATA_PRESTODELTA, verify)?;
+            repo.load_metadata_file::<PrestodeltaXml>(&prestodelta_href)?;
+        }
 
         Ok(repo)
     }
 
+    fn load_packages_from_xml(&mut self, path: &Path, verify: bool) -> Result<(), MetadataError> {
+        let primary_href = self.record_path(path, METADATA_PRIMARY, verify)?;
+        let filelists_href = self.record_path(path, METADATA_FILELISTS, verify)?;
+        let other_href = self.record_path(path, METADATA_OTHER, verify)?;
+
+        self.load_metadata_file::<PrimaryXml>(&primary_href)?;
+        self.load_metadata_file::<FilelistsXml>(&filelists_href)?;
+        self.load_metadata_file::<OtherXml>(&other_href)?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn load_packages_from_sqlite(
+        &mut self,
+        path: &Path,
+        verify: bool,
+    ) -> Result<(), MetadataError> {
+        let primary_href = self.record_path(path, METADATA_PRIMARY_DB, verify)?;
+        let filelists_href = self.record_path(path, METADATA_FILELISTS_DB, verify)?;
+        let other_href = self.record_path(path, METADATA_OTHER_DB, verify)?;
+
+        for package in sqlite::read_packages(&primary_href, &filelists_href, &other_href)? {
+            self.add_package(package)?;
+        }
+        self.packages_loaded = true;
+
+        Ok(())
+    }
+
+    /// Path of the file described by the `mdtype` record, for a repository located at `path`.
+    ///
+    /// If `verify` is set, the checksum of the file is checked against the record first.
+    fn record_path(
+        &self,
+        path: &Path,
+        mdtype: &str,
+        verify: bool,
+    ) -> Result<PathBuf, MetadataError> {
+        let record = self
+            .get_record(mdtype)
+            .ok_or_else(|| MetadataError::MissingRecordError(mdtype.to_owned()))?;
+        let file_path = path.join(&record.location_href);
+
+        if verify {
+            let (checksum_type, _) = record.checksum.to_values()?;
+            let (checksum, _) = utils::checksum_file(&file_path, checksum_type.try_into()?)?;
+            if checksum != record.checksum {
+                return Err(MetadataError::ChecksumMismatchError(
+                    record.location_href.clone(),
+                ));
+            }
+        }
+
+        Ok(file_path)
+    }
+
+    /// Write the repository metadata to the repodata/ directory under `path`.
+    ///
+    /// The repomd.xml records for the written files and the revision are regenerated, records
+    /// derived from the package metadata which aren't rewritten (such as sqlite databases) are
+    /// dropped because they would be stale.
     pub fn write_to_directory(
         &mut self,
         path: &Path,
         options: RepositoryOptions,
+    ) -> Result<(), MetadataError> {
+        self.write_to_directory_with_key(path, options, None)
+    }
+
+    /// Like [`Repository::write_to_directory`], but also sign repomd.xml with `signing_key`,
+    /// writing a detached signature to repomd.xml.asc.
+    pub fn write_to_directory_signed(
+        &mut self,
+        path: &Path,
+        options: RepositoryOptions,
+        signing_key: &SigningKey,
+    ) -> Result<(), MetadataError> {
+        self.write_to_directory_with_key(path, options, Some(signing_key))
+    }
+
+    fn write_to_directory_with_key(
+        &mut self,
+        path: &Path,
+        options: RepositoryOptions,
+        signing_key: Option<&SigningKey>,
     ) -> Result<(), MetadataError> {
         let repodata_dir = path.join("repodata");
+        let compression = options.metadata_compression_type;
 
         std::fs::create_dir_all(&repodata_dir)?;
 
-        self.write_metadata_file::<PrimaryXml>(&repodata_dir, options.metadata_compression_type)?;
-        self.write_metadata_file::<FilelistsXml>(&repodata_dir, options.metadata_compression_type)?;
-        self.write_metadata_file::<OtherXml>(&repodata_dir, options.metadata_compression_type)?;
+        for mdtype in &[
+            METADATA_PRIMARY,
+            METADATA_FILELISTS,
+            METADATA_OTHER,
+            METADATA_PRIMARY_DB,
+            METADATA_FILELISTS_DB,
+            METADATA_OTHER_DB,
+            METADATA_PRIMARY_ZCK,
+            METADATA_FILELISTS_ZCK,
+            METADATA_OTHER_ZCK,
+            METADATA_UPDATEINFO,
+            METADATA_GROUP,
+            METADATA_GROUP_GZ,
+            METADATA_MODULES,
+            METADATA_PRESTODELTA,
+        ] {
+            self.remove_record(mdtype);
+        }
+
+        let primary_path = self.write_metadata_file::<PrimaryXml>(&repodata_dir, compression)?;
+        self.add_record(create_record(METADATA_PRIMARY, &primary_path, &options)?);
+
+        let filelists_path =
+            self.write_metadata_file::<FilelistsXml>(&repodata_dir, compression)?;
+        self.add_record(create_record(
+            METADATA_FILELISTS,
+            &filelists_path,
+            &options,
+        )?);
+
+        let other_path = self.write_metadata_file::<OtherXml>(&repodata_dir, compression)?;
+        self.add_record(create_record(METADATA_OTHER, &other_path, &options)?);
+
+        #[cfg(feature = "sqlite")]
+        if options.sqlite_metadata {
+            let mut sqlite_writer = SqliteMetadataWriter::new(&repodata_dir)?;
+            for package in self.packages.values() {
+                sqlite_writer.add_package(package)?;
+            }
+            let db_paths = sqlite_writer.finish(
+                &self.get_primary_data().checksum,
+                &self.get_filelist_data().checksum,
+                &self.get_other_data().checksum,
+            )?;
+            for (mdtype, path) in db_paths {
+                self.add_record(create_database_record(mdtype, &path, &options)?);
+            }
+        }
+
+        if options.zchunk_metadata {
+            let mut zchunk_writer = ZchunkMetadataWriter::new(&repodata_dir)?;
+            zchunk_writer.start(self.packages.len())?;
+            for package in self.packages.values() {
+                zchunk_writer.add_package(package)?;
+            }
+            for (mdtype, path) in zchunk_writer.finish()? {
+                self.add_record(create_record(mdtype, &path, &options)?);
+            }
+        }
 
-        // TODO
-        // if !options.simple_metadata_filenames {
-        //     self.rename_metadata_files()
-        // }
+        if !self.advisories.is_empty() {
+            let updateinfo_path =
+                self.write_metadata_file::<UpdateinfoXml>(&repodata_dir, compression)?;
+            self.add_record(create_record(
+                METADATA_UPDATEINFO,
+                &updateinfo_path,
+                &options,
+            )?);
+        }
 
-        self.write_metadata_file::<RepomdXml>(&repodata_dir, CompressionType::None)?;
+        // comps.xml is published both uncompressed and compressed, like createrepo_c does
+        if self.comps.is_some() {
+            let group_path =
+                self.write_metadata_file::<CompsXml>(&repodata_dir, CompressionType::None)?;
+            self.add_record(create_record(METADATA_GROUP, &group_path, &options)?);
+
+            let group_gz_path =
+                self.write_metadata_file::<CompsXml>(&repodata_dir, CompressionType::Gzip)?;
+            self.add_record(create_record(METADATA_GROUP_GZ, &group_gz_path, &options)?);
+        }
+
+        if let Some(modules) = &self.modules {
+            let modules_path = write_modules_file(modules, &repodata_dir, compression)?;
+            self.add_record(create_record(METADATA_MODULES, &modules_path, &options)?);
+        }
+
+        if !self.deltas.is_empty() {
+            let prestodelta_path =
+                self.write_metadata_file::<PrestodeltaXml>(&repodata_dir, compression)?;
+            self.add_record(create_record(
+                METADATA_PRESTODELTA,
+                &prestodelta_path,
+                &options,
+            )?);
+        }
+
+        self.sort_records();
+        self.revision = Some(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .expect("system clock failure")
+                .as_secs()
+                .to_string(),
+        );
+
+        self.write_repomd(&repodata_dir, signing_key)?;
+        // the packages are now the package metadata of the repository at `path`
+        self.packages_loaded = true;
 
         Ok(())
     }
 
+    /// Add an arbitrary file (product certificates, vendor metadata...) to the repodata/
+    /// directory of the repository at `path` as a `mdtype` record, and rewrite repomd.xml, like
+    /// modifyrepo_c does.
+    ///
+    /// The file is compressed with the metadata compression type unless it is compressed
+    /// already. A record of the same type is replaced, and its file removed once repomd.xml has
+    /// been rewritten. If adding the file fails, the repository is left as it was.
+    ///
+    /// An existing repomd.xml.asc no longer matches and is removed, the repository has to be
+    /// signed again afterwards if needed.
+    pub fn add_metadata_file(
+        &mut self,
+        path: &Path,
+        file: &Path,
+        mdtype: &str,
+        options: RepositoryOptions,
+    ) -> Result<(), MetadataError> {
+        let repodata_dir = path.join("repodata");
+        std::fs::create_dir_all(&repodata_dir)?;
+
+        let filename = utils::file_name(file)?;
+        let (_, compressed) = utils::decompress_reader(Box::new(File::open(file)?))?;
+        let already_compressed = compressed || zchunk::is_zchunk_file(file)?;
+
+        // the new file is prepared and checksummed aside first, so that the repository is left
+        // as it was if that fails
+        let staging_dir = TempDir::new_in(&repodata_dir, ".staging")?;
+        let staged_path = if already_compressed {
+            let staged_path = staging_dir.path().join(&*filename);
+            std::fs::copy(file, &staged_path)?;
+            staged_path
+        } else {
+            let compression = options.metadata_compression_type;
+            let staged_path =
+                staging_dir
+                    .path()
+                    .join(format!("{}{}", filename, compression.to_file_extension()));
+            let mut writer = CompressedWriter::create(&staged_path, compression)?;
+            std::io::copy(&mut File::open(file)?, &mut writer)?;
+            writer.finish()?;
+            staged_path
+        };
+        let record = create_record(mdtype, &staged_path, &options)?;
+        let new_path = path.join(&record.location_href);
+        std::fs::rename(
+            staged_path.with_file_name(utils::file_name(&new_path)?),
+            &new_path,
+        )?;
+
+        let old_path = self
+            .get_record(mdtype)
+            .map(|old_record| path.join(&old_record.location_href));
+        self.remove_record(mdtype);
+        self.add_record(record);
+        self.sort_records();
+        self.write_repomd(&repodata_dir, None)?;
+
+        // only now that repomd.xml no longer references it
+        if let Some(old_path) = old_path.filter(|old_path| *old_path != new_path) {
+            if let Err(e) = std::fs::remove_file(old_path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the `mdtype` record and its file from the repository at `path`, and rewrite
+    /// repomd.xml.
+    ///
+    /// An existing repomd.xml.asc no longer matches and is removed, the repository has to be
+    /// signed again afterwards if needed.
+    pub fn remove_metadata_file(&mut self, path: &Path, mdtype: &str) -> Result<(), MetadataError> {
+        self.remove_record_file(path, mdtype)?;
+        self.write_repomd(&path.join("repodata"), None)?;
+        Ok(())
+    }
+
+    /// Check the detached signature (repomd.xml.asc) of the repomd.xml of the repository at
+    /// `path` against `keys`.
+    pub fn verify_repomd(path: &Path, keys: &[VerifyingKey]) -> Result<(), MetadataError> {
+        let repomd_path = path.join("repodata").join(RepomdXml::filename());
+        verify_repomd_signature(&repomd_path, &std::fs::read(&repomd_path)?, keys)
+    }
+
+    /// Sign the repomd.xml of the repository at `path` with `signing_key`, writing a detached
+    /// signature to repodata/repomd.xml.asc.
+    pub fn sign_repomd(path: &Path, signing_key: &SigningKey) -> Result<PathBuf, MetadataError> {
+        signing_key.sign_file(&path.join("repodata").join(RepomdXml::filename()))
+    }
+
+    /// Write repomd.xml to `repodata_dir`, along with a detached signature if `signing_key` is
+    /// provided. A repomd.xml.asc which was left over from a previous repomd.xml is removed.
+    fn write_repomd(
+        &self,
+        repodata_dir: &Path,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<(), MetadataError> {
+        let repomd_path =
+            self.write_metadata_file::<RepomdXml>(repodata_dir, CompressionType::None)?;
+
+        match signing_key {
+            Some(key) => {
+                key.sign_file(&repomd_path)?;
+            }
+            None => {
+                if let Err(e) = std::fs::remove_file(signing::signature_path(&repomd_path)) {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        return Err(e.into());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// RPMs, deltarpms and repodata/ files in the repository at `path` which are referenced
+    /// neither by the packages nor by the repomd.xml records (or deltarpms) of this repository,
+    /// such as RPMs which were removed from the metadata and metadata files from previous
+    /// revisions.
+    ///
+    /// Files modified within the last `grace_period` are never considered orphaned, so that
+    /// clients still working from the previous repomd.xml can finish their downloads. If the
+    /// repository is the top level of a distribution tree, the images and the repositories listed
+    /// in its .treeinfo are left alone too. Paths are relative to `path`.
+    ///
+    /// The packages have to be loaded (or written) first, otherwise every RPM would look
+    /// orphaned.
+    pub fn find_orphaned_files(
+        &self,
+        path: &Path,
+        grace_period: Duration,
+    ) -> Result<Vec<String>, MetadataError> {
+        if !self.packages_loaded {
+            return Err(MetadataError::PackagesNotLoadedError);
+        }
+
+        let cutoff = SystemTime::now()
+            .checked_sub(grace_period)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let referenced = verify::referenced_files(self);
+        let (tree_images, tree_repositories) = tree_paths(path)?;
+
+        let mut orphans = Vec::new();
+        for location_href in verify::list_repository_files(path)? {
+            let is_candidate = location_href.ends_with(".rpm")
+                || location_href.ends_with(".drpm")
+                || location_href.starts_with("repodata/");
+            if !is_candidate || referenced.contains(location_href.as_str()) {
+                continue;
+            }
+            if tree_images.contains(&location_href)
+                || tree_repositories
+                    .iter()
+                    .any(|repository| location_href.starts_with(repository))
+            {
+                continue;
+            }
+            if std::fs::metadata(path.join(&location_href))?.modified()? > cutoff {
+                continue;
+            }
+            orphans.push(location_href);
+        }
+
+        Ok(orphans)
+    }
+
+    /// Delete the files found by [`Repository::find_orphaned_files`], returning their paths.
+    pub fn remove_orphaned_files(
+        &self,
+        path: &Path,
+        grace_period: Duration,
+    ) -> Result<Vec<String>, MetadataError> {
+        let orphans = self.find_orphaned_files(path, grace_period)?;
+        for location_href in &orphans {
+            std::fs::remove_file(path.join(location_href))?;
+        }
+        Ok(orphans)
+    }
+
+    fn remove_record_file(&mut self, path: &Path, mdtype: &str) -> Result<(), MetadataError> {
+        let record_path = self.record_path(path, mdtype, false)?;
+        // the record is dropped even if someone already deleted the file
+        if let Err(e) = std::fs::remove_file(record_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        self.remove_record(mdtype);
+        Ok(())
+    }
+
     pub fn load_from_files(
         primary_xml: &Path,
         filelists_xml: &Path,
@@ -191,35 +640,30 @@ impl Repository {
         Ok(repo)
     }
 
-    pub(crate) fn load_metadata_file<M: RpmMetadata>(
-        &mut self,
-        path: &Path,
-    ) -> Result<(), MetadataError> {
-        let file = File::open(path)?;
-        let (reader, _compression) = niffler::get_reader(Box::new(&file))?;
-        let mut reader = Reader::from_reader(BufReader::new(reader));
-        configure_reader(&mut reader);
-
+    pub fn load_metadata_file<M: RpmMetadata>(&mut self, path: &Path) -> Result<(), MetadataError> {
+        let mut reader = create_xml_reader(path)?;
         M::load_metadata(self, &mut reader)
     }
 
-    pub(crate) fn load_metadata_str<M: RpmMetadata>(
-        &mut self,
-        str: &str,
-    ) -> Result<(), MetadataError> {
+    fn load_modules_file(&mut self, path: &Path) -> Result<(), MetadataError> {
+        let reader = create_file_reader(path)?;
+        self.modules = Some(Modules::from_reader(reader)?);
+        Ok(())
+    }
+
+    pub fn load_metadata_str<M: RpmMetadata>(&mut self, str: &str) -> Result<(), MetadataError> {
         let mut reader = Reader::from_str(str);
         configure_reader(&mut reader);
 
         M::load_metadata(self, &mut reader)
     }
 
-    pub(crate) fn load_metadata_bytes<M: RpmMetadata>(
+    pub fn load_metadata_bytes<M: RpmMetadata>(
         &mut self,
         bytes: &[u8],
     ) -> Result<(), MetadataError> {
-        let (reader, _compression) = niffler::get_reader(Box::new(bytes))?;
+        let (reader, _compressed) = utils::decompress_reader(Box::new(bytes))?;
         let mut reader = Reader::from_reader(BufReader::new(reader));
-        let mut reader = Reader::from_reader(bytes);
         configure_reader(&mut reader);
 
         M::load_metadata(self, &mut reader)
@@ -229,15 +673,16 @@ impl Repository {
         &self,
         path: &Path,
         compression: CompressionType,
-    ) -> Result<(), MetadataError> {
-        let new_path = PathBuf::from(path);
-        let new_path = new_path.join(M::filename());
-        let writer = create_xml_writer(&new_path, compression)?;
-        M::write_metadata(self, writer)?;
-        Ok(())
+    ) -> Result<PathBuf, MetadataError> {
+        let filename = format!("{}{}", M::filename(), compression.to_file_extension());
+        let new_path = path.join(filename);
+        let mut file = CompressedWriter::create(&new_path, compression)?;
+        M::write_metadata(self, Writer::new_with_indent(&mut file, b' ', 2))?;
+        file.finish()?;
+        Ok(new_path)
     }
 
-    pub(crate) fn to_string<M: RpmMetadata>(&self) -> Result<String, MetadataError> {
+    pub fn to_string<M: RpmMetadata>(&self) -> Result<String, MetadataError> {
         let bytes = self.to_bytes::<M>()?;
         Ok(String::from_utf8(bytes).map_err(|e| e.utf8_error())?)
     }
@@ -257,52 +702,186 @@ impl Repository {
     // * number of old packages?
     // * checksum types for metadata
     // * compression types. how customizable does it need to be?
-    // * sqlite metadata yes/no
-    // * zchunk metadata?
     // * signing
 }
 
+/// Open a (possibly compressed) metadata file for reading its decompressed contents.
+fn create_file_reader(path: &Path) -> Result<Box<dyn Read>, MetadataError> {
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if zchunk::is_zchunk_file(path)? {
+        Box::new(ZchunkReader::new(BufReader::new(file))?)
+    } else {
+        utils::decompress_reader(Box::new(file))?.0
+    };
+    Ok(reader)
+}
+
+fn create_xml_reader(path: &Path) -> Result<Reader<BufReader<Box<dyn Read>>>, MetadataError> {
+    let mut reader = Reader::from_reader(BufReader::new(create_file_reader(path)?));
+    configure_reader(&mut reader);
+    Ok(reader)
+}
+
 fn create_xml_writer(
     path: &Path,
     compression: CompressionType,
-) -> Result<Writer<Box<dyn Write>>, MetadataError> {
-    let extension = match compression {
-        CompressionType::None => "",
-        CompressionType::Gzip => ".gz",
-        CompressionType::Xz => ".xz",
-        CompressionType::Bz2 => ".bz2",
+) -> Result<Writer<CompressedWriter>, MetadataError> {
+    Ok(Writer::new_with_indent(
+        CompressedWriter::create(path, compression)?,
+        b' ',
+        2,
+    ))
+}
+
+fn write_modules_file(
+    modules: &Modules,
+    path: &Path,
+    compression: CompressionType,
+) -> Result<PathBuf, MetadataError> {
+    let filename = format!("modules.yaml{}", compression.to_file_extension());
+    let new_path = path.join(filename);
+    let mut writer = CompressedWriter::create(&new_path, compression)?;
+    modules.write(&mut writer)?;
+    writer.finish()?;
+    Ok(new_path)
+}
+
+/// Check `repomd_xml`, the contents of the file at `repomd_path`, against the detached signature
+/// next to it.
+fn verify_repomd_signature(
+    repomd_path: &Path,
+    repomd_xml: &[u8],
+    keys: &[VerifyingKey],
+) -> Result<(), MetadataError> {
+    let signature_path = signing::signature_path(repomd_path);
+    let signature = match std::fs::read(&signature_path) {
+        Ok(signature) => signature,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(MetadataError::SignatureVerificationError(format!(
+                "{} is missing",
+                signature_path.display()
+            )))
+        }
+        Err(e) => return Err(e.into()),
     };
+    signing::verify_signature(repomd_xml, &signature, keys)
+}
 
-    let mut filename = path.as_os_str().to_owned();
-    filename.push(&extension);
-
-    let file = File::create(filename)?;
-
-    let inner_writer = match compression {
-        CompressionType::None => Box::new(file),
-        CompressionType::Gzip => niffler::get_writer(
-            Box::new(file),
-            niffler::compression::Format::Gzip,
-            niffler::Level::Nine,
-        )?,
-        CompressionType::Bz2 => niffler::get_writer(
-            Box::new(file),
-            niffler::compression::Format::Bzip,
-            niffler::Level::Nine,
-        )?,
-        CompressionType::Xz => niffler::get_writer(
-            Box::new(file),
-            niffler::compression::Format::Lzma,
-            niffler::Level::Nine,
-        )?,
-        _ => unimplemented!(),
+/// Images and repository directories (with a trailing "/") listed in the .treeinfo of the tree
+/// at `path`, if it is one. The repository at the top level of the tree itself isn't included.
+fn tree_paths(path: &Path) -> Result<(BTreeSet<String>, Vec<String>), MetadataError> {
+    // older trees only have the file without the leading dot
+    let treeinfo_path = [".treeinfo", "treeinfo"]
+        .iter()
+        .map(|filename| path.join(filename))
+        .find(|treeinfo_path| treeinfo_path.exists());
+    let treeinfo = match treeinfo_path {
+        Some(treeinfo_path) => Treeinfo::from_reader(File::open(treeinfo_path)?)?,
+        None => return Ok((BTreeSet::new(), Vec::new())),
     };
-    Ok(Writer::new_with_indent(inner_writer, b' ', 2))
+
+    let images = treeinfo
+        .image_paths()
+        .into_iter()
+        .map(|image| image.trim_start_matches("./").to_owned())
+        .collect();
+    let repositories = treeinfo
+        .repository_paths()
+        .into_iter()
+        .map(|repository| repository.trim_start_matches("./").trim_end_matches('/'))
+        .filter(|repository| !repository.is_empty() && *repository != ".")
+        .map(|repository| format!("{}/", repository))
+        .collect();
+    Ok((images, repositories))
+}
+
+/// Create the repomd.xml record for a freshly written sqlite database.
+#[cfg(feature = "sqlite")]
+fn create_database_record(
+    mdtype: &str,
+    path: &Path,
+    options: &RepositoryOptions,
+) -> Result<RepoMdRecord, MetadataError> {
+    let mut record = create_record(mdtype, path, options)?;
+    record.database_version = Some(DATABASE_VERSION);
+    Ok(record)
+}
+
+/// Create the repomd.xml record for a freshly written metadata file.
+///
+/// Unless simple metadata filenames were requested, the file is renamed to
+/// `<checksum>-<filename>` so that it never collides with an older version of the same file.
+fn create_record(
+    mdtype: &str,
+    path: &Path,
+    options: &RepositoryOptions,
+) -> Result<RepoMdRecord, MetadataError> {
+    let mut record = RepoMdRecord::new(mdtype, path, options.metadata_checksum_type)?;
+
+    if !options.simple_metadata_filenames {
+        let (_, checksum) = record.checksum.to_values()?;
+        let filename = utils::file_name(path)?;
+        let new_filename = format!("{}-{}", checksum, filename);
+
+        std::fs::rename(path, path.with_file_name(&new_filename))?;
+        record.location_href = format!("repodata/{}", new_filename);
+    }
+
+    Ok(record)
+}
+
+/// Which metadata the packages of a repository are read from.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PackageMetadataSource {
+    /// primary.xml, filelists.xml and other.xml, falling back to the sqlite databases (when the
+    /// `sqlite` feature is enabled) if the XML is missing or can't be parsed
+    Auto,
+    /// Only primary.xml, filelists.xml and other.xml
+    Xml,
+    /// Only the primary, filelists and other sqlite databases
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+#[derive(Debug, Clone)]
+pub struct RepositoryLoadOptions {
+    package_metadata_source: PackageMetadataSource,
+    verification_keys: Vec<VerifyingKey>,
+}
+
+impl Default for RepositoryLoadOptions {
+    fn default() -> Self {
+        Self {
+            package_metadata_source: PackageMetadataSource::Auto,
+            verification_keys: Vec::new(),
+        }
+    }
+}
+
+impl RepositoryLoadOptions {
+    pub fn package_metadata_source(self, source: PackageMetadataSource) -> Self {
+        Self {
+            package_metadata_source: source,
+            ..self
+        }
+    }
+
+    /// Refuse to load the repository unless repomd.xml has a detached signature (repomd.xml.asc)
+    /// made by one of `keys`
+    pub fn verification_keys(self, keys: Vec<VerifyingKey>) -> Self {
+        Self {
+            verification_keys: keys,
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct RepositoryOptions {
     simple_metadata_filenames: bool,
+    zchunk_metadata: bool,
+    #[cfg(feature = "sqlite")]
+    sqlite_metadata: bool,
     metadata_compression_type: CompressionType,
     metadata_checksum_type: ChecksumType,
     package_checksum_type: ChecksumType,
@@ -312,6 +891,9 @@ impl Default for RepositoryOptions {
     fn default() -> Self {
         Self {
             simple_metadata_filenames: false,
+            zchunk_metadata: false,
+            #[cfg(feature = "sqlite")]
+            sqlite_metadata: false,
             metadata_compression_type: CompressionType::Gzip,
             metadata_checksum_type: ChecksumType::Sha256,
             package_checksum_type: ChecksumType::Sha256,
@@ -347,86 +929,170 @@ impl RepositoryOptions {
             ..self
         }
     }
+
+    /// Also write zchunk-compressed primary.xml, filelists.xml and other.xml
+    pub fn zchunk_metadata(self, val: bool) -> Self {
+        Self {
+            zchunk_metadata: val,
+            ..self
+        }
+    }
+
+    /// Also write yum-compatible sqlite databases for primary, filelists and other
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite_metadata(self, val: bool) -> Self {
+        Self {
+            sqlite_metadata: val,
+            ..self
+        }
+    }
+}
+
+/// Writes primary.xml.zck, filelists.xml.zck and other.xml.zck, with one chunk per package so that
+/// clients can download only the chunks which have changed since their last sync.
+struct ZchunkMetadataWriter {
+    primary_path: PathBuf,
+    filelists_path: PathBuf,
+    other_path: PathBuf,
+
+    primary_xml_writer: PrimaryXmlWriter<ZchunkWriter<BufWriter<File>>>,
+    filelists_xml_writer: FilelistsXmlWriter<ZchunkWriter<BufWriter<File>>>,
+    other_xml_writer: OtherXmlWriter<ZchunkWriter<BufWriter<File>>>,
+}
+
+impl ZchunkMetadataWriter {
+    fn new(repodata_dir: &Path) -> Result<Self, MetadataError> {
+        let primary_path = repodata_dir.join("primary.xml.zck");
+        let filelists_path = repodata_dir.join("filelists.xml.zck");
+        let other_path = repodata_dir.join("other.xml.zck");
+
+        Ok(Self {
+            primary_xml_writer: PrimaryXml::new_writer(create_zchunk_xml_writer(&primary_path)?),
+            filelists_xml_writer: FilelistsXml::new_writer(create_zchunk_xml_writer(
+                &filelists_path,
+            )?),
+            other_xml_writer: OtherXml::new_writer(create_zchunk_xml_writer(&other_path)?),
+
+            primary_path,
+            filelists_path,
+            other_path,
+        })
+    }
+
+    fn start(&mut self, num_pkgs: usize) -> Result<(), MetadataError> {
+        self.primary_xml_writer.write_header(num_pkgs)?;
+        self.filelists_xml_writer.write_header(num_pkgs)?;
+        self.other_xml_writer.write_header(num_pkgs)?;
+
+        self.primary_xml_writer.inner_mut().end_chunk()?;
+        self.filelists_xml_writer.inner_mut().end_chunk()?;
+        self.other_xml_writer.inner_mut().end_chunk()?;
+        Ok(())
+    }
+
+    fn add_package(&mut self, pkg: &Package) -> Result<(), MetadataError> {
+        self.primary_xml_writer.write_package(pkg)?;
+        self.filelists_xml_writer.write_package(pkg)?;
+        self.other_xml_writer.write_package(pkg)?;
+
+        self.primary_xml_writer.inner_mut().end_chunk()?;
+        self.filelists_xml_writer.inner_mut().end_chunk()?;
+        self.other_xml_writer.inner_mut().end_chunk()?;
+        Ok(())
+    }
+
+    /// Finish writing the files, returning the metadata type and path of each.
+    fn finish(mut self) -> Result<Vec<(&'static str, PathBuf)>, MetadataError> {
+        self.primary_xml_writer.finish()?;
+        self.filelists_xml_writer.finish()?;
+        self.other_xml_writer.finish()?;
+
+        self.primary_xml_writer.into_inner().finish()?;
+        self.filelists_xml_writer.into_inner().finish()?;
+        self.other_xml_writer.into_inner().finish()?;
+
+        Ok(vec![
+            (METADATA_PRIMARY_ZCK, self.primary_path),
+            (METADATA_FILELISTS_ZCK, self.filelists_path),
+            (METADATA_OTHER_ZCK, self.other_path),
+        ])
+    }
 }
 
-// pub fn stream_from_directory(path: &Path) -> Result<StreamingReader, MetadataError> {
-//     let mut repo = Repository::new();
-
-//     repo.load_metadata_file::<RepomdXml>(&path.join("repodata/repomd.xml"))?;
-
-//     let primary_href = path.join(
-//         repo.get_record(METADATA_PRIMARY)
-//             .unwrap()
-//             .location_href
-//             .as_str(),
-//     );
-//     let filelists_href = path.join(
-//         repo.get_record(METADATA_FILELISTS)
-//             .unwrap()
-//             .location_href
-//             .as_str(),
-//     );
-//     let other_href = path.join(
-//         repo.get_record(METADATA_OTHER)
-//             .unwrap()
-//             .location_href
-//             .as_str(),
-//     );
-
-//     let primary_file = File::open(&primary_href)?;
-//     let (primary_file_reader, _compression) = niffler::get_reader(Box::new(primary_file))?;
-//     let mut primary_reader = Reader::from_reader(BufReader::new(primary_file_reader));
-//     configure_reader(&mut primary_reader);
-
-//     let filelists_file = File::open(&filelists_href)?;
-//     let (filelists_file_reader, _compression) = niffler::get_reader(Box::new(filelists_file))?;
-//     let mut filelists_reader = Reader::from_reader(BufReader::new(filelists_file_reader));
-//     configure_reader(&mut filelists_reader);
-
-//     let other_file = File::open(&other_href)?;
-//     let (other_file_reader, _compression) = niffler::get_reader(Box::new(other_file))?;
-//     let mut other_reader = Reader::from_reader(BufReader::new(other_file_reader));
-//     configure_reader(&mut other_reader);
-
-//     Ok(StreamingReader {
-//         primary_reader,
-//         filelists_reader,
-//         other_reader,
-//     })
-// }
+fn create_zchunk_xml_writer(
+    path: &Path,
+) -> Result<Writer<ZchunkWriter<BufWriter<File>>>, MetadataError> {
+    let file = BufWriter::new(File::create(path)?);
+    Ok(Writer::new_with_indent(ZchunkWriter::new(file), b' ', 2))
+}
 
 pub struct RepositoryWriter {
     options: RepositoryOptions,
+    path: PathBuf,
 
-    primary_xml_writer: PrimaryXmlWriter<Box<dyn Write>>,
-    filelists_xml_writer: FilelistsXmlWriter<Box<dyn Write>>,
-    other_xml_writer: OtherXmlWriter<Box<dyn Write>>,
-}
+    primary_path: PathBuf,
+    filelists_path: PathBuf,
+    other_path: PathBuf,
 
-// // Writer<BufWriter<Box<dyn Write>>>
+    primary_xml_writer: PrimaryXmlWriter<CompressedWriter>,
+    filelists_xml_writer: FilelistsXmlWriter<CompressedWriter>,
+    other_xml_writer: OtherXmlWriter<CompressedWriter>,
+
+    zchunk_writer: Option<ZchunkMetadataWriter>,
+    #[cfg(feature = "sqlite")]
+    sqlite_writer: Option<SqliteMetadataWriter>,
+}
 
 impl RepositoryWriter {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, MetadataError> {
-        let options = RepositoryOptions::default();
+        Self::new_with_options(path, RepositoryOptions::default())
+    }
 
+    pub fn new_with_options<P: AsRef<Path>>(
+        path: P,
+        options: RepositoryOptions,
+    ) -> Result<Self, MetadataError> {
         let repodata_dir = path.as_ref().join("repodata");
         std::fs::create_dir_all(&repodata_dir)?;
 
-        let primary_path = repodata_dir.join("primary.xml");
-        let filelists_path = repodata_dir.join("filelists.xml");
-        let other_path = repodata_dir.join("other.xml");
+        let extension = options.metadata_compression_type.to_file_extension();
+        let primary_path = repodata_dir.join(format!("primary.xml{}", extension));
+        let filelists_path = repodata_dir.join(format!("filelists.xml{}", extension));
+        let other_path = repodata_dir.join(format!("other.xml{}", extension));
 
         let primary_writer = create_xml_writer(&primary_path, options.metadata_compression_type)?;
         let filelists_writer =
             create_xml_writer(&filelists_path, options.metadata_compression_type)?;
         let other_writer = create_xml_writer(&other_path, options.metadata_compression_type)?;
 
+        let zchunk_writer = if options.zchunk_metadata {
+            Some(ZchunkMetadataWriter::new(&repodata_dir)?)
+        } else {
+            None
+        };
+
+        #[cfg(feature = "sqlite")]
+        let sqlite_writer = if options.sqlite_metadata {
+            Some(SqliteMetadataWriter::new(&repodata_dir)?)
+        } else {
+            None
+        };
+
         Ok(Self {
-            options: options,
+            options,
+            path: path.as_ref().to_owned(),
+
+            primary_path,
+            filelists_path,
+            other_path,
 
             primary_xml_writer: PrimaryXml::new_writer(primary_writer),
             filelists_xml_writer: FilelistsXml::new_writer(filelists_writer),
             other_xml_writer: OtherXml::new_writer(other_writer),
+
+            zchunk_writer,
+            #[cfg(feature = "sqlite")]
+            sqlite_writer,
         })
     }
 
@@ -434,6 +1100,9 @@ impl RepositoryWriter {
         self.primary_xml_writer.write_header(num_pkgs)?;
         self.filelists_xml_writer.write_header(num_pkgs)?;
         self.other_xml_writer.write_header(num_pkgs)?;
+        if let Some(zchunk_writer) = &mut self.zchunk_writer {
+            zchunk_writer.start(num_pkgs)?;
+        }
         Ok(())
     }
 
@@ -441,36 +1110,300 @@ impl RepositoryWriter {
         self.primary_xml_writer.write_package(pkg)?;
         self.filelists_xml_writer.write_package(pkg)?;
         self.other_xml_writer.write_package(pkg)?;
+        if let Some(zchunk_writer) = &mut self.zchunk_writer {
+            zchunk_writer.add_package(pkg)?;
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(sqlite_writer) = &mut self.sqlite_writer {
+            sqlite_writer.add_package(pkg)?;
+        }
         Ok(())
     }
 
+    /// Read an RPM file and add it to the repository, using the configured package checksum type.
+    pub fn add_package_file(
+        &mut self,
+        path: &Path,
+        location_href: &str,
+    ) -> Result<(), MetadataError> {
+        let pkg = Package::from_rpm_file(path, self.options.package_checksum_type, location_href)?;
+        self.add_package(&pkg)
+    }
+
+    /// Finish writing primary.xml, filelists.xml and other.xml (and their sqlite and zchunk
+    /// variants, if enabled), and write a repomd.xml describing them.
+    ///
+    /// The compressed streams are closed before the files are checksummed, so no packages can be
+    /// added afterwards.
     pub fn finish(&mut self) -> Result<(), MetadataError> {
+        self.finish_with_key(None)
+    }
+
+    /// Like [`RepositoryWriter::finish`], but also sign repomd.xml with `signing_key`, writing a
+    /// detached signature to repomd.xml.asc.
+    pub fn finish_signed(&mut self, signing_key: &SigningKey) -> Result<(), MetadataError> {
+        self.finish_with_key(Some(signing_key))
+    }
+
+    fn finish_with_key(&mut self, signing_key: Option<&SigningKey>) -> Result<(), MetadataError> {
         self.primary_xml_writer.finish()?;
         self.filelists_xml_writer.finish()?;
         self.other_xml_writer.finish()?;
 
-        // let mut repomd_writer = RepomdXml::from_writer(repomd_writer);
-        // match self.options.metadata_checksum_type {}
+        self.primary_xml_writer.inner_mut().finish()?;
+        self.filelists_xml_writer.inner_mut().finish()?;
+        self.other_xml_writer.inner_mut().finish()?;
+
+        let mut repository = Repository::new();
+        repository.add_record(create_record(
+            METADATA_PRIMARY,
+            &self.primary_path,
+            &self.options,
+        )?);
+        repository.add_record(create_record(
+            METADATA_FILELISTS,
+            &self.filelists_path,
+            &self.options,
+        )?);
+        repository.add_record(create_record(
+            METADATA_OTHER,
+            &self.other_path,
+            &self.options,
+        )?);
+        #[cfg(feature = "sqlite")]
+        if let Some(sqlite_writer) = self.sqlite_writer.take() {
+            let db_paths = sqlite_writer.finish(
+                &repository.get_primary_data().checksum,
+                &repository.get_filelist_data().checksum,
+                &repository.get_other_data().checksum,
+            )?;
+            for (mdtype, path) in db_paths {
+                repository.add_record(create_database_record(mdtype, &path, &self.options)?);
+            }
+        }
+        if let Some(zchunk_writer) = self.zchunk_writer.take() {
+            for (mdtype, path) in zchunk_writer.finish()? {
+                repository.add_record(create_record(mdtype, &path, &self.options)?);
+            }
+        }
+        repository.sort_records();
+
+        repository.write_repomd(&self.path.join("repodata"), signing_key)?;
 
         Ok(())
     }
 }
 
-// pub struct StreamingReader {
-//     primary_reader: Reader<BufReader<Box<dyn Read>>>,
-//     filelists_reader: Reader<BufReader<Box<dyn Read>>>,
-//     other_reader: Reader<BufReader<Box<dyn Read>>>,
-// }
+/// Default limit of out-of-order filelists.xml and other.xml entries held by [`PackageIterator`]
+const DEFAULT_MAX_BUFFERED_PACKAGES: usize = 1000;
+
+/// Pull-based parser which yields one fully-populated package at a time.
+///
+/// primary.xml, filelists.xml and other.xml are read in lockstep, so only one package is held in
+/// memory at any time when the packages are listed in the same order in all three files. Entries
+/// of filelists.xml and other.xml listed out of order are buffered until primary.xml reaches them,
+/// see [`PackageIterator::max_buffered_packages`].
+pub struct PackageIterator {
+    primary_xml: PrimaryXmlReader<BufReader<Box<dyn Read>>>,
+    filelists_xml: FilelistsXmlReader<BufReader<Box<dyn Read>>>,
+    other_xml: OtherXmlReader<BufReader<Box<dyn Read>>>,
+
+    num_packages: usize,
+    num_remaining: usize,
+
+    max_buffered_packages: usize,
+    filelists_buffer: HashMap<String, Package>,
+    other_buffer: HashMap<String, Package>,
+}
 
-// impl Iterator for StreamingReader {
-//     type Item = Result<Package, MetadataError>;
-//     fn next(&mut self) -> Option<Self::Item> {
-//         let mut package = Package::default();
+impl PackageIterator {
+    pub fn from_repodata(path: &Path) -> Result<Self, MetadataError> {
+        let mut repo = Repository::new();
+        repo.load_metadata_file::<RepomdXml>(&path.join("repodata/repomd.xml"))?;
 
-//         // super::primary::read_package(package, self.primary_reader)?;
-//         // FilelistsXml::read_into_package(package, self.filelists_reader)?;
-//         // OtherXml::read_into_package(package, self.other_reader)?;
+        let primary_href = path.join(&repo.get_primary_data().location_href);
+        let filelists_href = path.join(&repo.get_filelist_data().location_href);
+        let other_href = path.join(&repo.get_other_data().location_href);
+
+        Self::from_files(&primary_href, &filelists_href, &other_href)
+    }
+
+    pub fn from_files(
+        primary_path: &Path,
+        filelists_path: &Path,
+        other_path: &Path,
+    ) -> Result<Self, MetadataError> {
+        let mut primary_xml = PrimaryXml::new_reader(create_xml_reader(primary_path)?);
+        let mut filelists_xml = FilelistsXml::new_reader(create_xml_reader(filelists_path)?);
+        let mut other_xml = OtherXml::new_reader(create_xml_reader(other_path)?);
+
+        let num_packages = primary_xml.read_header()?;
+        let num_filelists_packages = filelists_xml.read_header()?;
+        let num_other_packages = other_xml.read_header()?;
+
+        if num_packages != num_filelists_packages || num_packages != num_other_packages {
+            return Err(MetadataError::InconsistentMetadataError(format!(
+                "primary.xml, filelists.xml and other.xml declare different numbers of packages ({}, {}, {})",
+                num_packages, num_filelists_packages, num_other_packages
+            )));
+        }
+
+        Ok(Self {
+            primary_xml,
+            filelists_xml,
+            other_xml,
+            num_packages,
+            num_remaining: num_packages,
+
+            max_buffered_packages: DEFAULT_MAX_BUFFERED_PACKAGES,
+            filelists_buffer: HashMap::new(),
+            other_buffer: HashMap::new(),
+        })
+    }
 
-//         Some(Ok(package))
-//     }
-// }
+    /// Limit how many entries of filelists.xml and other.xml are held in memory while they list
+    /// packages in a different order than primary.xml.
+    ///
+    /// Entries which are encountered before the matching primary.xml entry are held in memory until
+    /// they are needed. If more than `max_buffered_packages` entries of either file need to be held
+    /// at once, parsing fails with [`MetadataError::BufferLimitExceededError`]. Defaults to 1000.
+    pub fn max_buffered_packages(self, max_buffered_packages: usize) -> Self {
+        Self {
+            max_buffered_packages,
+            ..self
+        }
+    }
+
+    /// Number of packages declared by the metadata.
+    pub fn total_packages(&self) -> usize {
+        self.num_packages
+    }
+
+    /// Number of packages which have not yet been parsed.
+    pub fn remaining_packages(&self) -> usize {
+        self.num_remaining
+    }
+
+    fn parse_package(&mut self) -> Result<Option<Package>, MetadataError> {
+        let mut package = None;
+        self.primary_xml.read_package(&mut package)?;
+        let mut package = match package {
+            Some(package) => package,
+            None => {
+                self.check_unmatched_entries()?;
+                return Ok(None);
+            }
+        };
+
+        let (_, pkgid) = package.checksum().to_values()?;
+        let pkgid = pkgid.to_owned();
+
+        let filelists_xml = &mut self.filelists_xml;
+        let filelists_entry = find_buffered_entry(
+            &mut self.filelists_buffer,
+            self.max_buffered_packages,
+            &pkgid,
+            "filelists.xml",
+            || filelists_xml.read_entry(),
+        )?;
+        for file in filelists_entry.files() {
+            package.add_file(file.filetype, &file.path);
+        }
+
+        let other_xml = &mut self.other_xml;
+        let other_entry = find_buffered_entry(
+            &mut self.other_buffer,
+            self.max_buffered_packages,
+            &pkgid,
+            "other.xml",
+            || other_xml.read_entry(),
+        )?;
+        for changelog in other_entry.changelogs() {
+            package.add_changelog(&changelog.author, &changelog.description, changelog.date);
+        }
+
+        self.num_remaining = self.num_remaining.saturating_sub(1);
+        Ok(Some(package))
+    }
+
+    /// Once primary.xml is exhausted, any entry of filelists.xml or other.xml left over belongs
+    /// to a package which primary.xml doesn't list.
+    fn check_unmatched_entries(&mut self) -> Result<(), MetadataError> {
+        let mut unmatched = Vec::new();
+        for (metadata_name, buffer) in [
+            ("filelists.xml", &mut self.filelists_buffer),
+            ("other.xml", &mut self.other_buffer),
+        ] {
+            let mut pkgids: Vec<String> = buffer.drain().map(|(pkgid, _)| pkgid).collect();
+            pkgids.sort();
+            unmatched.extend(pkgids.into_iter().map(|pkgid| (metadata_name, pkgid)));
+        }
+        while let Some((pkgid, _)) = self.filelists_xml.read_entry()? {
+            unmatched.push(("filelists.xml", pkgid));
+        }
+        while let Some((pkgid, _)) = self.other_xml.read_entry()? {
+            unmatched.push(("other.xml", pkgid));
+        }
+
+        if unmatched.is_empty() {
+            return Ok(());
+        }
+        let entries: Vec<String> = unmatched
+            .iter()
+            .map(|(metadata_name, pkgid)| format!("{} {}", metadata_name, pkgid))
+            .collect();
+        Err(MetadataError::InconsistentMetadataError(format!(
+            "entries without a matching package in primary.xml: {}",
+            entries.join(", ")
+        )))
+    }
+}
+
+/// Find the entry for `pkgid`, either in `buffer` or by reading ahead and buffering the entries
+/// which are skipped over.
+fn find_buffered_entry<F>(
+    buffer: &mut HashMap<String, Package>,
+    max_buffered_packages: usize,
+    pkgid: &str,
+    metadata_name: &str,
+    mut read_entry: F,
+) -> Result<Package, MetadataError>
+where
+    F: FnMut() -> Result<Option<(String, Package)>, MetadataError>,
+{
+    if let Some(entry) = buffer.remove(pkgid) {
+        return Ok(entry);
+    }
+
+    while let Some((entry_pkgid, entry)) = read_entry()? {
+        if entry_pkgid == pkgid {
+            return Ok(entry);
+        }
+
+        if buffer.contains_key(&entry_pkgid) {
+            return Err(MetadataError::InconsistentMetadataError(format!(
+                "{} has multiple entries for package {}",
+                metadata_name, entry_pkgid
+            )));
+        }
+        if buffer.len() >= max_buffered_packages {
+            return Err(MetadataError::BufferLimitExceededError(
+                max_buffered_packages,
+            ));
+        }
+        buffer.insert(entry_pkgid, entry);
+    }
+
+    Err(MetadataError::InconsistentMetadataError(format!(
+        "{} has no entry for package {}",
+        metadata_name, pkgid
+    )))
+}
+
+impl Iterator for PackageIterator {
+    type Item = Result<Package, MetadataError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parse_package().transpose()
+    }
+}
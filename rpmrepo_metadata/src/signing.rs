@@ -0,0 +1,299 @@
+use std::fmt;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::packet::{SignatureConfig, SignatureType, SignatureVersion, Subpacket};
+use pgp::types::{KeyTrait, SecretKeyTrait};
+use pgp::{Deserializable, SignedPublicKey, SignedSecretKey, StandaloneSignature};
+
+use super::{utils, MetadataError};
+
+/// An OpenPGP secret key used to create detached signatures of repomd.xml (repomd.xml.asc).
+///
+/// Signatures are always made with the primary key, which therefore has to be usable for
+/// signing.
+#[derive(Clone)]
+pub struct SigningKey {
+    key: SignedSecretKey,
+    passphrase: String,
+}
+
+impl SigningKey {
+    /// Read an ASCII-armored secret key, as exported by `gpg --armor --export-secret-keys`.
+    pub fn from_file(path: &Path, passphrase: Option<&str>) -> Result<Self, MetadataError> {
+        Self::from_armored_str(&std::fs::read_to_string(path)?, passphrase)
+    }
+
+    pub fn from_armored_str(
+        armored: &str,
+        passphrase: Option<&str>,
+    ) -> Result<Self, MetadataError> {
+        let (key, _headers) = SignedSecretKey::from_armor_single(Cursor::new(armored))?;
+        key.verify()?;
+
+        let signing_key = SigningKey {
+            key,
+            passphrase: passphrase.unwrap_or_default().to_owned(),
+        };
+        // fail early on a wrong passphrase rather than when the first signature is made
+        signing_key
+            .key
+            .unlock(|| signing_key.passphrase.clone(), |_| Ok(()))?;
+
+        Ok(signing_key)
+    }
+
+    /// Create a detached, ASCII-armored signature of `data`.
+    pub fn sign(&self, data: &[u8]) -> Result<String, MetadataError> {
+        let config = SignatureConfig::new_v4(
+            SignatureVersion::V4,
+            SignatureType::Binary,
+            self.key.algorithm(),
+            HashAlgorithm::SHA2_256,
+            vec![
+                Subpacket::SignatureCreationTime(chrono::Utc::now()),
+                Subpacket::Issuer(self.key.key_id()),
+            ],
+            vec![],
+        );
+        let signature = config.sign(&self.key, || self.passphrase.clone(), data)?;
+
+        Ok(StandaloneSignature::new(signature).to_armored_string(None)?)
+    }
+
+    /// Sign the file at `path`, writing the signature next to it as `<path>.asc`.
+    pub fn sign_file(&self, path: &Path) -> Result<PathBuf, MetadataError> {
+        let signature = self.sign(&std::fs::read(path)?)?;
+        let signature_path = signature_path(path);
+        std::fs::write(&signature_path, signature)?;
+        Ok(signature_path)
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // never print the passphrase
+        f.debug_struct("SigningKey")
+            .field("key_id", &self.key.key_id())
+            .finish()
+    }
+}
+
+/// An OpenPGP public key used to verify detached signatures of repomd.xml.
+#[derive(Debug, Clone)]
+pub struct VerifyingKey {
+    key: SignedPublicKey,
+}
+
+impl VerifyingKey {
+    /// Read an ASCII-armored public key, as exported by `gpg --armor --export`.
+    pub fn from_file(path: &Path) -> Result<Self, MetadataError> {
+        Self::from_armored_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_armored_str(armored: &str) -> Result<Self, MetadataError> {
+        let (key, _headers) = SignedPublicKey::from_armor_single(Cursor::new(armored))?;
+        key.verify()?;
+        Ok(VerifyingKey { key })
+    }
+
+    /// Whether `signature` is a valid signature of `data` made by this key or one of its subkeys.
+    fn has_signed(&self, signature: &StandaloneSignature, data: &[u8]) -> bool {
+        signature.verify(&self.key, data).is_ok()
+            || self
+                .key
+                .public_subkeys
+                .iter()
+                .any(|subkey| signature.verify(subkey, data).is_ok())
+    }
+}
+
+/// Check that `armored_signature` is a valid detached signature of `data` made by any of `keys`.
+pub fn verify_signature(
+    data: &[u8],
+    armored_signature: &[u8],
+    keys: &[VerifyingKey],
+) -> Result<(), MetadataError> {
+    let (signature, _headers) =
+        StandaloneSignature::from_armor_single(Cursor::new(armored_signature)).map_err(|e| {
+            MetadataError::SignatureVerificationError(format!("invalid signature: {}", e))
+        })?;
+
+    if keys.iter().any(|key| key.has_signed(&signature, data)) {
+        Ok(())
+    } else {
+        Err(MetadataError::SignatureVerificationError(
+            "the signature doesn't match any of the trusted keys".to_owned(),
+        ))
+    }
+}
+
+/// Result of checking the signatures of an RPM package.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageSignatureStatus {
+    /// All of the signatures of the package were made by trusted keys
+    Valid,
+    /// The package has no signature at all
+    Unsigned,
+    /// The package is signed, but not (correctly) by any trusted key
+    Invalid(String),
+}
+
+/// What to do with packages which are unsigned or whose signatures can't be verified.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PackageSignaturePolicy {
+    /// Refuse the packages
+    Reject,
+    /// Accept the packages, but warn about them
+    Warn,
+    /// Accept the packages, they are only listed in the summary
+    Allow,
+}
+
+impl std::str::FromStr for PackageSignaturePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(PackageSignaturePolicy::Reject),
+            "warn" => Ok(PackageSignaturePolicy::Warn),
+            "allow" => Ok(PackageSignaturePolicy::Allow),
+            _ => Err(format!(
+                "unknown signature policy {}, expected reject, warn or allow",
+                s
+            )),
+        }
+    }
+}
+
+/// Packages which failed signature verification, by location_href.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PackageSignatureSummary {
+    pub checked: usize,
+    pub unsigned: Vec<String>,
+    pub invalid: Vec<(String, String)>,
+}
+
+impl PackageSignatureSummary {
+    pub fn add(&mut self, location_href: &str, status: PackageSignatureStatus) {
+        self.checked += 1;
+        match status {
+            PackageSignatureStatus::Valid => (),
+            PackageSignatureStatus::Unsigned => self.unsigned.push(location_href.to_owned()),
+            PackageSignatureStatus::Invalid(reason) => {
+                self.invalid.push((location_href.to_owned(), reason))
+            }
+        }
+    }
+
+    /// Whether every checked package had a valid signature
+    pub fn all_valid(&self) -> bool {
+        self.unsigned.is_empty() && self.invalid.is_empty()
+    }
+}
+
+impl fmt::Display for PackageSignatureSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} packages checked, {} unsigned, {} with an invalid signature",
+            self.checked,
+            self.unsigned.len(),
+            self.invalid.len()
+        )?;
+        for location_href in &self.unsigned {
+            write!(f, "\n  {}: unsigned", location_href)?;
+        }
+        for (location_href, reason) in &self.invalid {
+            write!(f, "\n  {}: {}", location_href, reason)?;
+        }
+        Ok(())
+    }
+}
+
+// OpenPGP signature tags of the signature header, see rpmtag.h
+const RPMSIGTAG_DSA: u32 = 267; // header only
+const RPMSIGTAG_RSA: u32 = 268; // header only
+const RPMSIGTAG_PGP: u32 = 1002; // header and payload
+const RPMSIGTAG_GPG: u32 = 1005; // header and payload
+
+/// Check the OpenPGP signatures of the RPM package at `path` against `keys`.
+///
+/// Like `rpmkeys --checksig`, both the header-only and the header+payload signatures are
+/// checked, and every signature present has to be valid.
+pub fn verify_package_signature(
+    path: &Path,
+    keys: &[VerifyingKey],
+) -> Result<PackageSignatureStatus, MetadataError> {
+    let contents = std::fs::read(path)?;
+
+    let header_range = utils::rpm_header_range(&mut contents.as_slice());
+    let (signatures, (header_start, header_end)) = match (signatures(&contents), header_range) {
+        (Some(signatures), Ok((start, end))) => (signatures, (start as usize, end as usize)),
+        _ => {
+            return Ok(PackageSignatureStatus::Invalid(
+                "not a valid RPM package".to_owned(),
+            ))
+        }
+    };
+
+    if signatures.is_empty() {
+        return Ok(PackageSignatureStatus::Unsigned);
+    }
+
+    for (tag, signature_bytes) in signatures {
+        let (signed_data, description) = match tag {
+            RPMSIGTAG_DSA | RPMSIGTAG_RSA => (&contents[header_start..header_end], "header"),
+            _ => (&contents[header_start..], "header+payload"),
+        };
+        let signature = match StandaloneSignature::from_bytes(signature_bytes) {
+            Ok(signature) => signature,
+            Err(e) => {
+                return Ok(PackageSignatureStatus::Invalid(format!(
+                    "unreadable {} signature: {}",
+                    description, e
+                )))
+            }
+        };
+        if !keys
+            .iter()
+            .any(|key| key.has_signed(&signature, signed_data))
+        {
+            return Ok(PackageSignatureStatus::Invalid(format!(
+                "{} signature doesn't match any of the trusted keys",
+                description
+            )));
+        }
+    }
+
+    Ok(PackageSignatureStatus::Valid)
+}
+
+/// The OpenPGP signatures found in the signature header, by tag.
+fn signatures(bytes: &[u8]) -> Option<Vec<(u32, &[u8])>> {
+    let (entry_count, store_size) = utils::rpm_header_sizes(bytes, utils::RPM_LEAD_SIZE)?;
+    let index_start = utils::RPM_LEAD_SIZE + 16;
+    let store_start = index_start.checked_add(entry_count.checked_mul(16)?)?;
+    let store = bytes.get(store_start..store_start.checked_add(store_size)?)?;
+
+    let mut signatures = Vec::new();
+    for entry in 0..entry_count {
+        // tag, type, offset, count
+        let entry_start = index_start + entry * 16;
+        let tag = utils::read_u32(bytes, entry_start)?;
+        if let RPMSIGTAG_DSA | RPMSIGTAG_RSA | RPMSIGTAG_PGP | RPMSIGTAG_GPG = tag {
+            let offset = utils::read_u32(bytes, entry_start + 8)? as usize;
+            let size = utils::read_u32(bytes, entry_start + 12)? as usize;
+            signatures.push((tag, store.get(offset..offset.checked_add(size)?)?));
+        }
+    }
+    Some(signatures)
+}
+
+/// Path of the detached signature of the file at `path`.
+pub(crate) fn signature_path(path: &Path) -> PathBuf {
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".asc");
+    PathBuf::from(signature_path)
+}
@@ -0,0 +1,614 @@
+//! yum-compatible sqlite databases (primary.sqlite, filelists.sqlite and other.sqlite).
+//!
+//! The schema matches version 10 of the databases written by createrepo and createrepo_c.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OpenFlags};
+use tempdir::TempDir;
+
+use super::metadata::{
+    Checksum, CompressionType, FileType, Package, Requirement, METADATA_FILELISTS_DB,
+    METADATA_OTHER_DB, METADATA_PRIMARY_DB,
+};
+use super::primary;
+use super::utils::{self, CompressedWriter};
+use super::{MetadataError, EVR};
+
+/// Version of the database schema, recorded in the db_info table and in repomd.xml.
+pub(crate) const DATABASE_VERSION: u32 = 10;
+
+const PRIMARY_SCHEMA: &str = "
+    CREATE TABLE db_info (dbversion INTEGER, checksum TEXT);
+    CREATE TABLE packages (
+        pkgKey INTEGER PRIMARY KEY,
+        pkgId TEXT,
+        name TEXT,
+        arch TEXT,
+        version TEXT,
+        epoch TEXT,
+        release TEXT,
+        summary TEXT,
+        description TEXT,
+        url TEXT,
+        time_file INTEGER,
+        time_build INTEGER,
+        rpm_license TEXT,
+        rpm_vendor TEXT,
+        rpm_group TEXT,
+        rpm_buildhost TEXT,
+        rpm_sourcerpm TEXT,
+        rpm_header_start INTEGER,
+        rpm_header_end INTEGER,
+        rpm_packager TEXT,
+        size_package INTEGER,
+        size_installed INTEGER,
+        size_archive INTEGER,
+        location_href TEXT,
+        location_base TEXT,
+        checksum_type TEXT);
+    CREATE TABLE files (name TEXT, type TEXT, pkgKey INTEGER);
+    CREATE TABLE requires (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER, pre BOOLEAN DEFAULT FALSE);
+    CREATE TABLE provides (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER);
+    CREATE TABLE conflicts (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER);
+    CREATE TABLE obsoletes (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER);
+    CREATE TABLE suggests (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER);
+    CREATE TABLE enhances (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER);
+    CREATE TABLE recommends (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER);
+    CREATE TABLE supplements (name TEXT, flags TEXT, epoch TEXT, version TEXT, release TEXT, pkgKey INTEGER);
+    CREATE TRIGGER removals AFTER DELETE ON packages
+        BEGIN
+            DELETE FROM files WHERE pkgKey = old.pkgKey;
+            DELETE FROM requires WHERE pkgKey = old.pkgKey;
+            DELETE FROM provides WHERE pkgKey = old.pkgKey;
+            DELETE FROM conflicts WHERE pkgKey = old.pkgKey;
+            DELETE FROM obsoletes WHERE pkgKey = old.pkgKey;
+            DELETE FROM suggests WHERE pkgKey = old.pkgKey;
+            DELETE FROM enhances WHERE pkgKey = old.pkgKey;
+            DELETE FROM recommends WHERE pkgKey = old.pkgKey;
+            DELETE FROM supplements WHERE pkgKey = old.pkgKey;
+        END;
+";
+
+const PRIMARY_INDEXES: &str = "
+    CREATE INDEX packagename ON packages (name);
+    CREATE INDEX packageId ON packages (pkgId);
+    CREATE INDEX filenames ON files (name);
+    CREATE INDEX pkgfiles ON files (pkgKey);
+    CREATE INDEX pkgrequires on requires (pkgKey);
+    CREATE INDEX requiresname ON requires (name);
+    CREATE INDEX pkgprovides on provides (pkgKey);
+    CREATE INDEX providesname ON provides (name);
+    CREATE INDEX pkgconflicts on conflicts (pkgKey);
+    CREATE INDEX pkgobsoletes on obsoletes (pkgKey);
+    CREATE INDEX pkgsuggests on suggests (pkgKey);
+    CREATE INDEX pkgenhances on enhances (pkgKey);
+    CREATE INDEX pkgrecommends on recommends (pkgKey);
+    CREATE INDEX pkgsupplements on supplements (pkgKey);
+";
+
+const FILELISTS_SCHEMA: &str = "
+    CREATE TABLE db_info (dbversion INTEGER, checksum TEXT);
+    CREATE TABLE packages (pkgKey INTEGER PRIMARY KEY, pkgId TEXT);
+    CREATE TABLE filelist (pkgKey INTEGER, dirname TEXT, filenames TEXT, filetypes TEXT);
+    CREATE TRIGGER remove_filelist AFTER DELETE ON packages
+        BEGIN
+            DELETE FROM filelist WHERE pkgKey = old.pkgKey;
+        END;
+";
+
+const FILELISTS_INDEXES: &str = "
+    CREATE INDEX keyfile ON filelist (pkgKey);
+    CREATE INDEX pkgId ON packages (pkgId);
+    CREATE INDEX dirnames ON filelist (dirname);
+";
+
+const OTHER_SCHEMA: &str = "
+    CREATE TABLE db_info (dbversion INTEGER, checksum TEXT);
+    CREATE TABLE packages (pkgKey INTEGER PRIMARY KEY, pkgId TEXT);
+    CREATE TABLE changelog (pkgKey INTEGER, author TEXT, date INTEGER, changelog TEXT);
+    CREATE TRIGGER remove_changelogs AFTER DELETE ON packages
+        BEGIN
+            DELETE FROM changelog WHERE pkgKey = old.pkgKey;
+        END;
+";
+
+const OTHER_INDEXES: &str = "
+    CREATE INDEX keychange ON changelog (pkgKey);
+    CREATE INDEX pkgId ON packages (pkgId);
+";
+
+/// Writes primary.sqlite.bz2, filelists.sqlite.bz2 and other.sqlite.bz2, one package at a time.
+///
+/// The databases are built uncompressed in the repodata directory and compressed by
+/// [`SqliteMetadataWriter::finish`].
+pub(crate) struct SqliteMetadataWriter {
+    primary_path: PathBuf,
+    filelists_path: PathBuf,
+    other_path: PathBuf,
+
+    primary_db: Connection,
+    filelists_db: Connection,
+    other_db: Connection,
+
+    pkg_key: i64,
+}
+
+impl SqliteMetadataWriter {
+    pub fn new(repodata_dir: &Path) -> Result<Self, MetadataError> {
+        let primary_path = repodata_dir.join("primary.sqlite");
+        let filelists_path = repodata_dir.join("filelists.sqlite");
+        let other_path = repodata_dir.join("other.sqlite");
+
+        Ok(Self {
+            primary_db: create_database(&primary_path, PRIMARY_SCHEMA)?,
+            filelists_db: create_database(&filelists_path, FILELISTS_SCHEMA)?,
+            other_db: create_database(&other_path, OTHER_SCHEMA)?,
+
+            primary_path,
+            filelists_path,
+            other_path,
+
+            pkg_key: 0,
+        })
+    }
+
+    pub fn add_package(&mut self, pkg: &Package) -> Result<(), MetadataError> {
+        self.pkg_key += 1;
+        let pkg_key = self.pkg_key;
+        let (checksum_type, pkgid) = pkg.checksum().to_values()?;
+
+        self.primary_db
+            .prepare_cached(
+                "INSERT INTO packages (
+                    pkgKey, pkgId, name, arch, version, epoch, release, summary, description, url,
+                    time_file, time_build, rpm_license, rpm_vendor, rpm_group, rpm_buildhost,
+                    rpm_sourcerpm, rpm_header_start, rpm_header_end, rpm_packager, size_package,
+                    size_installed, size_archive, location_href, location_base, checksum_type)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, NULL, ?)",
+            )?
+            .execute(params![
+                pkg_key,
+                pkgid,
+                pkg.name(),
+                pkg.arch(),
+                pkg.evr().version,
+                pkg.evr().epoch,
+                pkg.evr().release,
+                pkg.summary(),
+                pkg.description(),
+                pkg.url(),
+                pkg.time().file as i64,
+                pkg.time().build as i64,
+                pkg.rpm_license(),
+                pkg.rpm_vendor(),
+                pkg.rpm_group(),
+                pkg.rpm_buildhost(),
+                pkg.rpm_sourcerpm(),
+                pkg.rpm_header_range().start as i64,
+                pkg.rpm_header_range().end as i64,
+                pkg.packager(),
+                pkg.size().package as i64,
+                pkg.size().installed as i64,
+                pkg.size().archive as i64,
+                pkg.location_href(),
+                checksum_type,
+            ])?;
+
+        for (table, requirements) in &[
+            ("requires", pkg.requires()),
+            ("provides", pkg.provides()),
+            ("conflicts", pkg.conflicts()),
+            ("obsoletes", pkg.obsoletes()),
+            ("suggests", pkg.suggests()),
+            ("enhances", pkg.enhances()),
+            ("recommends", pkg.recommends()),
+            ("supplements", pkg.supplements()),
+        ] {
+            insert_requirements(&self.primary_db, table, pkg_key, requirements)?;
+        }
+
+        // primary.sqlite lists the same subset of paths as primary.xml, but of every file type
+        for file in pkg.files() {
+            if primary::is_primary_file(file) {
+                let filetype = match file.filetype {
+                    FileType::File => "file",
+                    FileType::Dir => "dir",
+                    FileType::Ghost => "ghost",
+                };
+                self.primary_db
+                    .prepare_cached("INSERT INTO files (name, type, pkgKey) VALUES (?, ?, ?)")?
+                    .execute(params![file.path, filetype, pkg_key])?;
+            }
+        }
+
+        self.filelists_db
+            .prepare_cached("INSERT INTO packages (pkgKey, pkgId) VALUES (?, ?)")?
+            .execute(params![pkg_key, pkgid])?;
+
+        // consecutive files in the same directory share a row
+        let mut dirs: Vec<(&str, Vec<&str>, String)> = Vec::new();
+        for file in pkg.files() {
+            let (dirname, filename) = split_path(&file.path);
+            let filetype = match file.filetype {
+                FileType::File => 'f',
+                FileType::Dir => 'd',
+                FileType::Ghost => 'g',
+            };
+            match dirs.last_mut() {
+                Some((last_dirname, filenames, filetypes)) if *last_dirname == dirname => {
+                    filenames.push(filename);
+                    filetypes.push(filetype);
+                }
+                _ => dirs.push((dirname, vec![filename], filetype.to_string())),
+            }
+        }
+        for (dirname, filenames, filetypes) in dirs {
+            self.filelists_db
+                .prepare_cached(
+                    "INSERT INTO filelist (pkgKey, dirname, filenames, filetypes) VALUES (?, ?, ?, ?)",
+                )?
+                .execute(params![pkg_key, dirname, filenames.join("/"), filetypes])?;
+        }
+
+        self.other_db
+            .prepare_cached("INSERT INTO packages (pkgKey, pkgId) VALUES (?, ?)")?
+            .execute(params![pkg_key, pkgid])?;
+
+        for changelog in pkg.changelogs() {
+            self.other_db
+                .prepare_cached(
+                    "INSERT INTO changelog (pkgKey, author, date, changelog) VALUES (?, ?, ?, ?)",
+                )?
+                .execute(params![
+                    pkg_key,
+                    changelog.author,
+                    changelog.date as i64,
+                    changelog.description
+                ])?;
+        }
+
+        Ok(())
+    }
+
+    /// Finish the databases and compress them, returning the metadata type and path of each.
+    ///
+    /// The checksums are those of the corresponding XML files, which yum uses to tell whether a
+    /// database is up to date.
+    pub fn finish(
+        self,
+        primary_checksum: &Checksum,
+        filelists_checksum: &Checksum,
+        other_checksum: &Checksum,
+    ) -> Result<Vec<(&'static str, PathBuf)>, MetadataError> {
+        finish_database(self.primary_db, PRIMARY_INDEXES, primary_checksum)?;
+        finish_database(self.filelists_db, FILELISTS_INDEXES, filelists_checksum)?;
+        finish_database(self.other_db, OTHER_INDEXES, other_checksum)?;
+
+        Ok(vec![
+            (METADATA_PRIMARY_DB, compress_database(&self.primary_path)?),
+            (
+                METADATA_FILELISTS_DB,
+                compress_database(&self.filelists_path)?,
+            ),
+            (METADATA_OTHER_DB, compress_database(&self.other_path)?),
+        ])
+    }
+}
+
+/// Read the packages described by primary.sqlite, filelists.sqlite and other.sqlite.
+///
+/// The databases may be compressed with any format niffler understands. The packages are
+/// returned in the order they appear in primary.sqlite.
+pub(crate) fn read_packages(
+    primary_path: &Path,
+    filelists_path: &Path,
+    other_path: &Path,
+) -> Result<Vec<Package>, MetadataError> {
+    // sqlite can only open files, so compressed databases are unpacked into a scratch directory
+    let scratch_dir = TempDir::new("rpmrepo_sqlite")?;
+    let primary_db = open_database(primary_path, &scratch_dir.path().join("primary.sqlite"))?;
+    let filelists_db = open_database(filelists_path, &scratch_dir.path().join("filelists.sqlite"))?;
+    let other_db = open_database(other_path, &scratch_dir.path().join("other.sqlite"))?;
+
+    let mut packages = read_primary_packages(&primary_db)?;
+
+    let pkg_keys: HashMap<String, i64> = packages
+        .iter()
+        .map(|(pkg_key, pkg)| Ok((pkg.checksum().to_values()?.1.to_owned(), *pkg_key)))
+        .collect::<Result<_, MetadataError>>()?;
+
+    let mut statement = filelists_db.prepare(
+        "SELECT pkgId, dirname, filenames, filetypes
+         FROM filelist INNER JOIN packages USING (pkgKey)
+         ORDER BY filelist.rowid",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let pkgid: String = row.get(0)?;
+        let dirname: String = row.get(1)?;
+        let filenames: String = row.get(2)?;
+        let filetypes: String = row.get(3)?;
+
+        let package = find_package(&mut packages, &pkg_keys, &pkgid)?;
+        for (filename, filetype) in filenames.split('/').zip(filetypes.chars()) {
+            let filetype = match filetype {
+                'd' => FileType::Dir,
+                'g' => FileType::Ghost,
+                _ => FileType::File,
+            };
+            let path = match dirname.as_str() {
+                "/" => format!("/{}", filename),
+                _ => format!("{}/{}", dirname, filename),
+            };
+            package.add_file(filetype, &path);
+        }
+    }
+
+    let mut statement = other_db.prepare(
+        "SELECT pkgId, author, date, changelog
+         FROM changelog INNER JOIN packages USING (pkgKey)
+         ORDER BY changelog.rowid",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let pkgid: String = row.get(0)?;
+        let author: String = row.get(1)?;
+        let date: i64 = row.get(2)?;
+        let description: String = row.get(3)?;
+
+        let package = find_package(&mut packages, &pkg_keys, &pkgid)?;
+        package.add_changelog(&author, &description, date as u64);
+    }
+
+    Ok(packages.into_values().collect())
+}
+
+/// Find the package with `pkgid` among the packages of primary.sqlite, by their pkgKey.
+fn find_package<'a>(
+    packages: &'a mut BTreeMap<i64, Package>,
+    pkg_keys: &HashMap<String, i64>,
+    pkgid: &str,
+) -> Result<&'a mut Package, MetadataError> {
+    pkg_keys
+        .get(pkgid)
+        .and_then(move |pkg_key| packages.get_mut(pkg_key))
+        .ok_or_else(|| {
+            MetadataError::InconsistentMetadataError(format!(
+                "primary.sqlite has no entry for package {}",
+                pkgid
+            ))
+        })
+}
+
+fn open_database(path: &Path, scratch_path: &Path) -> Result<Connection, MetadataError> {
+    let (mut reader, _) = utils::decompress_reader(Box::new(File::open(path)?))?;
+    std::io::copy(
+        &mut reader,
+        &mut BufWriter::new(File::create(scratch_path)?),
+    )?;
+    Ok(Connection::open_with_flags(
+        scratch_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?)
+}
+
+fn read_primary_packages(db: &Connection) -> Result<BTreeMap<i64, Package>, MetadataError> {
+    let mut packages = BTreeMap::new();
+
+    let mut statement = db.prepare(
+        "SELECT pkgKey, pkgId, checksum_type, name, arch, epoch, version, release, summary,
+                description, url, time_file, time_build, rpm_license, rpm_vendor, rpm_group,
+                rpm_buildhost, rpm_sourcerpm, rpm_header_start, rpm_header_end, rpm_packager,
+                size_package, size_installed, size_archive, location_href
+         FROM packages",
+    )?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        // createrepo and createrepo_c disagree on whether empty fields are stored as NULL
+        let text = |idx: usize| -> rusqlite::Result<String> {
+            Ok(row.get::<_, Option<String>>(idx)?.unwrap_or_default())
+        };
+        let integer = |idx: usize| -> rusqlite::Result<u64> {
+            Ok(row.get::<_, Option<i64>>(idx)?.unwrap_or_default() as u64)
+        };
+
+        let pkg_key: i64 = row.get(0)?;
+        let checksum = Checksum::try_create(text(2)?, text(1)?)?;
+        let evr = EVR::new(&text(5)?, &text(6)?, &text(7)?);
+
+        let mut package = Package::new(&text(3)?, &evr, &text(4)?, &checksum, &text(24)?);
+        package
+            .set_summary(&text(8)?)
+            .set_description(&text(9)?)
+            .set_url(&text(10)?)
+            .set_time(integer(11)?, integer(12)?)
+            .set_rpm_license(&text(13)?)
+            .set_rpm_vendor(&text(14)?)
+            .set_rpm_group(&text(15)?)
+            .set_rpm_buildhost(&text(16)?)
+            .set_rpm_sourcerpm(&text(17)?)
+            .set_rpm_header_range(integer(18)?, integer(19)?)
+            .set_packager(&text(20)?)
+            .set_size(integer(21)?, integer(22)?, integer(23)?);
+
+        packages.insert(pkg_key, package);
+    }
+
+    let mut requires = read_requirements(db, "requires")?;
+    let mut provides = read_requirements(db, "provides")?;
+    let mut conflicts = read_requirements(db, "conflicts")?;
+    let mut obsoletes = read_requirements(db, "obsoletes")?;
+    // the weak dependency tables are missing from databases written by createrepo
+    let mut suggests = read_weak_requirements(db, "suggests")?;
+    let mut enhances = read_weak_requirements(db, "enhances")?;
+    let mut recommends = read_weak_requirements(db, "recommends")?;
+    let mut supplements = read_weak_requirements(db, "supplements")?;
+
+    for (pkg_key, package) in packages.iter_mut() {
+        package
+            .set_requires(requires.remove(pkg_key).unwrap_or_default())
+            .set_provides(provides.remove(pkg_key).unwrap_or_default())
+            .set_conflicts(conflicts.remove(pkg_key).unwrap_or_default())
+            .set_obsoletes(obsoletes.remove(pkg_key).unwrap_or_default())
+            .set_suggests(suggests.remove(pkg_key).unwrap_or_default())
+            .set_enhances(enhances.remove(pkg_key).unwrap_or_default())
+            .set_recommends(recommends.remove(pkg_key).unwrap_or_default())
+            .set_supplements(supplements.remove(pkg_key).unwrap_or_default());
+    }
+
+    Ok(packages)
+}
+
+/// Like [`read_requirements`], but a missing table is treated as empty.
+fn read_weak_requirements(
+    db: &Connection,
+    table: &str,
+) -> Result<HashMap<i64, Vec<Requirement>>, MetadataError> {
+    let table_exists: bool = db.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?",
+        [table],
+        |row| row.get(0),
+    )?;
+    if !table_exists {
+        return Ok(HashMap::new());
+    }
+    read_requirements(db, table)
+}
+
+fn read_requirements(
+    db: &Connection,
+    table: &str,
+) -> Result<HashMap<i64, Vec<Requirement>>, MetadataError> {
+    let mut requirements: HashMap<i64, Vec<Requirement>> = HashMap::new();
+
+    let pre_column = if table == "requires" { "pre" } else { "NULL" };
+    let mut statement = db.prepare(&format!(
+        "SELECT pkgKey, name, flags, epoch, version, release, {} FROM {} ORDER BY rowid",
+        pre_column, table
+    ))?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        // createrepo stores "TRUE" / "FALSE", createrepo_c stores integers
+        let preinstall = match row.get::<_, Value>(6)? {
+            Value::Integer(value) => value != 0,
+            Value::Text(value) => value.eq_ignore_ascii_case("true") || value == "1",
+            _ => false,
+        };
+
+        requirements
+            .entry(row.get(0)?)
+            .or_default()
+            .push(Requirement {
+                name: row.get(1)?,
+                flags: row.get(2)?,
+                epoch: row.get(3)?,
+                version: row.get(4)?,
+                release: row.get(5)?,
+                preinstall: if preinstall { Some(true) } else { None },
+            });
+    }
+
+    Ok(requirements)
+}
+
+fn create_database(path: &Path, schema: &str) -> Result<Connection, MetadataError> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+
+    let connection = Connection::open(path)?;
+    connection.execute_batch(
+        "PRAGMA synchronous = OFF;
+         PRAGMA journal_mode = MEMORY;",
+    )?;
+    connection.execute_batch(schema)?;
+    // everything is written in one transaction, committed by finish_database()
+    connection.execute_batch("BEGIN;")?;
+
+    Ok(connection)
+}
+
+fn finish_database(
+    connection: Connection,
+    indexes: &str,
+    checksum: &Checksum,
+) -> Result<(), MetadataError> {
+    let (_, checksum) = checksum.to_values()?;
+    connection.execute(
+        "INSERT INTO db_info (dbversion, checksum) VALUES (?, ?)",
+        params![DATABASE_VERSION, checksum],
+    )?;
+    connection.execute_batch(indexes)?;
+    connection.execute_batch("COMMIT;")?;
+    connection.close().map_err(|(_, e)| e)?;
+    Ok(())
+}
+
+/// Compress the database with bzip2 and remove the uncompressed copy.
+fn compress_database(path: &Path) -> Result<PathBuf, MetadataError> {
+    let compressed_path = path.with_extension("sqlite.bz2");
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut writer = CompressedWriter::create(&compressed_path, CompressionType::Bz2)?;
+    std::io::copy(&mut reader, &mut writer)?;
+    writer.finish()?;
+    std::fs::remove_file(path)?;
+
+    Ok(compressed_path)
+}
+
+fn insert_requirements(
+    connection: &Connection,
+    table: &str,
+    pkg_key: i64,
+    requirements: &[Requirement],
+) -> Result<(), MetadataError> {
+    for requirement in requirements {
+        if table == "requires" {
+            connection
+                .prepare_cached(
+                    "INSERT INTO requires (name, flags, epoch, version, release, pkgKey, pre)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )?
+                .execute(params![
+                    requirement.name,
+                    requirement.flags,
+                    requirement.epoch,
+                    requirement.version,
+                    requirement.release,
+                    pkg_key,
+                    requirement.preinstall.unwrap_or(false),
+                ])?;
+        } else {
+            connection
+                .prepare_cached(&format!(
+                    "INSERT INTO {} (name, flags, epoch, version, release, pkgKey)
+                     VALUES (?, ?, ?, ?, ?, ?)",
+                    table
+                ))?
+                .execute(params![
+                    requirement.name,
+                    requirement.flags,
+                    requirement.epoch,
+                    requirement.version,
+                    requirement.release,
+                    pkg_key,
+                ])?;
+        }
+    }
+    Ok(())
+}
+
+/// Split a path into directory and filename the way yum does, so "/usr" becomes ("/", "usr").
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some(("", filename)) => ("/", filename),
+        Some((dirname, filename)) => (dirname, filename),
+        None => ("", path),
+    }
+}
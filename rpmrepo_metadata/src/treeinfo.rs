@@ -0,0 +1,466 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use super::metadata::Checksum;
+use super::MetadataError;
+
+const SECTION_HEADER: &str = "header";
+const SECTION_RELEASE: &str = "release";
+const SECTION_BASE_PRODUCT: &str = "base_product";
+const SECTION_TREE: &str = "tree";
+const SECTION_STAGE2: &str = "stage2";
+const SECTION_MEDIA: &str = "media";
+const SECTION_CHECKSUMS: &str = "checksums";
+const SECTION_IMAGES_PREFIX: &str = "images-";
+const SECTION_VARIANT_PREFIX: &str = "variant-";
+const SECTION_ADDON_PREFIX: &str = "addon-";
+
+const TREEINFO_TYPE: &str = "productmd.treeinfo";
+const TREEINFO_VERSION: &str = "1.2";
+
+type Section = BTreeMap<String, String>;
+
+/// An installable distribution tree, as described by its .treeinfo file.
+///
+/// Only the productmd .treeinfo format (version 1.x) is understood, sections which aren't part
+/// of the model (such as the legacy [general] section) are preserved as-is in `other_sections`.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Treeinfo {
+    /// Version of the .treeinfo format
+    pub format_version: String,
+    pub release: TreeinfoProduct,
+    /// The product a layered product is built on top of
+    pub base_product: Option<TreeinfoProduct>,
+    pub tree: TreeinfoTree,
+    pub stage2: Option<TreeinfoStage2>,
+    pub media: Option<TreeinfoMedia>,
+    pub variants: Vec<TreeinfoVariant>,
+    /// Installer images by platform, each mapping an image type (kernel, initrd, boot.iso...)
+    /// to its path relative to the tree
+    pub images: BTreeMap<String, BTreeMap<String, String>>,
+    /// Checksums of files in the tree, by path relative to the tree
+    pub checksums: BTreeMap<String, Checksum>,
+    pub other_sections: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TreeinfoProduct {
+    pub name: String,
+    pub short: String,
+    pub version: String,
+    /// Release type, e.g. "ga" or "beta"
+    pub product_type: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TreeinfoTree {
+    pub arch: String,
+    pub build_timestamp: u64,
+    pub platforms: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TreeinfoStage2 {
+    pub mainimage: Option<String>,
+    pub instimage: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TreeinfoMedia {
+    pub discnum: u32,
+    pub totaldiscs: u32,
+}
+
+/// A variant of the tree, or an addon of a variant.
+///
+/// All paths are relative to the tree.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct TreeinfoVariant {
+    pub id: String,
+    pub uid: String,
+    pub name: String,
+    /// "variant", "optional", "addon"...
+    pub variant_type: String,
+    pub packages: Option<String>,
+    pub repository: Option<String>,
+    pub source_packages: Option<String>,
+    pub source_repository: Option<String>,
+    pub debug_packages: Option<String>,
+    pub debug_repository: Option<String>,
+    pub identity: Option<String>,
+    pub addons: Vec<TreeinfoVariant>,
+}
+
+impl Treeinfo {
+    pub fn new() -> Self {
+        Treeinfo {
+            format_version: TREEINFO_VERSION.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, MetadataError> {
+        let mut treeinfo = String::new();
+        reader.read_to_string(&mut treeinfo)?;
+        treeinfo.parse()
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), MetadataError> {
+        writer.write_all(self.to_string().as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Paths of the repositories of every variant and addon, without duplicates.
+    pub fn repository_paths(&self) -> Vec<&str> {
+        fn collect<'a>(variants: &'a [TreeinfoVariant], paths: &mut BTreeSet<&'a str>) {
+            for variant in variants {
+                if let Some(repository) = &variant.repository {
+                    paths.insert(repository);
+                }
+                collect(&variant.addons, paths);
+            }
+        }
+
+        let mut paths = BTreeSet::new();
+        collect(&self.variants, &mut paths);
+        paths.into_iter().collect()
+    }
+
+    /// Paths of the installer images of every platform and of the stage2 images, without
+    /// duplicates.
+    pub fn image_paths(&self) -> Vec<&str> {
+        let mut paths: BTreeSet<&str> = self
+            .images
+            .values()
+            .flat_map(|images| images.values().map(String::as_str))
+            .collect();
+        if let Some(stage2) = &self.stage2 {
+            paths.extend(stage2.mainimage.as_deref());
+            paths.extend(stage2.instimage.as_deref());
+        }
+        paths.into_iter().collect()
+    }
+
+    fn from_sections(mut sections: BTreeMap<String, Section>) -> Result<Self, MetadataError> {
+        let mut treeinfo = Treeinfo::default();
+
+        let mut header = take_section(&mut sections, SECTION_HEADER)?;
+        treeinfo.format_version = take_value(&mut header, "version")?;
+        if !treeinfo.format_version.starts_with("1.") {
+            return Err(MetadataError::TreeinfoParseError(format!(
+                "unsupported .treeinfo version {}",
+                treeinfo.format_version
+            )));
+        }
+
+        let mut release = take_section(&mut sections, SECTION_RELEASE)?;
+        release.remove("is_layered");
+        treeinfo.release = parse_product(release)?;
+        treeinfo.base_product = sections
+            .remove(SECTION_BASE_PRODUCT)
+            .map(parse_product)
+            .transpose()?;
+
+        let mut tree = take_section(&mut sections, SECTION_TREE)?;
+        treeinfo.tree = TreeinfoTree {
+            arch: take_value(&mut tree, "arch")?,
+            build_timestamp: parse_timestamp(&take_value(&mut tree, "build_timestamp")?)?,
+            platforms: split_list(tree.get("platforms").map(String::as_str).unwrap_or("")),
+        };
+
+        if let Some(mut stage2) = sections.remove(SECTION_STAGE2) {
+            treeinfo.stage2 = Some(TreeinfoStage2 {
+                mainimage: stage2.remove("mainimage"),
+                instimage: stage2.remove("instimage"),
+            });
+        }
+
+        if let Some(mut media) = sections.remove(SECTION_MEDIA) {
+            treeinfo.media = Some(TreeinfoMedia {
+                discnum: take_value(&mut media, "discnum")?.parse()?,
+                totaldiscs: take_value(&mut media, "totaldiscs")?.parse()?,
+            });
+        }
+
+        for uid in split_list(tree.get("variants").map(String::as_str).unwrap_or("")) {
+            let section =
+                take_section(&mut sections, &format!("{}{}", SECTION_VARIANT_PREFIX, uid))?;
+            treeinfo
+                .variants
+                .push(parse_variant(section, &mut sections)?);
+        }
+
+        if let Some(checksums) = sections.remove(SECTION_CHECKSUMS) {
+            for (path, value) in checksums {
+                let (checksum_type, checksum) = value.split_once(':').ok_or_else(|| {
+                    MetadataError::TreeinfoParseError(format!("invalid checksum for {}", path))
+                })?;
+                treeinfo
+                    .checksums
+                    .insert(path, Checksum::try_create(checksum_type, checksum)?);
+            }
+        }
+
+        let image_sections: Vec<String> = sections
+            .keys()
+            .filter(|name| name.starts_with(SECTION_IMAGES_PREFIX))
+            .cloned()
+            .collect();
+        for name in image_sections {
+            let images = sections.remove(&name).unwrap();
+            let platform = name[SECTION_IMAGES_PREFIX.len()..].to_owned();
+            treeinfo.images.insert(platform, images);
+        }
+
+        treeinfo.other_sections = sections;
+
+        Ok(treeinfo)
+    }
+
+    fn to_sections(&self) -> BTreeMap<String, Section> {
+        let mut sections = self.other_sections.clone();
+
+        let mut header = Section::new();
+        header.insert("type".to_owned(), TREEINFO_TYPE.to_owned());
+        header.insert("version".to_owned(), self.format_version.clone());
+        sections.insert(SECTION_HEADER.to_owned(), header);
+
+        let mut release = product_section(&self.release);
+        if self.base_product.is_some() {
+            release.insert("is_layered".to_owned(), "true".to_owned());
+        }
+        sections.insert(SECTION_RELEASE.to_owned(), release);
+        if let Some(base_product) = &self.base_product {
+            sections.insert(
+                SECTION_BASE_PRODUCT.to_owned(),
+                product_section(base_product),
+            );
+        }
+
+        let mut tree = Section::new();
+        tree.insert("arch".to_owned(), self.tree.arch.clone());
+        tree.insert(
+            "build_timestamp".to_owned(),
+            self.tree.build_timestamp.to_string(),
+        );
+        tree.insert("platforms".to_owned(), self.tree.platforms.join(","));
+        let variant_uids: Vec<&str> = self.variants.iter().map(|v| v.uid.as_str()).collect();
+        tree.insert("variants".to_owned(), variant_uids.join(","));
+        sections.insert(SECTION_TREE.to_owned(), tree);
+
+        if let Some(stage2) = &self.stage2 {
+            let mut section = Section::new();
+            insert_optional(&mut section, "mainimage", &stage2.mainimage);
+            insert_optional(&mut section, "instimage", &stage2.instimage);
+            sections.insert(SECTION_STAGE2.to_owned(), section);
+        }
+
+        if let Some(media) = &self.media {
+            let mut section = Section::new();
+            section.insert("discnum".to_owned(), media.discnum.to_string());
+            section.insert("totaldiscs".to_owned(), media.totaldiscs.to_string());
+            sections.insert(SECTION_MEDIA.to_owned(), section);
+        }
+
+        for variant in &self.variants {
+            add_variant_sections(&mut sections, variant, SECTION_VARIANT_PREFIX, None);
+        }
+
+        if !self.checksums.is_empty() {
+            let mut section = Section::new();
+            for (path, checksum) in &self.checksums {
+                // a checksum without a type can't be written, the file is listed as unchecked
+                if let Ok((checksum_type, value)) = checksum.to_values() {
+                    section.insert(path.clone(), format!("{}:{}", checksum_type, value));
+                }
+            }
+            sections.insert(SECTION_CHECKSUMS.to_owned(), section);
+        }
+
+        for (platform, images) in &self.images {
+            sections.insert(
+                format!("{}{}", SECTION_IMAGES_PREFIX, platform),
+                images.clone(),
+            );
+        }
+
+        sections
+    }
+}
+
+impl FromStr for Treeinfo {
+    type Err = MetadataError;
+
+    fn from_str(treeinfo: &str) -> Result<Self, Self::Err> {
+        let mut sections: BTreeMap<String, Section> = BTreeMap::new();
+        let mut current: Option<String> = None;
+
+        for (number, line) in treeinfo.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                sections.entry(name.to_owned()).or_default();
+                current = Some(name.to_owned());
+                continue;
+            }
+
+            let invalid_line =
+                || MetadataError::TreeinfoParseError(format!("invalid line {}", number + 1));
+            let section = current.as_ref().ok_or_else(invalid_line)?;
+            let (key, value) = line.split_once('=').ok_or_else(invalid_line)?;
+            sections
+                .get_mut(section)
+                .unwrap()
+                .insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+
+        Treeinfo::from_sections(sections)
+    }
+}
+
+/// Writes the .treeinfo file, with the [header] section first and the remaining sections and
+/// their keys in alphabetical order, like productmd does.
+impl fmt::Display for Treeinfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sections = self.to_sections();
+
+        let header = sections.remove(SECTION_HEADER).unwrap();
+        write_section(f, SECTION_HEADER, &header)?;
+        for (name, section) in &sections {
+            writeln!(f)?;
+            write_section(f, name, section)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_section(f: &mut fmt::Formatter<'_>, name: &str, section: &Section) -> fmt::Result {
+    writeln!(f, "[{}]", name)?;
+    for (key, value) in section {
+        writeln!(f, "{} = {}", key, value)?;
+    }
+    Ok(())
+}
+
+fn take_section(
+    sections: &mut BTreeMap<String, Section>,
+    name: &str,
+) -> Result<Section, MetadataError> {
+    sections
+        .remove(name)
+        .ok_or_else(|| MetadataError::TreeinfoParseError(format!("missing section [{}]", name)))
+}
+
+fn take_value(section: &mut Section, key: &'static str) -> Result<String, MetadataError> {
+    section
+        .remove(key)
+        .ok_or(MetadataError::MissingFieldError(key))
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Build timestamps are sometimes written as floats, e.g. "1619258060.0".
+fn parse_timestamp(value: &str) -> Result<u64, MetadataError> {
+    let seconds = value.split_once('.').map(|(s, _)| s).unwrap_or(value);
+    Ok(seconds.parse()?)
+}
+
+fn parse_product(mut section: Section) -> Result<TreeinfoProduct, MetadataError> {
+    Ok(TreeinfoProduct {
+        name: take_value(&mut section, "name")?,
+        short: take_value(&mut section, "short")?,
+        version: take_value(&mut section, "version")?,
+        product_type: section.remove("type"),
+    })
+}
+
+fn product_section(product: &TreeinfoProduct) -> Section {
+    let mut section = Section::new();
+    section.insert("name".to_owned(), product.name.clone());
+    section.insert("short".to_owned(), product.short.clone());
+    section.insert("version".to_owned(), product.version.clone());
+    insert_optional(&mut section, "type", &product.product_type);
+    section
+}
+
+fn parse_variant(
+    mut section: Section,
+    sections: &mut BTreeMap<String, Section>,
+) -> Result<TreeinfoVariant, MetadataError> {
+    let mut variant = TreeinfoVariant {
+        id: take_value(&mut section, "id")?,
+        uid: take_value(&mut section, "uid")?,
+        name: take_value(&mut section, "name")?,
+        variant_type: take_value(&mut section, "type")?,
+        packages: section.remove("packages"),
+        repository: section.remove("repository"),
+        source_packages: section.remove("source_packages"),
+        source_repository: section.remove("source_repository"),
+        debug_packages: section.remove("debug_packages"),
+        debug_repository: section.remove("debug_repository"),
+        identity: section.remove("identity"),
+        addons: Vec::new(),
+    };
+
+    for uid in split_list(section.get("addons").map(String::as_str).unwrap_or("")) {
+        let addon_section = take_section(sections, &format!("{}{}", SECTION_ADDON_PREFIX, uid))?;
+        variant.addons.push(parse_variant(addon_section, sections)?);
+    }
+
+    Ok(variant)
+}
+
+fn add_variant_sections(
+    sections: &mut BTreeMap<String, Section>,
+    variant: &TreeinfoVariant,
+    prefix: &str,
+    parent: Option<&str>,
+) {
+    let mut section = Section::new();
+    section.insert("id".to_owned(), variant.id.clone());
+    section.insert("uid".to_owned(), variant.uid.clone());
+    section.insert("name".to_owned(), variant.name.clone());
+    section.insert("type".to_owned(), variant.variant_type.clone());
+    insert_optional(&mut section, "packages", &variant.packages);
+    insert_optional(&mut section, "repository", &variant.repository);
+    insert_optional(&mut section, "source_packages", &variant.source_packages);
+    insert_optional(
+        &mut section,
+        "source_repository",
+        &variant.source_repository,
+    );
+    insert_optional(&mut section, "debug_packages", &variant.debug_packages);
+    insert_optional(&mut section, "debug_repository", &variant.debug_repository);
+    insert_optional(&mut section, "identity", &variant.identity);
+    if let Some(parent) = parent {
+        section.insert("parent".to_owned(), parent.to_owned());
+    }
+    if !variant.addons.is_empty() {
+        let addon_uids: Vec<&str> = variant.addons.iter().map(|a| a.uid.as_str()).collect();
+        section.insert("addons".to_owned(), addon_uids.join(","));
+    }
+    sections.insert(format!("{}{}", prefix, variant.uid), section);
+
+    for addon in &variant.addons {
+        add_variant_sections(sections, addon, SECTION_ADDON_PREFIX, Some(&variant.uid));
+    }
+}
+
+fn insert_optional(section: &mut Section, key: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        section.insert(key.to_owned(), value.clone());
+    }
+}
@@ -2,8 +2,11 @@ use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::{Reader, Writer};
 use std::io::{BufRead, Write};
 
-use super::metadata::{RpmMetadata, UpdateRecord, UpdateinfoXml};
-use super::{MetadataError, Package, Repository};
+use super::metadata::{
+    Checksum, RpmMetadata, UpdateCollection, UpdateCollectionModule, UpdateCollectionPackage,
+    UpdateRecord, UpdateReference, UpdateinfoXml,
+};
+use super::{utils, MetadataError, Repository};
 
 const TAG_UPDATES: &[u8] = b"updates";
 const TAG_UPDATE: &[u8] = b"update";
@@ -13,7 +16,8 @@ const TAG_RELEASE: &[u8] = b"release";
 const TAG_SEVERITY: &[u8] = b"severity";
 const TAG_ISSUED: &[u8] = b"issued";
 const TAG_UPDATED: &[u8] = b"updated";
-const TAG_COPYRIGHT: &[u8] = b"copyright";
+const TAG_RIGHTS: &[u8] = b"rights";
+const TAG_PUSHCOUNT: &[u8] = b"pushcount";
 const TAG_SUMMARY: &[u8] = b"summary";
 const TAG_DESCRIPTION: &[u8] = b"description";
 const TAG_SOLUTION: &[u8] = b"solution";
@@ -22,7 +26,11 @@ const TAG_COLLECTION: &[u8] = b"collection";
 const TAG_NAME: &[u8] = b"name";
 const TAG_PACKAGE: &[u8] = b"package";
 const TAG_FILENAME: &[u8] = b"filename";
+const TAG_MODULE: &[u8] = b"module";
+const TAG_SUM: &[u8] = b"sum";
 const TAG_REBOOT_SUGGESTED: &[u8] = b"reboot_suggested";
+const TAG_RESTART_SUGGESTED: &[u8] = b"restart_suggested";
+const TAG_RELOGIN_SUGGESTED: &[u8] = b"relogin_suggested";
 const TAG_REFERENCES: &[u8] = b"references";
 const TAG_REFERENCE: &[u8] = b"reference";
 
@@ -57,6 +65,30 @@ fn read_updateinfo_xml<R: BufRead>(
     repository: &mut Repository,
     reader: &mut Reader<R>,
 ) -> Result<(), MetadataError> {
+    let mut buf = Vec::new();
+    let mut found_metadata_tag = false;
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(e) => match e.name() {
+                TAG_UPDATES => {
+                    found_metadata_tag = true;
+                }
+                TAG_UPDATE => {
+                    let record = parse_updaterecord(reader, &e)?;
+                    repository.advisories.push(record);
+                }
+                _ => (),
+            },
+            Event::Eof => break,
+            Event::Decl(_) => (),
+            _ => (),
+        }
+        buf.clear();
+    }
+    if !found_metadata_tag {
+        return Err(MetadataError::MissingHeaderError);
+    }
     Ok(())
 }
 
@@ -65,7 +97,7 @@ pub struct UpdateinfoXmlWriter<W: Write> {
 }
 
 impl<W: Write> UpdateinfoXmlWriter<W> {
-    fn write_header(&mut self) -> Result<(), MetadataError> {
+    pub fn write_header(&mut self) -> Result<(), MetadataError> {
         // <?xml version="1.0" encoding="UTF-8"?>
         self.writer
             .write_event(Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)))?;
@@ -78,11 +110,11 @@ impl<W: Write> UpdateinfoXmlWriter<W> {
         Ok(())
     }
 
-    fn write_updaterecord(&mut self, record: &UpdateRecord) -> Result<(), MetadataError> {
+    pub fn write_updaterecord(&mut self, record: &UpdateRecord) -> Result<(), MetadataError> {
         write_updaterecord(record, &mut self.writer)
     }
 
-    fn finish(&mut self) -> Result<(), MetadataError> {
+    pub fn finish(&mut self) -> Result<(), MetadataError> {
         // </updates>
         self.writer
             .write_event(Event::End(BytesEnd::borrowed(TAG_UPDATES)))?;
@@ -99,16 +131,43 @@ impl<W: Write> UpdateinfoXmlWriter<W> {
     }
 }
 
-pub struct UpdateinfoXmlReader<'a, R: BufRead> {
-    reader: &'a mut Reader<R>,
+pub struct UpdateinfoXmlReader<R: BufRead> {
+    reader: Reader<R>,
+    buffer: Vec<u8>,
 }
 
-impl<'a, R: BufRead> UpdateinfoXmlReader<'a, R> {
-    pub fn read_header(&mut self) {}
-
-    pub fn read_package(&mut self, package: &mut Package) {}
+impl<R: BufRead> UpdateinfoXmlReader<R> {
+    /// Read up to and including the <updates> tag.
+    pub fn read_header(&mut self) -> Result<(), MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_UPDATES => break,
+                Event::Eof => return Err(MetadataError::MissingHeaderError),
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+        self.buffer.clear();
+        Ok(())
+    }
 
-    pub fn finish(&mut self) {}
+    /// Read the next advisory, or leave `record` untouched if there are no more.
+    pub fn read_update(&mut self, record: &mut Option<UpdateRecord>) -> Result<(), MetadataError> {
+        loop {
+            match self.reader.read_event(&mut self.buffer)? {
+                Event::Start(e) if e.name() == TAG_UPDATE => {
+                    *record = Some(parse_updaterecord(&mut self.reader, &e)?);
+                    break;
+                }
+                Event::End(e) if e.name() == TAG_UPDATES => break,
+                Event::Eof => break,
+                _ => (),
+            }
+            self.buffer.clear();
+        }
+        self.buffer.clear();
+        Ok(())
+    }
 }
 
 impl UpdateinfoXml {
@@ -116,8 +175,11 @@ impl UpdateinfoXml {
         UpdateinfoXmlWriter { writer }
     }
 
-    pub fn new_reader<'a, R: BufRead>(reader: &'a mut Reader<R>) -> UpdateinfoXmlReader<'a, R> {
-        UpdateinfoXmlReader { reader }
+    pub fn new_reader<R: BufRead>(reader: Reader<R>) -> UpdateinfoXmlReader<R> {
+        UpdateinfoXmlReader {
+            reader,
+            buffer: Vec::new(),
+        }
     }
 }
 
@@ -147,19 +209,21 @@ fn write_updaterecord<W: Write>(
     if let Some(issued_date) = &record.issued_date {
         writer
             .create_element(TAG_ISSUED)
-            .write_text_content(BytesText::from_plain_str(issued_date.as_str()))?;
+            .with_attribute(("date", issued_date.as_str()))
+            .write_empty()?;
     }
 
     // <updated date="2021-04-03 00:15:00"/>
     if let Some(updated_date) = &record.updated_date {
         writer
             .create_element(TAG_UPDATED)
-            .write_text_content(BytesText::from_plain_str(updated_date.as_str()))?;
+            .with_attribute(("date", updated_date.as_str()))
+            .write_empty()?;
     }
 
     // <rights>Copyright (C) 2021 Red Hat, Inc. and others.</rights>
     writer
-        .create_element(TAG_COPYRIGHT)
+        .create_element(TAG_RIGHTS)
         .write_text_content(BytesText::from_plain_str(record.rights.as_str()))?;
 
     // <release>Fedora 32</release>
@@ -167,6 +231,13 @@ fn write_updaterecord<W: Write>(
         .create_element(TAG_RELEASE)
         .write_text_content(BytesText::from_plain_str(record.release.as_str()))?;
 
+    // <pushcount>1</pushcount> (maybe)
+    if let Some(pushcount) = &record.pushcount {
+        writer
+            .create_element(TAG_PUSHCOUNT)
+            .write_text_content(BytesText::from_plain_str(pushcount.as_str()))?;
+    }
+
     // <severity>Moderate</severity>
     writer
         .create_element(TAG_SEVERITY)
@@ -182,10 +253,26 @@ fn write_updaterecord<W: Write>(
         .create_element(TAG_DESCRIPTION)
         .write_text_content(BytesText::from_plain_str(record.description.as_str()))?;
 
-    // TODO: find example
-    writer
-        .create_element(TAG_SOLUTION)
-        .write_cdata_content(BytesText::from_plain_str(record.solution.as_str()))?;
+    // <solution>For details on how to apply this update, refer to ...</solution> (maybe)
+    match record.solution.as_deref() {
+        Some("") => {
+            // <solution/>
+            writer.create_element(TAG_SOLUTION).write_empty()?;
+        }
+        Some(solution) => {
+            writer
+                .create_element(TAG_SOLUTION)
+                .write_text_content(BytesText::from_plain_str(solution))?;
+        }
+        None => (),
+    }
+
+    // <reboot_suggested>True</reboot_suggested> (maybe)
+    if record.reboot_suggested {
+        writer
+            .create_element(TAG_REBOOT_SUGGESTED)
+            .write_text_content(BytesText::from_plain_str("True"))?;
+    }
 
     let tag_references = BytesStart::borrowed_name(TAG_REFERENCES);
     if !record.references.is_empty() {
@@ -217,27 +304,7 @@ fn write_updaterecord<W: Write>(
         writer.write_event(Event::Start(tag_pkglist.to_borrowed()))?;
 
         for collection in &record.pkglist {
-            // <collection short="F32">
-            let mut tag_collection = BytesStart::borrowed_name(TAG_COLLECTION);
-            tag_collection.push_attribute(("short", collection.shortname.as_str()));
-            writer.write_event(Event::Start(tag_collection.to_borrowed()))?;
-
-            // updatecollectionmodule
-
-            for package in &collection.packages {}
-            //     <name>Fedora 32</name>
-            //     <package name="fbzx" version="4.2.0" release="1.fc32" epoch="0" arch="src" src="https://download.fedoraproject.org/pub/fedora/linux/updates/32/SRPMS/f/fbzx-4.2.0-1.fc32.src.rpm">
-            //     <filename>fbzx-4.2.0-1.fc32.src.rpm</filename>
-            //     </package>
-            //     <package name="fbzx-debugsource" version="4.2.0" release="1.fc32" epoch="0" arch="armv7hl" src="https://download.fedoraproject.org/pub/fedora/linux/updates/32/armv7hl/f/fbzx-debugsource-4.2.0-1.fc32.armv7hl.rpm">
-            //     <filename>fbzx-debugsource-4.2.0-1.fc32.armv7hl.rpm</filename>
-            //     </package>
-            //     <package name="fbzx-debuginfo" version="4.2.0" release="1.fc32" epoch="0" arch="armv7hl" src="https://download.fedoraproject.org/pub/fedora/linux/updates/32/armv7hl/f/fbzx-debuginfo-4.2.0-1.fc32.armv7hl.rpm">
-            //     <filename>fbzx-debuginfo-4.2.0-1.fc32.armv7hl.rpm</filename>
-            //     </package>
-
-            // </collection>
-            writer.write_event(Event::End(tag_collection.to_end()))?;
+            write_collection(collection, writer)?;
         }
 
         // </pkglist>
@@ -252,3 +319,357 @@ fn write_updaterecord<W: Write>(
 
     Ok(())
 }
+
+fn write_collection<W: Write>(
+    collection: &UpdateCollection,
+    writer: &mut Writer<W>,
+) -> Result<(), MetadataError> {
+    // <collection short="F32">
+    let mut tag_collection = BytesStart::borrowed_name(TAG_COLLECTION);
+    tag_collection.push_attribute(("short", collection.shortname.as_str()));
+    writer.write_event(Event::Start(tag_collection.to_borrowed()))?;
+
+    // <name>Fedora 32</name>
+    writer
+        .create_element(TAG_NAME)
+        .write_text_content(BytesText::from_plain_str(collection.name.as_str()))?;
+
+    // <module name="nodejs" stream="12" version="3220200626153814" context="e1ab9396" arch="x86_64"/> (maybe)
+    if let Some(module) = &collection.module {
+        writer
+            .create_element(TAG_MODULE)
+            .with_attribute(("name", module.name.as_str()))
+            .with_attribute(("stream", module.stream.as_str()))
+            .with_attribute(("version", module.version.to_string().as_str()))
+            .with_attribute(("context", module.context.as_str()))
+            .with_attribute(("arch", module.arch.as_str()))
+            .write_empty()?;
+    }
+
+    for package in &collection.packages {
+        write_collection_package(package, writer)?;
+    }
+
+    // </collection>
+    writer.write_event(Event::End(tag_collection.to_end()))?;
+
+    Ok(())
+}
+
+fn write_collection_package<W: Write>(
+    package: &UpdateCollectionPackage,
+    writer: &mut Writer<W>,
+) -> Result<(), MetadataError> {
+    // <package name="fbzx" version="4.2.0" release="1.fc32" epoch="0" arch="src" src="https://download.fedoraproject.org/pub/fedora/linux/updates/32/SRPMS/f/fbzx-4.2.0-1.fc32.src.rpm">
+    let mut tag_package = BytesStart::borrowed_name(TAG_PACKAGE);
+    tag_package.push_attribute(("name", package.name.as_str()));
+    tag_package.push_attribute(("version", package.version.as_str()));
+    tag_package.push_attribute(("release", package.release.as_str()));
+    tag_package.push_attribute(("epoch", package.epoch.to_string().as_str()));
+    tag_package.push_attribute(("arch", package.arch.as_str()));
+    tag_package.push_attribute(("src", package.src.as_str()));
+    writer.write_event(Event::Start(tag_package.to_borrowed()))?;
+
+    // <filename>fbzx-4.2.0-1.fc32.src.rpm</filename>
+    writer
+        .create_element(TAG_FILENAME)
+        .write_text_content(BytesText::from_plain_str(package.filename.as_str()))?;
+
+    // <sum type="sha256">...</sum> (maybe)
+    if package.checksum != Checksum::Unknown {
+        let (checksum_type, checksum_value) = package.checksum.to_values()?;
+        writer
+            .create_element(TAG_SUM)
+            .with_attribute(("type", checksum_type))
+            .write_text_content(BytesText::from_plain_str(checksum_value))?;
+    }
+
+    // <reboot_suggested>True</reboot_suggested> (maybe)
+    if package.reboot_suggested {
+        writer
+            .create_element(TAG_REBOOT_SUGGESTED)
+            .write_text_content(BytesText::from_plain_str("True"))?;
+    }
+
+    // <restart_suggested>True</restart_suggested> (maybe)
+    if package.restart_suggested {
+        writer
+            .create_element(TAG_RESTART_SUGGESTED)
+            .write_text_content(BytesText::from_plain_str("True"))?;
+    }
+
+    // <relogin_suggested>True</relogin_suggested> (maybe)
+    if package.relogin_suggested {
+        writer
+            .create_element(TAG_RELOGIN_SUGGESTED)
+            .write_text_content(BytesText::from_plain_str("True"))?;
+    }
+
+    // </package>
+    writer.write_event(Event::End(tag_package.to_end()))?;
+
+    Ok(())
+}
+
+//   <update from="updates@fedoraproject.org" status="stable" type="bugfix" version="2.0">
+//     <id>FEDORA-2020-15f9382449</id>
+//     <title>nano-4.9.3-1.fc32</title>
+//     <issued date="2020-05-27 04:10:31"/>
+//     ...
+//   </update>
+pub fn parse_updaterecord<R: BufRead>(
+    reader: &mut Reader<R>,
+    open_tag: &BytesStart,
+) -> Result<UpdateRecord, MetadataError> {
+    let mut record = UpdateRecord {
+        from: utils::parse_optional_attribute(reader, open_tag, "from")?.unwrap_or_default(),
+        status: utils::parse_optional_attribute(reader, open_tag, "status")?.unwrap_or_default(),
+        update_type: utils::parse_optional_attribute(reader, open_tag, "type")?.unwrap_or_default(),
+        version: utils::parse_optional_attribute(reader, open_tag, "version")?.unwrap_or_default(),
+        ..UpdateRecord::default()
+    };
+
+    let mut buf = Vec::new();
+    let mut text_buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_UPDATE => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_UPDATE)),
+            Event::Start(e) => match e.name() {
+                TAG_ID => {
+                    record.id = reader.read_text(TAG_ID, &mut text_buf)?;
+                }
+                TAG_TITLE => {
+                    record.title = reader.read_text(TAG_TITLE, &mut text_buf)?;
+                }
+                TAG_ISSUED => {
+                    record.issued_date = utils::parse_optional_attribute(reader, &e, "date")?;
+                    reader.read_to_end(TAG_ISSUED, &mut text_buf)?;
+                }
+                TAG_UPDATED => {
+                    record.updated_date = utils::parse_optional_attribute(reader, &e, "date")?;
+                    reader.read_to_end(TAG_UPDATED, &mut text_buf)?;
+                }
+                TAG_RIGHTS => {
+                    record.rights = reader.read_text(TAG_RIGHTS, &mut text_buf)?;
+                }
+                TAG_RELEASE => {
+                    record.release = reader.read_text(TAG_RELEASE, &mut text_buf)?;
+                }
+                TAG_PUSHCOUNT => {
+                    record.pushcount = Some(reader.read_text(TAG_PUSHCOUNT, &mut text_buf)?);
+                }
+                TAG_SEVERITY => {
+                    record.severity = reader.read_text(TAG_SEVERITY, &mut text_buf)?;
+                }
+                TAG_SUMMARY => {
+                    record.summary = reader.read_text(TAG_SUMMARY, &mut text_buf)?;
+                }
+                TAG_DESCRIPTION => {
+                    record.description = reader.read_text(TAG_DESCRIPTION, &mut text_buf)?;
+                }
+                TAG_SOLUTION => {
+                    record.solution = Some(reader.read_text(TAG_SOLUTION, &mut text_buf)?);
+                }
+                TAG_REBOOT_SUGGESTED => {
+                    record.reboot_suggested =
+                        utils::parse_bool(&reader.read_text(TAG_REBOOT_SUGGESTED, &mut text_buf)?);
+                }
+                TAG_REFERENCES => {
+                    record.references = parse_references(reader)?;
+                }
+                TAG_PKGLIST => {
+                    record.pkglist = parse_pkglist(reader)?;
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        buf.clear();
+        text_buf.clear();
+    }
+
+    Ok(record)
+}
+
+// <references>
+//   <reference href="https://bugzilla.redhat.com/show_bug.cgi?id=1839351" id="1839351" type="bugzilla" title="nano-4.9.3 is available"/>
+// </references>
+fn parse_references<R: BufRead>(
+    reader: &mut Reader<R>,
+) -> Result<Vec<UpdateReference>, MetadataError> {
+    let mut references = Vec::new();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_REFERENCES => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_REFERENCES)),
+            Event::Start(e) if e.name() == TAG_REFERENCE => {
+                references.push(UpdateReference {
+                    href: utils::parse_optional_attribute(reader, &e, "href")?.unwrap_or_default(),
+                    id: utils::parse_optional_attribute(reader, &e, "id")?.unwrap_or_default(),
+                    title: utils::parse_optional_attribute(reader, &e, "title")?
+                        .unwrap_or_default(),
+                    reftype: utils::parse_optional_attribute(reader, &e, "type")?
+                        .unwrap_or_default(),
+                });
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(references)
+}
+
+// <pkglist>
+//   <collection short="F32">
+//     <name>Fedora 32</name>
+//     <module name="nodejs" stream="12" version="3220200626153814" context="e1ab9396" arch="x86_64"/>
+//     <package name="nano" version="4.9.3" release="1.fc32" epoch="0" arch="x86_64" src="nano-4.9.3-1.fc32.src.rpm">
+//       <filename>nano-4.9.3-1.fc32.x86_64.rpm</filename>
+//       <sum type="sha256">...</sum>
+//       <reboot_suggested>True</reboot_suggested>
+//     </package>
+//   </collection>
+// </pkglist>
+fn parse_pkglist<R: BufRead>(
+    reader: &mut Reader<R>,
+) -> Result<Vec<UpdateCollection>, MetadataError> {
+    let mut collections = Vec::new();
+    let mut buf = Vec::new();
+    let mut text_buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_PKGLIST => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_PKGLIST)),
+            Event::Start(e) if e.name() == TAG_COLLECTION => {
+                let mut collection = UpdateCollection {
+                    shortname: utils::parse_optional_attribute(reader, &e, "short")?
+                        .unwrap_or_default(),
+                    ..UpdateCollection::default()
+                };
+
+                let mut collection_buf = Vec::new();
+                loop {
+                    match reader.read_event(&mut collection_buf)? {
+                        Event::End(e) if e.name() == TAG_COLLECTION => break,
+                        Event::Eof => return Err(utils::unexpected_eof(TAG_COLLECTION)),
+                        Event::Start(e) => match e.name() {
+                            TAG_NAME => {
+                                collection.name = reader.read_text(TAG_NAME, &mut text_buf)?;
+                            }
+                            TAG_MODULE => {
+                                collection.module = Some(parse_collection_module(reader, &e)?);
+                            }
+                            TAG_PACKAGE => {
+                                collection
+                                    .packages
+                                    .push(parse_collection_package(reader, &e)?);
+                            }
+                            _ => (),
+                        },
+                        _ => (),
+                    }
+                    collection_buf.clear();
+                    text_buf.clear();
+                }
+
+                collections.push(collection);
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(collections)
+}
+
+// <module name="nodejs" stream="12" version="3220200626153814" context="e1ab9396" arch="x86_64"/>
+fn parse_collection_module<R: BufRead>(
+    reader: &mut Reader<R>,
+    open_tag: &BytesStart,
+) -> Result<UpdateCollectionModule, MetadataError> {
+    let module = UpdateCollectionModule {
+        name: utils::parse_optional_attribute(reader, open_tag, "name")?.unwrap_or_default(),
+        stream: utils::parse_optional_attribute(reader, open_tag, "stream")?.unwrap_or_default(),
+        version: utils::parse_optional_attribute(reader, open_tag, "version")?
+            .map(|v| v.parse())
+            .transpose()?
+            .unwrap_or_default(),
+        context: utils::parse_optional_attribute(reader, open_tag, "context")?.unwrap_or_default(),
+        arch: utils::parse_optional_attribute(reader, open_tag, "arch")?.unwrap_or_default(),
+    };
+    reader.read_to_end(TAG_MODULE, &mut Vec::new())?;
+
+    Ok(module)
+}
+
+// <package name="nano" version="4.9.3" release="1.fc32" epoch="0" arch="x86_64" src="nano-4.9.3-1.fc32.src.rpm">
+//   <filename>nano-4.9.3-1.fc32.x86_64.rpm</filename>
+//   <sum type="sha256">...</sum>
+//   <reboot_suggested>True</reboot_suggested>
+// </package>
+fn parse_collection_package<R: BufRead>(
+    reader: &mut Reader<R>,
+    open_tag: &BytesStart,
+) -> Result<UpdateCollectionPackage, MetadataError> {
+    let mut package = UpdateCollectionPackage {
+        name: utils::parse_optional_attribute(reader, open_tag, "name")?.unwrap_or_default(),
+        version: utils::parse_optional_attribute(reader, open_tag, "version")?.unwrap_or_default(),
+        release: utils::parse_optional_attribute(reader, open_tag, "release")?.unwrap_or_default(),
+        epoch: utils::parse_optional_attribute(reader, open_tag, "epoch")?
+            .map(|e| e.parse())
+            .transpose()?
+            .unwrap_or_default(),
+        arch: utils::parse_optional_attribute(reader, open_tag, "arch")?.unwrap_or_default(),
+        src: utils::parse_optional_attribute(reader, open_tag, "src")?.unwrap_or_default(),
+        ..UpdateCollectionPackage::default()
+    };
+
+    let mut buf = Vec::new();
+    let mut text_buf = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::End(e) if e.name() == TAG_PACKAGE => break,
+            Event::Eof => return Err(utils::unexpected_eof(TAG_PACKAGE)),
+            Event::Start(e) => match e.name() {
+                TAG_FILENAME => {
+                    package.filename = reader.read_text(TAG_FILENAME, &mut text_buf)?;
+                }
+                TAG_SUM => {
+                    let checksum_type = e
+                        .try_get_attribute("type")?
+                        .ok_or_else(|| MetadataError::MissingAttributeError("type"))?
+                        .unescape_and_decode_value(reader)?;
+                    let checksum_value = reader.read_text(TAG_SUM, &mut text_buf)?;
+                    package.checksum = Checksum::try_create(checksum_type, checksum_value)?;
+                }
+                TAG_REBOOT_SUGGESTED => {
+                    package.reboot_suggested =
+                        utils::parse_bool(&reader.read_text(TAG_REBOOT_SUGGESTED, &mut text_buf)?);
+                }
+                TAG_RESTART_SUGGESTED => {
+                    package.restart_suggested =
+                        utils::parse_bool(&reader.read_text(TAG_RESTART_SUGGESTED, &mut text_buf)?);
+                }
+                TAG_RELOGIN_SUGGESTED => {
+                    package.relogin_suggested =
+                        utils::parse_bool(&reader.read_text(TAG_RELOGIN_SUGGESTED, &mut text_buf)?);
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        buf.clear();
+        text_buf.clear();
+    }
+
+    Ok(package)
+}
+
+// createrepo_c writes "True", some older tooling writes "1"
@@ -0,0 +1,292 @@
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use quick_xml::events::BytesStart;
+use quick_xml::Reader;
+use ring::digest;
+
+use super::metadata::{Checksum, ChecksumType, CompressionType, MetadataError};
+use super::zchunk::{self, ZchunkReader};
+
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+pub(crate) const RPM_LEAD_SIZE: usize = 96;
+const RPM_HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8, 0x01];
+
+/// Wrap `inner` in a decoder for the compression format it uses, if any, and return whether it is
+/// compressed. niffler doesn't support zstd, so that format is detected here.
+pub(crate) fn decompress_reader<'a>(
+    inner: Box<dyn Read + 'a>,
+) -> Result<(Box<dyn Read + 'a>, bool), MetadataError> {
+    let mut inner = BufReader::new(inner);
+    if inner.fill_buf()?.starts_with(ZSTD_MAGIC) {
+        return Ok((
+            Box::new(zstd::stream::read::Decoder::with_buffer(inner)?),
+            true,
+        ));
+    }
+
+    let (reader, format) = niffler::get_reader(Box::new(inner))?;
+    Ok((reader, format != niffler::compression::Format::No))
+}
+
+/// A file which compresses whatever is written to it.
+///
+/// Unlike a plain `Box<dyn Write>`, the compressed stream has to be closed with [`finish`], so
+/// that errors while writing its trailer are reported rather than lost when it is dropped.
+///
+/// [`finish`]: CompressedWriter::finish
+pub(crate) enum CompressedWriter {
+    None(BufWriter<File>),
+    Gzip(flate2::write::GzEncoder<File>),
+    Bz2(bzip2::write::BzEncoder<File>),
+    Xz(xz2::write::XzEncoder<File>),
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+}
+
+impl CompressedWriter {
+    /// Create the file at `path`, compressing whatever is written to it with `compression`.
+    pub(crate) fn create(path: &Path, compression: CompressionType) -> Result<Self, MetadataError> {
+        let file = File::create(path)?;
+        let writer = match compression {
+            CompressionType::None => CompressedWriter::None(BufWriter::new(file)),
+            CompressionType::Gzip => CompressedWriter::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::best(),
+            )),
+            CompressionType::Bz2 => CompressedWriter::Bz2(bzip2::write::BzEncoder::new(
+                file,
+                bzip2::Compression::best(),
+            )),
+            CompressionType::Xz => CompressedWriter::Xz(xz2::write::XzEncoder::new(file, 9)),
+            CompressionType::Zstd => CompressedWriter::Zstd(zstd::stream::write::Encoder::new(
+                file,
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?),
+        };
+        Ok(writer)
+    }
+
+    /// Write the end of the compressed stream and flush the file. Nothing may be written
+    /// afterwards.
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::None(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => {
+                encoder.try_finish()?;
+                encoder.get_mut().flush()
+            }
+            CompressedWriter::Bz2(encoder) => {
+                encoder.try_finish()?;
+                encoder.get_mut().flush()
+            }
+            CompressedWriter::Xz(encoder) => {
+                encoder.try_finish()?;
+                encoder.get_mut().flush()
+            }
+            CompressedWriter::Zstd(encoder) => {
+                encoder.do_finish()?;
+                encoder.get_mut().flush()
+            }
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn Write {
+        match self {
+            CompressedWriter::None(writer) => writer,
+            CompressedWriter::Gzip(encoder) => encoder,
+            CompressedWriter::Bz2(encoder) => encoder,
+            CompressedWriter::Xz(encoder) => encoder,
+            CompressedWriter::Zstd(encoder) => encoder,
+        }
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner_mut().flush()
+    }
+}
+
+/// Error for a document which ends before the closing tag of the element `tag`.
+pub(crate) fn unexpected_eof(tag: &[u8]) -> MetadataError {
+    MetadataError::InconsistentMetadataError(format!(
+        "unexpected end of file inside <{}>",
+        String::from_utf8_lossy(tag)
+    ))
+}
+
+/// Value of the attribute `name` of `tag`, unescaped, if the tag has it.
+pub(crate) fn parse_optional_attribute<R: BufRead>(
+    reader: &Reader<R>,
+    tag: &BytesStart,
+    name: &str,
+) -> Result<Option<String>, MetadataError> {
+    Ok(tag
+        .try_get_attribute(name)?
+        .map(|a| a.unescape_and_decode_value(reader))
+        .transpose()?)
+}
+
+// createrepo_c writes "True", some older tooling writes "1"
+pub(crate) fn parse_bool(value: &str) -> bool {
+    value == "1" || value.eq_ignore_ascii_case("true")
+}
+
+/// Hash everything which can be read from `reader`, returning the checksum and the number of bytes read.
+pub(crate) fn checksum_reader<R: Read>(
+    reader: &mut R,
+    checksum_type: ChecksumType,
+) -> Result<(Checksum, u64), MetadataError> {
+    let algorithm = match checksum_type {
+        ChecksumType::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        ChecksumType::Sha256 => &digest::SHA256,
+        ChecksumType::Sha384 => &digest::SHA384,
+        ChecksumType::Sha512 => &digest::SHA512,
+        ChecksumType::Unknown => {
+            return Err(MetadataError::UnsupportedChecksumTypeError(
+                "unknown".to_owned(),
+            ))
+        }
+    };
+
+    let mut context = digest::Context::new(algorithm);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        context.update(&buffer[..count]);
+        size += count as u64;
+    }
+
+    let value = encode_hex(context.finish().as_ref());
+    let checksum = match checksum_type {
+        ChecksumType::Sha1 => Checksum::Sha1(value),
+        ChecksumType::Sha256 => Checksum::Sha256(value),
+        ChecksumType::Sha384 => Checksum::Sha384(value),
+        ChecksumType::Sha512 => Checksum::Sha512(value),
+        ChecksumType::Unknown => unreachable!(),
+    };
+
+    Ok((checksum, size))
+}
+
+/// Checksum and size of a byte slice.
+pub(crate) fn checksum_bytes(
+    bytes: &[u8],
+    checksum_type: ChecksumType,
+) -> Result<(Checksum, u64), MetadataError> {
+    checksum_reader(&mut &bytes[..], checksum_type)
+}
+
+/// Checksum and size of the file as it exists on disk.
+pub(crate) fn checksum_file(
+    path: &Path,
+    checksum_type: ChecksumType,
+) -> Result<(Checksum, u64), MetadataError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    checksum_reader(&mut reader, checksum_type)
+}
+
+/// Checksum and size of the decompressed contents of the file, or `None` if it isn't compressed.
+pub(crate) fn checksum_file_contents(
+    path: &Path,
+    checksum_type: ChecksumType,
+) -> Result<Option<(Checksum, u64)>, MetadataError> {
+    // niffler doesn't know about zchunk
+    if zchunk::is_zchunk_file(path)? {
+        let mut reader = ZchunkReader::new(BufReader::new(File::open(path)?))?;
+        return Ok(Some(checksum_reader(&mut reader, checksum_type)?));
+    }
+
+    let (mut reader, compressed) = decompress_reader(Box::new(File::open(path)?))?;
+    if !compressed {
+        return Ok(None);
+    }
+    Ok(Some(checksum_reader(&mut reader, checksum_type)?))
+}
+
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+/// Index entry count and store size of the RPM header structure whose 16 byte intro (magic,
+/// version, reserved bytes, entry count, store size) starts at `start`.
+pub(crate) fn rpm_header_sizes(bytes: &[u8], start: usize) -> Option<(usize, usize)> {
+    if bytes.get(start..start.checked_add(4)?)? != RPM_HEADER_MAGIC {
+        return None;
+    }
+    let entry_count = read_u32(bytes, start + 8)? as usize;
+    let store_size = read_u32(bytes, start + 12)? as usize;
+    Some((entry_count, store_size))
+}
+
+/// Start and end offsets of the main header of the RPM package read from `reader`.
+///
+/// The lead is followed by the signature header, padded to a multiple of 8 bytes, and then by
+/// the main header. Only the headers are read, not the payload.
+pub(crate) fn rpm_header_range<R: Read>(reader: &mut R) -> Result<(u64, u64), MetadataError> {
+    skip_exact(reader, RPM_LEAD_SIZE as u64)?;
+
+    let signature_size = skip_rpm_header(reader)?;
+    let padding = (8 - signature_size % 8) % 8;
+    skip_exact(reader, padding)?;
+
+    let header_start = RPM_LEAD_SIZE as u64 + signature_size + padding;
+    let header_size = skip_rpm_header(reader)?;
+    Ok((header_start, header_start + header_size))
+}
+
+/// Skip over an RPM header structure, returning its size.
+fn skip_rpm_header<R: Read>(reader: &mut R) -> Result<u64, MetadataError> {
+    let mut intro = [0; 16];
+    reader.read_exact(&mut intro)?;
+    let (entry_count, store_size) = rpm_header_sizes(&intro, 0)
+        .ok_or_else(|| MetadataError::RpmReadError("invalid RPM header".to_owned()))?;
+
+    let size = entry_count as u64 * 16 + store_size as u64;
+    skip_exact(reader, size)?;
+    Ok(intro.len() as u64 + size)
+}
+
+fn skip_exact<R: Read>(reader: &mut R, length: u64) -> io::Result<()> {
+    if io::copy(&mut reader.take(length), &mut io::sink())? != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Last component of the path of a metadata file, which must name a file.
+pub(crate) fn file_name(path: &Path) -> Result<String, MetadataError> {
+    let filename = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} has no filename", path.display()),
+        )
+    })?;
+    Ok(filename.to_string_lossy().into_owned())
+}
+
+/// Modification time of the file in seconds since the epoch.
+pub(crate) fn file_mtime(path: &Path) -> Result<u64, MetadataError> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    Ok(modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs()))
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
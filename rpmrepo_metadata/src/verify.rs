@@ -0,0 +1,259 @@
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fmt;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use super::metadata::{
+    Checksum, ChecksumType, PrestodeltaXml, PrimaryXml, RepoMdRecord, RepomdXml, RpmMetadata,
+    METADATA_PRESTODELTA, METADATA_PRIMARY,
+};
+use super::{utils, MetadataError, Repository};
+
+/// A file whose size or checksum doesn't match the metadata describing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorruptFile {
+    pub location_href: String,
+    pub reason: String,
+}
+
+/// Problems found by [`verify_repository`]. All of the paths are relative to the repository.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepositoryVerificationReport {
+    /// Files referenced by the metadata which don't exist
+    pub missing: Vec<String>,
+    /// Files which exist, but don't match the metadata
+    pub corrupt: Vec<CorruptFile>,
+    /// Files in the repository which aren't referenced by the metadata
+    pub unreferenced: Vec<String>,
+}
+
+impl RepositoryVerificationReport {
+    /// Whether no problem at all was found
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupt.is_empty() && self.unreferenced.is_empty()
+    }
+}
+
+impl fmt::Display for RepositoryVerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} missing, {} corrupt, {} unreferenced files",
+            self.missing.len(),
+            self.corrupt.len(),
+            self.unreferenced.len()
+        )?;
+        for location_href in &self.missing {
+            write!(f, "\n  missing: {}", location_href)?;
+        }
+        for file in &self.corrupt {
+            write!(f, "\n  corrupt: {} ({})", file.location_href, file.reason)?;
+        }
+        for location_href in &self.unreferenced {
+            write!(f, "\n  unreferenced: {}", location_href)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the repository at `path` in place, against its own metadata.
+///
+/// The size, checksum and open-size / open-checksum of every file listed in repomd.xml are
+/// checked, and so are the size and checksum of every package (and deltarpm, if there is a
+/// prestodelta.xml) against the file at its location_href. Files which exist in the repository
+/// but aren't referenced by any of the metadata are reported too.
+///
+/// The returned error is reserved for problems which prevent the verification itself, such as a
+/// missing or unparseable repomd.xml.
+pub fn verify_repository(path: &Path) -> Result<RepositoryVerificationReport, MetadataError> {
+    let mut report = RepositoryVerificationReport::default();
+
+    let mut repo = Repository::new();
+    repo.load_metadata_file::<RepomdXml>(&path.join("repodata").join(RepomdXml::filename()))?;
+
+    let mut intact_records = BTreeSet::new();
+    for record in repo.records() {
+        match check_record(path, record)? {
+            FileState::Missing => report.missing.push(record.location_href.clone()),
+            FileState::Corrupt(reason) => report.corrupt.push(CorruptFile {
+                location_href: record.location_href.clone(),
+                reason,
+            }),
+            FileState::Intact => {
+                intact_records.insert(record.mdtype.clone());
+            }
+        }
+    }
+
+    // packages can only be checked if primary.xml can be trusted, and likewise for deltarpms
+    if intact_records.contains(METADATA_PRIMARY) {
+        let primary_path = path.join(&repo.get_primary_data().location_href);
+        repo.load_metadata_file::<PrimaryXml>(&primary_path)?;
+    }
+    if intact_records.contains(METADATA_PRESTODELTA) {
+        let prestodelta_path =
+            path.join(&repo.get_record(METADATA_PRESTODELTA).unwrap().location_href);
+        repo.load_metadata_file::<PrestodeltaXml>(&prestodelta_path)?;
+    }
+
+    let mut files: Vec<(&str, u64, &Checksum)> = repo
+        .packages()
+        .values()
+        .map(|package| {
+            let location_href = package.location_href();
+            (location_href, package.size().package, package.checksum())
+        })
+        .collect();
+    files.extend(
+        repo.deltas
+            .iter()
+            .flat_map(|package| package.deltas.iter())
+            .map(|delta| (delta.filename.as_str(), delta.size, &delta.checksum)),
+    );
+
+    let file_states = files
+        .par_iter()
+        .map(|(location_href, size, checksum)| {
+            check_file(&path.join(location_href), Some(*size), checksum)
+        })
+        .collect::<Result<Vec<_>, MetadataError>>()?;
+
+    for ((location_href, _, _), state) in files.iter().zip(file_states) {
+        match state {
+            FileState::Missing => report.missing.push(location_href.to_string()),
+            FileState::Corrupt(reason) => report.corrupt.push(CorruptFile {
+                location_href: location_href.to_string(),
+                reason,
+            }),
+            FileState::Intact => (),
+        }
+    }
+
+    let referenced = referenced_files(&repo);
+    report.unreferenced = list_repository_files(path)?
+        .into_iter()
+        .filter(|location_href| !referenced.contains(location_href.as_str()))
+        .collect();
+
+    report.missing.sort();
+    report
+        .corrupt
+        .sort_by(|a, b| a.location_href.cmp(&b.location_href));
+
+    Ok(report)
+}
+
+enum FileState {
+    Missing,
+    Corrupt(String),
+    Intact,
+}
+
+fn check_record(path: &Path, record: &RepoMdRecord) -> Result<FileState, MetadataError> {
+    let file_path = path.join(&record.location_href);
+    let state = check_file(&file_path, record.size, &record.checksum)?;
+    if !matches!(state, FileState::Intact) {
+        return Ok(state);
+    }
+
+    if let Some(open_checksum) = &record.open_checksum {
+        let open_values = utils::checksum_file_contents(&file_path, checksum_type(open_checksum)?)?;
+        let (actual_checksum, actual_size) = match open_values {
+            Some(open_values) => open_values,
+            None => {
+                return Ok(FileState::Corrupt(
+                    "file isn't compressed, but has an open-checksum".to_owned(),
+                ))
+            }
+        };
+        if let Some(expected_size) = record.open_size {
+            if actual_size != expected_size {
+                return Ok(FileState::Corrupt(format!(
+                    "open-size is {}, expected {}",
+                    actual_size, expected_size
+                )));
+            }
+        }
+        if &actual_checksum != open_checksum {
+            return Ok(FileState::Corrupt("open-checksum mismatch".to_owned()));
+        }
+    }
+
+    Ok(FileState::Intact)
+}
+
+fn check_file(
+    file_path: &Path,
+    expected_size: Option<u64>,
+    expected_checksum: &Checksum,
+) -> Result<FileState, MetadataError> {
+    let metadata = match std::fs::metadata(file_path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(FileState::Missing),
+        Err(e) => return Err(e.into()),
+    };
+
+    // comparing the size first avoids hashing files which are obviously broken
+    if let Some(expected_size) = expected_size {
+        if metadata.len() != expected_size {
+            return Ok(FileState::Corrupt(format!(
+                "size is {}, expected {}",
+                metadata.len(),
+                expected_size
+            )));
+        }
+    }
+
+    let (actual_checksum, _) = utils::checksum_file(file_path, checksum_type(expected_checksum)?)?;
+    if &actual_checksum != expected_checksum {
+        return Ok(FileState::Corrupt("checksum mismatch".to_owned()));
+    }
+
+    Ok(FileState::Intact)
+}
+
+fn checksum_type(checksum: &Checksum) -> Result<ChecksumType, MetadataError> {
+    let (checksum_type, _) = checksum.to_values()?;
+    checksum_type.try_into()
+}
+
+/// Every file which is referenced by the (loaded) metadata of `repo`, relative to the repository.
+pub(crate) fn referenced_files(repo: &Repository) -> BTreeSet<&str> {
+    let mut referenced: BTreeSet<&str> = ["repodata/repomd.xml", "repodata/repomd.xml.asc"]
+        .iter()
+        .copied()
+        .collect();
+    referenced.extend(repo.records().iter().map(|r| r.location_href.as_str()));
+    referenced.extend(repo.packages().values().map(|p| p.location_href()));
+    referenced.extend(
+        repo.deltas
+            .iter()
+            .flat_map(|package| package.deltas.iter())
+            .map(|delta| delta.filename.as_str()),
+    );
+    referenced
+}
+
+/// All of the files under `path`, relative to it and with "/" as the separator, in sorted order.
+pub(crate) fn list_repository_files(path: &Path) -> Result<Vec<String>, MetadataError> {
+    fn walk(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<(), MetadataError> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let relative_path = format!("{}{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                walk(&entry.path(), &format!("{}/", relative_path), files)?;
+            } else {
+                files.push(relative_path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(path, "", &mut files)?;
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,434 @@
+//! Native support for the subset of the zchunk format used by repository metadata.
+//!
+//! See https://github.com/zchunk/zchunk/blob/main/zchunk_format.txt
+//!
+//! Files are written with SHA-256 header and data checksums, SHA-512/128 chunk checksums,
+//! zstd-compressed chunks and no dictionary. The reader additionally understands dictionaries,
+//! uncompressed chunks and the other checksum types, but ignores streams and signatures.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use ring::digest;
+
+const ZCK_MAGIC: &[u8] = b"\0ZCK1";
+
+const ZCK_CHECKSUM_SHA1: u64 = 0;
+const ZCK_CHECKSUM_SHA256: u64 = 1;
+const ZCK_CHECKSUM_SHA512: u64 = 2;
+const ZCK_CHECKSUM_SHA512_128: u64 = 3;
+
+const ZCK_COMPRESSION_NONE: u64 = 0;
+const ZCK_COMPRESSION_ZSTD: u64 = 2;
+
+const ZCK_FLAG_STREAMS: u64 = 1 << 0;
+const ZCK_FLAG_OPTIONAL_ELEMENTS: u64 = 1 << 1;
+const ZCK_FLAG_UNCOMPRESSED_CHECKSUMS: u64 = 1 << 2;
+
+const ZSTD_COMPRESSION_LEVEL: i32 = 9;
+
+struct ChunkEntry {
+    checksum: Vec<u8>,
+    length: u64,
+    uncompressed_length: u64,
+}
+
+/// Writes a zchunk file, one chunk at a time.
+///
+/// Data written to the writer is accumulated into the current chunk until [`ZchunkWriter::end_chunk`]
+/// is called. The header can only be written once every chunk is known, so the compressed chunks
+/// are held in memory until [`ZchunkWriter::finish`].
+pub struct ZchunkWriter<W: Write> {
+    inner: W,
+    current_chunk: Vec<u8>,
+    chunks: Vec<ChunkEntry>,
+    data: Vec<u8>,
+}
+
+impl<W: Write> ZchunkWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ZchunkWriter {
+            inner,
+            current_chunk: Vec::new(),
+            chunks: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Close the current chunk, the next write starts a new one. Empty chunks are skipped.
+    pub fn end_chunk(&mut self) -> io::Result<()> {
+        if self.current_chunk.is_empty() {
+            return Ok(());
+        }
+
+        let compressed =
+            zstd::stream::encode_all(self.current_chunk.as_slice(), ZSTD_COMPRESSION_LEVEL)?;
+        self.chunks.push(ChunkEntry {
+            checksum: checksum(ZCK_CHECKSUM_SHA512_128, &compressed)?,
+            length: compressed.len() as u64,
+            uncompressed_length: self.current_chunk.len() as u64,
+        });
+        self.data.extend_from_slice(&compressed);
+        self.current_chunk.clear();
+
+        Ok(())
+    }
+
+    /// Write the header followed by the chunks, and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end_chunk()?;
+
+        let mut index = Vec::new();
+        write_compint(&mut index, ZCK_CHECKSUM_SHA512_128);
+        // the chunk count includes the dictionary, which is always present even if it's empty
+        write_compint(&mut index, self.chunks.len() as u64 + 1);
+        index.extend_from_slice(&[0; 16]);
+        write_compint(&mut index, 0);
+        write_compint(&mut index, 0);
+        for chunk in &self.chunks {
+            index.extend_from_slice(&chunk.checksum);
+            write_compint(&mut index, chunk.length);
+            write_compint(&mut index, chunk.uncompressed_length);
+        }
+
+        let mut header = Vec::new();
+        // preface: data checksum, flags, compression type
+        header.extend_from_slice(&checksum(ZCK_CHECKSUM_SHA256, &self.data)?);
+        write_compint(&mut header, 0);
+        write_compint(&mut header, ZCK_COMPRESSION_ZSTD);
+        // index
+        write_compint(&mut header, index.len() as u64);
+        header.extend_from_slice(&index);
+        // signatures
+        write_compint(&mut header, 0);
+
+        let mut lead = Vec::new();
+        lead.extend_from_slice(ZCK_MAGIC);
+        write_compint(&mut lead, ZCK_CHECKSUM_SHA256);
+        write_compint(&mut lead, header.len() as u64);
+
+        // The header checksum covers everything up to the end of the header, except itself
+        let header_checksum = checksum(
+            ZCK_CHECKSUM_SHA256,
+            &[lead.as_slice(), header.as_slice()].concat(),
+        )?;
+
+        self.inner.write_all(&lead)?;
+        self.inner.write_all(&header_checksum)?;
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&self.data)?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ZchunkWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.current_chunk.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads the decompressed contents of a zchunk file.
+///
+/// The header checksum is verified when the reader is created, each chunk checksum as the chunk
+/// is read, and the data checksum once the end of the file is reached.
+pub struct ZchunkReader<R: Read> {
+    inner: R,
+    header: Vec<u8>,
+
+    compression: u64,
+    dictionary: Vec<u8>,
+    chunk_checksum_type: u64,
+    chunks: VecDeque<ChunkEntry>,
+
+    data_checksum_type: u64,
+    data_checksum: Vec<u8>,
+    data_context: Option<digest::Context>,
+
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> ZchunkReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut lead = vec![0; ZCK_MAGIC.len()];
+        inner.read_exact(&mut lead)?;
+        if lead != ZCK_MAGIC {
+            return Err(invalid_data("not a zchunk file".to_owned()));
+        }
+
+        let data_checksum_type = read_compint(&mut inner, &mut lead)?;
+        let header_size = read_compint(&mut inner, &mut lead)?;
+
+        let mut header_checksum = vec![0; checksum_length(data_checksum_type)?];
+        inner.read_exact(&mut header_checksum)?;
+        let header = read_bounded(&mut inner, header_size)?;
+
+        if checksum(
+            data_checksum_type,
+            &[lead.as_slice(), header.as_slice()].concat(),
+        )? != header_checksum
+        {
+            return Err(invalid_data("zchunk header checksum mismatch".to_owned()));
+        }
+
+        let mut cursor = header.as_slice();
+        let mut ignored = Vec::new();
+
+        // preface
+        let mut data_checksum = vec![0; checksum_length(data_checksum_type)?];
+        cursor.read_exact(&mut data_checksum)?;
+        let flags = read_compint(&mut cursor, &mut ignored)?;
+        let compression = read_compint(&mut cursor, &mut ignored)?;
+        if compression != ZCK_COMPRESSION_NONE && compression != ZCK_COMPRESSION_ZSTD {
+            return Err(invalid_data(format!(
+                "unsupported zchunk compression type {}",
+                compression
+            )));
+        }
+        if flags & ZCK_FLAG_OPTIONAL_ELEMENTS != 0 {
+            let num_elements = read_compint(&mut cursor, &mut ignored)?;
+            for _ in 0..num_elements {
+                let _element_id = read_compint(&mut cursor, &mut ignored)?;
+                let element_size = read_compint(&mut cursor, &mut ignored)?;
+                skip(&mut cursor, element_size as usize)?;
+            }
+        }
+
+        // index
+        let _index_size = read_compint(&mut cursor, &mut ignored)?;
+        let chunk_checksum_type = read_compint(&mut cursor, &mut ignored)?;
+        let num_chunks = read_compint(&mut cursor, &mut ignored)?;
+        let mut chunks = VecDeque::new();
+        for _ in 0..num_chunks {
+            if flags & ZCK_FLAG_STREAMS != 0 {
+                let _stream = read_compint(&mut cursor, &mut ignored)?;
+            }
+            let mut checksum = vec![0; checksum_length(chunk_checksum_type)?];
+            cursor.read_exact(&mut checksum)?;
+            if flags & ZCK_FLAG_UNCOMPRESSED_CHECKSUMS != 0 {
+                skip(&mut cursor, checksum_length(chunk_checksum_type)?)?;
+            }
+            chunks.push_back(ChunkEntry {
+                checksum,
+                length: read_compint(&mut cursor, &mut ignored)?,
+                uncompressed_length: read_compint(&mut cursor, &mut ignored)?,
+            });
+        }
+
+        let mut reader = ZchunkReader {
+            inner,
+            header: [
+                lead.as_slice(),
+                header_checksum.as_slice(),
+                header.as_slice(),
+            ]
+            .concat(),
+
+            compression,
+            dictionary: Vec::new(),
+            chunk_checksum_type,
+            chunks,
+
+            data_checksum_type,
+            data_checksum,
+            data_context: Some(digest::Context::new(algorithm(data_checksum_type)?)),
+
+            buffer: Vec::new(),
+            position: 0,
+        };
+
+        // the first chunk is always the dictionary
+        if let Some(dict_chunk) = reader.chunks.pop_front() {
+            if dict_chunk.length > 0 {
+                reader.dictionary = reader.read_chunk(&dict_chunk, false)?;
+            }
+        }
+
+        Ok(reader)
+    }
+
+    /// The lead and header of the file, exactly as they were read.
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    fn read_chunk(&mut self, chunk: &ChunkEntry, use_dictionary: bool) -> io::Result<Vec<u8>> {
+        let data = read_bounded(&mut self.inner, chunk.length)?;
+
+        if let Some(context) = &mut self.data_context {
+            context.update(&data);
+        }
+        if checksum(self.chunk_checksum_type, &data)? != chunk.checksum {
+            return Err(invalid_data("zchunk chunk checksum mismatch".to_owned()));
+        }
+
+        if self.compression == ZCK_COMPRESSION_NONE {
+            return Ok(data);
+        }
+        if chunk.uncompressed_length == 0 {
+            return Ok(Vec::new());
+        }
+
+        let dictionary: &[u8] = if use_dictionary {
+            &self.dictionary
+        } else {
+            &[]
+        };
+        let decoder = zstd::stream::read::Decoder::with_dictionary(data.as_slice(), dictionary)?;
+
+        // don't trust the uncompressed length from the index, decompress at most one byte more
+        let mut uncompressed = Vec::new();
+        decoder
+            .take(chunk.uncompressed_length.saturating_add(1))
+            .read_to_end(&mut uncompressed)?;
+        if uncompressed.len() as u64 != chunk.uncompressed_length {
+            return Err(invalid_data(
+                "zchunk chunk doesn't match its uncompressed length".to_owned(),
+            ));
+        }
+
+        Ok(uncompressed)
+    }
+
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        match self.chunks.pop_front() {
+            Some(chunk) => {
+                self.buffer = self.read_chunk(&chunk, true)?;
+                self.position = 0;
+                Ok(true)
+            }
+            None => {
+                if let Some(context) = self.data_context.take() {
+                    let length = checksum_length(self.data_checksum_type)?;
+                    if context.finish().as_ref()[..length] != self.data_checksum[..] {
+                        return Err(invalid_data("zchunk data checksum mismatch".to_owned()));
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for ZchunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            if !self.next_chunk()? {
+                return Ok(0);
+            }
+        }
+
+        let count = std::cmp::min(buf.len(), self.buffer.len() - self.position);
+        buf[..count].copy_from_slice(&self.buffer[self.position..self.position + count]);
+        self.position += count;
+
+        Ok(count)
+    }
+}
+
+/// Check whether the file starts with the zchunk magic bytes.
+pub(crate) fn is_zchunk_file(path: &Path) -> io::Result<bool> {
+    let mut magic = vec![0; ZCK_MAGIC.len()];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == ZCK_MAGIC),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read the lead and header of a zchunk file, or return `None` if it isn't a zchunk file.
+pub(crate) fn read_header(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    if !is_zchunk_file(path)? {
+        return Ok(None);
+    }
+    let reader = ZchunkReader::new(io::BufReader::new(File::open(path)?))?;
+    Ok(Some(reader.header().to_vec()))
+}
+
+fn algorithm(checksum_type: u64) -> io::Result<&'static digest::Algorithm> {
+    match checksum_type {
+        ZCK_CHECKSUM_SHA1 => Ok(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+        ZCK_CHECKSUM_SHA256 => Ok(&digest::SHA256),
+        ZCK_CHECKSUM_SHA512 | ZCK_CHECKSUM_SHA512_128 => Ok(&digest::SHA512),
+        _ => Err(invalid_data(format!(
+            "unsupported zchunk checksum type {}",
+            checksum_type
+        ))),
+    }
+}
+
+fn checksum_length(checksum_type: u64) -> io::Result<usize> {
+    match checksum_type {
+        ZCK_CHECKSUM_SHA512_128 => Ok(16),
+        _ => Ok(algorithm(checksum_type)?.output_len),
+    }
+}
+
+fn checksum(checksum_type: u64, data: &[u8]) -> io::Result<Vec<u8>> {
+    let digest = digest::digest(algorithm(checksum_type)?, data);
+    Ok(digest.as_ref()[..checksum_length(checksum_type)?].to_vec())
+}
+
+// Integers are stored in 7 bit groups, least significant first, with the high bit set on the last byte
+fn write_compint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value % 128) as u8;
+        value /= 128;
+        if value == 0 {
+            buf.push(byte | 0x80);
+            break;
+        }
+        buf.push(byte);
+    }
+}
+
+/// Read an integer, appending the raw bytes to `raw`.
+fn read_compint<R: Read>(reader: &mut R, raw: &mut Vec<u8>) -> io::Result<u64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        raw.push(byte[0]);
+
+        if shift > 63 {
+            return Err(invalid_data("zchunk integer is too large".to_owned()));
+        }
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Read exactly `length` bytes, without allocating them up front since the length comes from the file.
+fn read_bounded<R: Read>(reader: &mut R, length: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut data)?;
+    if data.len() as u64 != length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "zchunk file is truncated",
+        ));
+    }
+    Ok(data)
+}
+
+fn skip<R: Read>(reader: &mut R, length: usize) -> io::Result<()> {
+    io::copy(&mut reader.by_ref().take(length as u64), &mut io::sink())?;
+    Ok(())
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -27,3 +27,20 @@ createrepo_c \
   --no-database \
   --pkglist=fixture_data/complex_repo_pkglist.txt \
   fixture_data/packages/
+
+# Keys for the repomd.xml signing tests (no passphrase)
+export GNUPGHOME=$(mktemp -d)
+mkdir -p signing/
+gpg --batch --pinentry-mode loopback --passphrase '' \
+  --quick-gen-key "rpmrepo test key <test@example.com>" rsa2048 sign never
+gpg --batch --pinentry-mode loopback --passphrase '' \
+  --quick-gen-key "rpmrepo other test key <other@example.com>" rsa2048 sign never
+gpg --batch --pinentry-mode loopback --passphrase '' \
+  --armor --export-secret-keys test@example.com > signing/test-key.asc
+gpg --armor --export test@example.com > signing/test-key.pub.asc
+gpg --armor --export other@example.com > signing/other-key.pub.asc
+
+# Unsigned package and a copy of it signed with the test key, for the package signature tests
+cp fixture_data/packages/rpm-empty-0-0.x86_64.rpm packages/
+cp fixture_data/packages/rpm-empty-0-0.x86_64.rpm signing/
+rpmsign --addsign --define "_gpg_name test@example.com" signing/rpm-empty-0-0.x86_64.rpm
@@ -7,6 +7,9 @@ use rpmrepo_metadata::{Checksum, FileType, Package, Requirement, EVR};
 
 pub const COMPLEX_REPO_FIXTURE_PATH: &str = "./tests/assets/complex_repo/";
 pub const EMPTY_REPO_FIXTURE_PATH: &str = "./tests/assets/empty_repo/";
+/// A real package from Fedora 31, which is committed rather than built by build_repos.sh
+pub const RPM_SIGN_PACKAGE_FIXTURE_PATH: &str =
+    "./tests/assets/packages/rpm-sign-4.15.1-1.fc31.x86_64.rpm";
 
 pub static COMPLEX_PACKAGE: Lazy<Package> = Lazy::new(|| {
     let mut package = Package::default();
@@ -0,0 +1,201 @@
+extern crate rpmrepo_metadata;
+
+use pretty_assertions::assert_eq;
+
+use rpmrepo_metadata::*;
+use tempdir::TempDir;
+
+static COMPS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE comps PUBLIC "-//Red Hat, Inc.//DTD Comps info//EN" "comps.dtd">
+<comps>
+  <group>
+    <id>core</id>
+    <name>Core</name>
+    <name xml:lang="de">Kern</name>
+    <name xml:lang="fr">Noyau</name>
+    <description>Smallest possible installation</description>
+    <description xml:lang="de">Kleinstmögliche Installation</description>
+    <default>false</default>
+    <uservisible>false</uservisible>
+    <packagelist>
+      <packagereq type="mandatory">bash</packagereq>
+      <packagereq type="default">dnf-plugins-core</packagereq>
+      <packagereq type="optional" basearchonly="true">dracut-config-rescue</packagereq>
+    </packagelist>
+  </group>
+  <group>
+    <id>german-support</id>
+    <name>German Support</name>
+    <description></description>
+    <default>false</default>
+    <uservisible>true</uservisible>
+    <biarchonly>true</biarchonly>
+    <display_order>100</display_order>
+    <langonly>de</langonly>
+    <packagelist>
+      <packagereq type="conditional" requires="gimp">gimp-help-de</packagereq>
+    </packagelist>
+  </group>
+  <category>
+    <id>base-system</id>
+    <name>Base System</name>
+    <name xml:lang="de">Basissystem</name>
+    <description>Various core pieces of the system.</description>
+    <display_order>200</display_order>
+    <grouplist>
+      <groupid>core</groupid>
+      <groupid>german-support</groupid>
+    </grouplist>
+  </category>
+  <environment>
+    <id>minimal-environment</id>
+    <name>Minimal Install</name>
+    <description>Basic functionality.</description>
+    <display_order>5</display_order>
+    <grouplist>
+      <groupid>core</groupid>
+    </grouplist>
+    <optionlist>
+      <groupid>german-support</groupid>
+      <groupid default="true">guest-agents</groupid>
+    </optionlist>
+  </environment>
+  <langpacks>
+    <match install="aspell-%s" name="aspell"/>
+    <match install="gimp-help-%s" name="gimp-help"/>
+  </langpacks>
+</comps>
+"#;
+
+#[test]
+fn test_comps_xml_read() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<CompsXml>(COMPS_XML)?;
+
+    let comps = repo.comps.as_ref().unwrap();
+    assert_eq!(comps.groups.len(), 2);
+    assert_eq!(comps.categories.len(), 1);
+    assert_eq!(comps.environments.len(), 1);
+    assert_eq!(comps.langpacks.len(), 2);
+
+    let core = &comps.groups[0];
+    assert_eq!(core.id, "core");
+    assert_eq!(core.name, "Core");
+    assert_eq!(
+        core.name_by_lang.get("fr").map(String::as_str),
+        Some("Noyau")
+    );
+    assert_eq!(
+        core.desc_by_lang.get("de").map(String::as_str),
+        Some("Kleinstmögliche Installation")
+    );
+    assert!(!core.default);
+    assert!(!core.uservisible);
+    assert_eq!(core.display_order, None);
+    assert_eq!(
+        core.packages[2],
+        CompsPackageReq {
+            name: "dracut-config-rescue".to_owned(),
+            reqtype: CompsPackageType::Optional,
+            requires: None,
+            basearchonly: true,
+        }
+    );
+
+    let german = &comps.groups[1];
+    assert!(german.biarchonly);
+    assert_eq!(german.display_order, Some(100));
+    assert_eq!(german.langonly.as_deref(), Some("de"));
+    assert_eq!(german.packages[0].reqtype, CompsPackageType::Conditional);
+    assert_eq!(german.packages[0].requires.as_deref(), Some("gimp"));
+
+    assert_eq!(
+        comps.categories[0].group_ids,
+        vec!["core".to_owned(), "german-support".to_owned()]
+    );
+
+    let environment = &comps.environments[0];
+    assert_eq!(environment.group_ids, vec!["core".to_owned()]);
+    assert_eq!(
+        environment.options,
+        vec![
+            CompsEnvironmentOption {
+                group_id: "german-support".to_owned(),
+                default: false,
+            },
+            CompsEnvironmentOption {
+                group_id: "guest-agents".to_owned(),
+                default: true,
+            },
+        ]
+    );
+
+    assert_eq!(
+        comps.langpacks[1],
+        CompsLangpack {
+            name: "gimp-help".to_owned(),
+            install: "gimp-help-%s".to_owned(),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_comps_xml_roundtrip() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<CompsXml>(COMPS_XML)?;
+
+    let actual = repo.to_string::<CompsXml>()?;
+    assert_eq!(actual, COMPS_XML);
+
+    Ok(())
+}
+
+#[test]
+fn test_comps_unknown_package_type() {
+    let comps_xml = COMPS_XML.replace(r#"type="default""#, r#"type="bogus""#);
+
+    let mut repo = Repository::new();
+    assert!(matches!(
+        repo.load_metadata_str::<CompsXml>(&comps_xml),
+        Err(MetadataError::UnknownCompsPackageTypeError(_))
+    ));
+}
+
+#[test]
+fn test_comps_xml_read_truncated() {
+    // the file ends inside of each kind of element in turn
+    for end_tag in &["</group>", "</category>", "</environment>", "</langpacks>"] {
+        let truncated = &COMPS_XML[..COMPS_XML.find(end_tag).unwrap()];
+
+        let mut repo = Repository::new();
+        assert!(matches!(
+            repo.load_metadata_str::<CompsXml>(truncated),
+            Err(MetadataError::InconsistentMetadataError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_comps_write_to_directory() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<CompsXml>(COMPS_XML)?;
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    let group = repo.get_record("group").unwrap();
+    assert!(group.location_href.ends_with("-comps.xml"));
+    assert_eq!(group.open_checksum, None);
+
+    let group_gz = repo.get_record("group_gz").unwrap();
+    assert!(group_gz.location_href.ends_with("-comps.xml.gz"));
+    assert_eq!(group_gz.open_checksum.as_ref(), Some(&group.checksum));
+    assert_eq!(group_gz.open_size, group.size);
+
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(reloaded.comps, repo.comps);
+
+    Ok(())
+}
@@ -127,6 +127,37 @@ fn test_filelists_xml_writer_file() -> Result<(), MetadataError> {
     Ok(())
 }
 
+#[test]
+fn test_filelists_xml_reader_truncated() -> Result<(), MetadataError> {
+    // the file ends in the middle of a package
+    let truncated = &COMPLEX_FILELISTS[..COMPLEX_FILELISTS.find("</package>").unwrap()];
+    let mut reader = FilelistsXml::new_reader(quick_xml::Reader::from_reader(truncated.as_bytes()));
+    reader.read_header()?;
+    assert!(matches!(
+        reader.read_package(&mut None),
+        Err(MetadataError::InconsistentMetadataError(_))
+    ));
+
+    // the file ends before the entry of a package that is expected
+    let truncated = &COMPLEX_FILELISTS[..COMPLEX_FILELISTS.find("  <package").unwrap()];
+    let mut reader = FilelistsXml::new_reader(quick_xml::Reader::from_reader(truncated.as_bytes()));
+    reader.read_header()?;
+    let mut package = Some(Package::default());
+    assert!(matches!(
+        reader.read_package(&mut package),
+        Err(MetadataError::InconsistentMetadataError(_))
+    ));
+
+    // but if no package is expected, that's just the end of the file
+    let mut reader = FilelistsXml::new_reader(quick_xml::Reader::from_reader(truncated.as_bytes()));
+    reader.read_header()?;
+    let mut package = None;
+    reader.read_package(&mut package)?;
+    assert!(package.is_none());
+
+    Ok(())
+}
+
 // pub(crate) fn to_string<M: RpmMetadata>(&self) -> Result<String, MetadataError> {
 //     let bytes = self.to_bytes::<M>()?;
 //     Ok(String::from_utf8(bytes).map_err(|e| e.utf8_error())?)
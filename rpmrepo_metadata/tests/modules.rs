@@ -0,0 +1,245 @@
+extern crate rpmrepo_metadata;
+
+use pretty_assertions::assert_eq;
+
+use rpmrepo_metadata::*;
+use tempdir::TempDir;
+
+static MODULES_YAML: &str = r#"---
+document: modulemd
+version: 2
+data:
+  name: nodejs
+  stream: 12
+  version: 8030020210519085237
+  context: bd1311ed
+  arch: x86_64
+  summary: Javascript runtime
+  description: >-
+    Node.js is a platform built on Chrome's JavaScript runtime for easily building
+    fast, scalable network applications.
+  servicelevels:
+    rawhide:
+      eol: 2021-11-30
+  license:
+    module:
+    - MIT
+    content:
+    - MIT and ASL 2.0 and ISC and BSD
+  dependencies:
+  - buildrequires:
+      platform: [el8.4.0]
+    requires:
+      platform: [el8]
+  references:
+    community: http://nodejs.org
+  profiles:
+    common:
+      rpms:
+      - nodejs
+      - npm
+    development:
+      rpms:
+      - nodejs
+      - nodejs-devel
+      - npm
+  api:
+    rpms:
+    - nodejs
+    - npm
+  components:
+    rpms:
+      nodejs:
+        rationale: Javascript runtime and npm package manager.
+        repository: git+https://src.fedoraproject.org/rpms/nodejs
+        cache: https://src.fedoraproject.org/repo/pkgs/nodejs
+        ref: 12
+        buildorder: 10
+      nodejs-packaging:
+        rationale: RPM macros for building Node.js modules.
+        ref: 12
+        buildonly: true
+        buildroot: true
+        buildafter:
+        - nodejs
+  artifacts:
+    rpms:
+    - nodejs-1:12.22.1-1.module+el8.4.0+10990+dea8e5ec.src
+    - nodejs-1:12.22.1-1.module+el8.4.0+10990+dea8e5ec.x86_64
+    - npm-1:6.14.12-1.12.22.1.1.module+el8.4.0+10990+dea8e5ec.x86_64
+    rpm-map:
+      sha256:
+        8a2b4bb2d5c8cbd0b4a5d6b5fc0eb3e3d8d0c1b6d0ba4f3e0d1b2c3a4b5c6d7e:
+          name: npm
+          epoch: 1
+          version: 6.14.12
+          release: 1.12.22.1.1.module+el8.4.0+10990+dea8e5ec
+          arch: x86_64
+          nevra: npm-1:6.14.12-1.12.22.1.1.module+el8.4.0+10990+dea8e5ec.x86_64
+...
+---
+document: modulemd-defaults
+version: 1
+data:
+  module: nodejs
+  stream: "10"
+  profiles:
+    "10": [common]
+    "12": [common]
+...
+---
+document: modulemd-obsoletes
+version: 1
+data:
+  modified: 2021-06-08T00:00Z
+  module: nodejs
+  stream: "10"
+  message: Module stream nodejs:10 is no longer supported.
+  eol_date: 2021-05-01T00:00Z
+  obsoleted_by:
+    module: nodejs
+    stream: "12"
+...
+---
+document: modulemd-translations
+version: 1
+data:
+  module: nodejs
+  stream: "12"
+  modified: 202106080000
+  translations:
+    de:
+      summary: Javascript-Laufzeitumgebung
+...
+"#;
+
+#[test]
+fn test_modules_yaml_read() -> Result<(), MetadataError> {
+    let modules = Modules::from_yaml_str(MODULES_YAML)?;
+
+    assert_eq!(modules.streams.len(), 1);
+    assert_eq!(modules.defaults.len(), 1);
+    assert_eq!(modules.obsoletes.len(), 1);
+    assert_eq!(modules.other_documents.len(), 1);
+
+    let stream = &modules.streams[0];
+    assert_eq!(stream.name, "nodejs");
+    assert_eq!(stream.stream, "12");
+    assert_eq!(stream.version, 8030020210519085237);
+    assert_eq!(stream.context, "bd1311ed");
+    assert_eq!(stream.arch.as_deref(), Some("x86_64"));
+    assert_eq!(stream.license.module, vec!["MIT".to_owned()]);
+    assert_eq!(
+        stream.dependencies[0].requires.get("platform"),
+        Some(&vec!["el8".to_owned()])
+    );
+    assert_eq!(
+        stream.profiles["development"].rpms,
+        vec![
+            "nodejs".to_owned(),
+            "nodejs-devel".to_owned(),
+            "npm".to_owned()
+        ]
+    );
+    assert_eq!(
+        stream.api.as_ref().unwrap().rpms,
+        vec!["nodejs".to_owned(), "npm".to_owned()]
+    );
+
+    let nodejs = &stream.components.as_ref().unwrap().rpms["nodejs"];
+    assert_eq!(nodejs.git_ref.as_deref(), Some("12"));
+    assert_eq!(nodejs.buildorder, Some(10));
+    let nodejs_packaging = &stream.components.as_ref().unwrap().rpms["nodejs-packaging"];
+    assert!(nodejs_packaging.buildonly && nodejs_packaging.buildroot);
+    assert_eq!(nodejs_packaging.buildafter, vec!["nodejs".to_owned()]);
+    assert_eq!(
+        stream.servicelevels["rawhide"].eol.as_deref(),
+        Some("2021-11-30")
+    );
+    let artifacts = stream.artifacts.as_ref().unwrap();
+    assert_eq!(artifacts.rpms.len(), 3);
+    assert!(artifacts.rpm_map.is_some());
+
+    assert_eq!(
+        modules.defaults[0],
+        ModuleDefaults {
+            module: "nodejs".to_owned(),
+            stream: Some("10".to_owned()),
+            profiles: vec![
+                ("10".to_owned(), vec!["common".to_owned()]),
+                ("12".to_owned(), vec!["common".to_owned()]),
+            ]
+            .into_iter()
+            .collect(),
+            intents: None,
+        }
+    );
+
+    let obsoletes = &modules.obsoletes[0];
+    assert_eq!(obsoletes.stream, "10");
+    assert!(!obsoletes.reset);
+    assert_eq!(obsoletes.eol_date.as_deref(), Some("2021-05-01T00:00Z"));
+    assert_eq!(
+        obsoletes.obsoleted_by,
+        Some(ModuleObsoletedBy {
+            module: "nodejs".to_owned(),
+            stream: "12".to_owned(),
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_modules_yaml_roundtrip() -> Result<(), MetadataError> {
+    let modules = Modules::from_yaml_str(MODULES_YAML)?;
+
+    let yaml = modules.to_yaml_string()?;
+    assert!(yaml.starts_with("---\ndocument: modulemd\n"));
+    assert!(yaml.ends_with("...\n"));
+    assert_eq!(yaml.matches("\n...\n").count(), 4);
+
+    assert_eq!(Modules::from_yaml_str(&yaml)?, modules);
+    assert!(yaml.contains("servicelevels:"));
+    assert!(yaml.contains("rpm-map:"));
+    assert!(yaml.contains("buildafter:"));
+
+    Ok(())
+}
+
+#[test]
+fn test_modules_yaml_read_directives() -> Result<(), MetadataError> {
+    // documents may start with directives, and the end marker is optional
+    let yaml = r#"%YAML 1.1
+---
+document: modulemd-defaults
+version: 1
+data:
+  module: nodejs
+  stream: 8
+"#;
+    let modules = Modules::from_yaml_str(yaml)?;
+
+    assert_eq!(modules.defaults.len(), 1);
+    assert_eq!(modules.defaults[0].stream.as_deref(), Some("8"));
+
+    Ok(())
+}
+
+#[test]
+fn test_modules_write_to_directory() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+
+    let mut repo = Repository::new();
+    repo.modules = Some(Modules::from_yaml_str(MODULES_YAML)?);
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    let record = repo.get_record("modules").unwrap();
+    assert!(record.location_href.ends_with("-modules.yaml.gz"));
+    assert!(record.open_checksum.is_some());
+
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(reloaded.modules, repo.modules);
+
+    Ok(())
+}
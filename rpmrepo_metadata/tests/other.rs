@@ -124,3 +124,34 @@ fn test_other_xml_writer_file() -> Result<(), MetadataError> {
 
     Ok(())
 }
+
+#[test]
+fn test_other_xml_reader_truncated() -> Result<(), MetadataError> {
+    // the file ends in the middle of a package
+    let truncated = &COMPLEX_OTHERDATA[..COMPLEX_OTHERDATA.find("</package>").unwrap()];
+    let mut reader = OtherXml::new_reader(quick_xml::Reader::from_reader(truncated.as_bytes()));
+    reader.read_header()?;
+    assert!(matches!(
+        reader.read_package(&mut None),
+        Err(MetadataError::InconsistentMetadataError(_))
+    ));
+
+    // the file ends before the entry of a package that is expected
+    let truncated = &COMPLEX_OTHERDATA[..COMPLEX_OTHERDATA.find("  <package").unwrap()];
+    let mut reader = OtherXml::new_reader(quick_xml::Reader::from_reader(truncated.as_bytes()));
+    reader.read_header()?;
+    let mut package = Some(Package::default());
+    assert!(matches!(
+        reader.read_package(&mut package),
+        Err(MetadataError::InconsistentMetadataError(_))
+    ));
+
+    // but if no package is expected, that's just the end of the file
+    let mut reader = OtherXml::new_reader(quick_xml::Reader::from_reader(truncated.as_bytes()));
+    reader.read_header()?;
+    let mut package = None;
+    reader.read_package(&mut package)?;
+    assert!(package.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,257 @@
+extern crate rpmrepo_metadata;
+
+use std::path::Path;
+
+use pretty_assertions::assert_eq;
+use rpmrepo_metadata::{
+    Checksum, ChecksumType, FileType, MetadataError, Package, Requirement, EVR,
+};
+
+mod common;
+
+#[test]
+fn test_package_from_rpm_file_checksum_type() -> Result<(), MetadataError> {
+    let path = Path::new(common::RPM_SIGN_PACKAGE_FIXTURE_PATH);
+
+    let sha1 = Package::from_rpm_file(path, ChecksumType::Sha1, "rpm-sign.rpm")?;
+    let sha512 = Package::from_rpm_file(path, ChecksumType::Sha512, "rpm-sign.rpm")?;
+
+    assert_eq!(
+        sha1.checksum(),
+        &Checksum::Sha1("59708de4eed2259d50f78127eff189116fa1adbd".to_owned())
+    );
+    assert_eq!(
+        sha512.checksum(),
+        &Checksum::Sha512("5a6aa8bd0b1324b6046497b22804e4aaa04119225ad640e1d4f0f7f742237a8ff1fd0a481733e92cd2b4e14dd3ba609d531f058b77b79a4fe5ef7427fa67e4d3".to_owned())
+    );
+
+    let mtime = std::fs::metadata(path)?
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert_eq!(sha1.time().file, mtime);
+
+    Ok(())
+}
+
+// The expected values were read from the raw headers of the package, independently of rpm-rs
+#[test]
+fn test_package_from_rpm_file_fedora() -> Result<(), MetadataError> {
+    let package = Package::from_rpm_file(
+        Path::new(common::RPM_SIGN_PACKAGE_FIXTURE_PATH),
+        ChecksumType::Sha256,
+        "Packages/r/rpm-sign-4.15.1-1.fc31.x86_64.rpm",
+    )?;
+
+    assert_eq!(package.name(), "rpm-sign");
+    assert_eq!(package.arch(), "x86_64");
+    assert_eq!(package.evr(), &EVR::new("0", "4.15.1", "1.fc31"));
+    assert_eq!(
+        package.checksum(),
+        &Checksum::Sha256(
+            "ac4bffeae67858a60a1a2b66b7f0f3b5a0fcf2030133749e73f98e42b42f307d".to_owned()
+        )
+    );
+    assert_eq!(
+        package.location_href(),
+        "Packages/r/rpm-sign-4.15.1-1.fc31.x86_64.rpm"
+    );
+    assert_eq!(package.summary(), "Package signing support");
+    assert_eq!(package.packager(), "Fedora Project");
+    assert_eq!(package.url(), "http://www.rpm.org/");
+    assert_eq!(package.time().build, 1574156420);
+    assert_eq!(package.size().package, 25056);
+    assert_eq!(package.size().installed, 22412);
+    assert_eq!(package.size().archive, 23276);
+
+    assert_eq!(package.rpm_license(), "GPLv2+");
+    assert_eq!(package.rpm_vendor(), "Fedora Project");
+    assert_eq!(package.rpm_group(), "Unspecified");
+    assert_eq!(package.rpm_buildhost(), "buildvm-05.phx2.fedoraproject.org");
+    assert_eq!(package.rpm_sourcerpm(), "rpm-4.15.1-1.fc31.src.rpm");
+    assert_eq!(package.rpm_header_range().start, 4504);
+    assert_eq!(package.rpm_header_range().end, 17328);
+
+    // the rpmlib() requirements are left out
+    let requires: Vec<&str> = package.requires().iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(
+        requires,
+        vec![
+            "libc.so.6()(64bit)",
+            "libc.so.6(GLIBC_2.2.5)(64bit)",
+            "libc.so.6(GLIBC_2.3.4)(64bit)",
+            "libc.so.6(GLIBC_2.4)(64bit)",
+            "libpopt.so.0()(64bit)",
+            "libpopt.so.0(LIBPOPT_0)(64bit)",
+            "libpthread.so.0()(64bit)",
+            "libpthread.so.0(GLIBC_2.2.5)(64bit)",
+            "librpm.so.9()(64bit)",
+            "librpmio.so.9()(64bit)",
+            "librpmsign.so.9()(64bit)",
+            "rpm-sign-libs(x86-64)",
+            "rtld(GNU_HASH)",
+        ]
+    );
+    assert_eq!(
+        package.requires()[11],
+        requirement(
+            "rpm-sign-libs(x86-64)",
+            Some("EQ"),
+            Some(("0", "4.15.1", "1.fc31"))
+        )
+    );
+    assert_eq!(
+        package.provides(),
+        &[
+            requirement("rpm-sign", Some("EQ"), Some(("0", "4.15.1", "1.fc31"))),
+            requirement(
+                "rpm-sign(x86-64)",
+                Some("EQ"),
+                Some(("0", "4.15.1", "1.fc31"))
+            ),
+        ]
+    );
+    assert!(package.conflicts().is_empty());
+    assert!(package.obsoletes().is_empty());
+    assert!(package.suggests().is_empty());
+    assert!(package.enhances().is_empty());
+    assert!(package.recommends().is_empty());
+    assert!(package.supplements().is_empty());
+
+    let files: Vec<(FileType, &str)> = package
+        .files()
+        .iter()
+        .map(|f| (f.filetype, f.path.as_str()))
+        .collect();
+    assert_eq!(
+        files,
+        vec![
+            (FileType::File, "/usr/bin/rpmsign"),
+            (FileType::Dir, "/usr/lib/.build-id"),
+            (FileType::Dir, "/usr/lib/.build-id/a6"),
+            (
+                FileType::File,
+                "/usr/lib/.build-id/a6/d79cbdf87a3979ddb9fdfa6459d588afd476a5"
+            ),
+            (FileType::File, "/usr/share/man/man8/rpmsign.8.gz"),
+        ]
+    );
+
+    // oldest first
+    let changelogs = package.changelogs();
+    assert_eq!(changelogs.len(), 69);
+    assert_eq!(changelogs[0].date, 1516104000);
+    assert_eq!(
+        changelogs[68].author,
+        "Panu Matilainen <pmatilai@redhat.com> - 4.15.1-1"
+    );
+    assert_eq!(changelogs[68].date, 1574078400);
+    assert_eq!(
+        changelogs[68].description,
+        "- Rebase to 4.15.1 (https://rpm.org/wiki/Releases/4.15.1)"
+    );
+
+    Ok(())
+}
+
+fn requirement(name: &str, flags: Option<&str>, evr: Option<(&str, &str, &str)>) -> Requirement {
+    let (epoch, version, release) = match evr {
+        Some((e, v, r)) => (
+            Some(e).filter(|e| !e.is_empty()),
+            Some(v),
+            Some(r).filter(|r| !r.is_empty()),
+        ),
+        None => (None, None, None),
+    };
+    Requirement {
+        name: name.to_owned(),
+        flags: flags.map(str::to_owned),
+        epoch: epoch.map(str::to_owned),
+        version: version.map(str::to_owned),
+        release: release.map(str::to_owned),
+        ..Requirement::default()
+    }
+}
+
+#[test]
+fn test_requirement_is_satisfied_by() {
+    let provide = requirement("foo", Some("EQ"), Some(("0", "1.2", "3")));
+
+    // name mismatch
+    assert!(!requirement("bar", None, None).is_satisfied_by(&provide));
+
+    // unversioned requirement / provide
+    assert!(requirement("foo", None, None).is_satisfied_by(&provide));
+    let unversioned_provide = requirement("foo", None, None);
+    assert!(
+        requirement("foo", Some("LT"), Some(("", "0.1", ""))).is_satisfied_by(&unversioned_provide)
+    );
+
+    let cases = [
+        ("EQ", ("0", "1.2", "3"), true),
+        ("EQ", ("0", "1.2", "4"), false),
+        ("LT", ("0", "1.2", "3"), false),
+        ("LT", ("0", "1.2", "4"), true),
+        ("LE", ("0", "1.2", "3"), true),
+        ("GT", ("0", "1.2", "3"), false),
+        ("GT", ("0", "1.1", "9"), true),
+        ("GE", ("0", "1.2", "3"), true),
+        ("GE", ("0", "1.10", "1"), false),
+        // a missing epoch is 0
+        ("EQ", ("", "1.2", "3"), true),
+        ("GE", ("1", "1.0", "1"), false),
+        // a missing release only compares epoch and version
+        ("EQ", ("0", "1.2", ""), true),
+        ("GT", ("0", "1.2", ""), false),
+        ("LE", ("0", "1.2", ""), true),
+        ("LT", ("0", "1.2", ""), false),
+    ];
+    for (flags, evr, expected) in &cases {
+        let required = requirement("foo", Some(*flags), Some(*evr));
+        assert_eq!(
+            required.is_satisfied_by(&provide),
+            *expected,
+            "foo {} {:?}",
+            flags,
+            evr
+        );
+    }
+
+    // ranges on both sides
+    let provide = requirement("foo", Some("GE"), Some(("0", "2.0", "1")));
+    assert!(requirement("foo", Some("GT"), Some(("0", "3.0", "1"))).is_satisfied_by(&provide));
+    assert!(!requirement("foo", Some("LT"), Some(("0", "2.0", "1"))).is_satisfied_by(&provide));
+    assert!(requirement("foo", Some("LE"), Some(("0", "2.0", "1"))).is_satisfied_by(&provide));
+}
+
+#[test]
+fn test_package_satisfies() {
+    let package = &*common::COMPLEX_PACKAGE;
+
+    // implicit "name = EVR" provide
+    assert!(package.satisfies(&requirement(
+        "complex-package",
+        Some("GE"),
+        Some(("1", "2.3.4", ""))
+    )));
+    assert!(!package.satisfies(&requirement(
+        "complex-package",
+        Some("GT"),
+        Some(("1", "2.3.4", "5.el8"))
+    )));
+
+    // explicit provides
+    assert!(package.satisfies(&requirement("laughter", Some("GE"), Some(("", "30", "")))));
+    assert!(!package.satisfies(&requirement("laughter", Some("LT"), Some(("", "33", "")))));
+    assert!(package.satisfies(&requirement(
+        "narration(ronhoward)",
+        Some("GE"),
+        Some(("", "1", ""))
+    )));
+    assert!(!package.satisfies(&requirement("tragedy", None, None)));
+
+    // files
+    let file = &package.files()[0].path;
+    assert!(package.satisfies(&requirement(file, None, None)));
+}
@@ -0,0 +1,116 @@
+extern crate rpmrepo_metadata;
+
+use pretty_assertions::assert_eq;
+
+use rpmrepo_metadata::*;
+use tempdir::TempDir;
+
+static PRESTODELTA_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<prestodelta>
+  <newpackage name="nano" epoch="0" version="4.9.3" release="1.fc32" arch="x86_64">
+    <delta oldepoch="0" oldversion="4.9.2" oldrelease="1.fc32">
+      <filename>drpms/nano-4.9.2-1.fc32_4.9.3-1.fc32.x86_64.drpm</filename>
+      <sequence>nano-4.9.2-1.fc32-4a4d1f7fc4e2fb7a3e4e6b1d23ac4c9c</sequence>
+      <size>123017</size>
+      <checksum type="sha256">5ce1a3b9ad0d1fbb4a3e5f1fc4c09c4cbd1a7b3e26f0b1dd9b5d07ecc6cb7d11</checksum>
+    </delta>
+    <delta oldepoch="0" oldversion="4.9.1" oldrelease="2.fc32">
+      <filename>drpms/nano-4.9.1-2.fc32_4.9.3-1.fc32.x86_64.drpm</filename>
+      <sequence>nano-4.9.1-2.fc32-0d6c9b0e5b41b3a87ac6f0fb3e3b8c16</sequence>
+      <size>201544</size>
+      <checksum type="sha256">a1f3a2b2e0c1b3f2bd5ed1b9cb96f5a2fd79e8a64b0a0f0fe1fd0c1f7ab5d3c2</checksum>
+    </delta>
+  </newpackage>
+  <newpackage name="bash" epoch="0" version="5.0.17" release="1.fc32" arch="x86_64">
+    <delta oldepoch="0" oldversion="5.0.11" oldrelease="2.fc32">
+      <filename>drpms/bash-5.0.11-2.fc32_5.0.17-1.fc32.x86_64.drpm</filename>
+      <sequence>bash-5.0.11-2.fc32-83e0d4b1c15ffc1e2f2b9d75ebb4f7ad</sequence>
+      <size>774012</size>
+      <checksum type="sha256">0e9f3f5bde24fa8fda52d7dc1c4c7e77e2b1f2a2ab7dc8f0b4bb3aab0fd3a77c</checksum>
+    </delta>
+  </newpackage>
+</prestodelta>
+"#;
+
+#[test]
+fn test_prestodelta_xml_read() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<PrestodeltaXml>(PRESTODELTA_XML)?;
+
+    assert_eq!(repo.deltas.len(), 2);
+
+    let nano = &repo.deltas[0];
+    assert_eq!(nano.name, "nano");
+    assert_eq!(nano.arch, "x86_64");
+    assert_eq!(nano.evr, EVR::new("0", "4.9.3", "1.fc32"));
+    assert_eq!(nano.deltas.len(), 2);
+    assert_eq!(
+        nano.deltas[0],
+        Delta {
+            old_evr: EVR::new("0", "4.9.2", "1.fc32"),
+            filename: "drpms/nano-4.9.2-1.fc32_4.9.3-1.fc32.x86_64.drpm".to_owned(),
+            sequence: "nano-4.9.2-1.fc32-4a4d1f7fc4e2fb7a3e4e6b1d23ac4c9c".to_owned(),
+            size: 123017,
+            checksum: Checksum::Sha256(
+                "5ce1a3b9ad0d1fbb4a3e5f1fc4c09c4cbd1a7b3e26f0b1dd9b5d07ecc6cb7d11".to_owned()
+            ),
+        }
+    );
+
+    assert_eq!(repo.deltas[1].deltas[0].size, 774012);
+
+    Ok(())
+}
+
+#[test]
+fn test_prestodelta_xml_roundtrip() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<PrestodeltaXml>(PRESTODELTA_XML)?;
+
+    let actual = repo.to_string::<PrestodeltaXml>()?;
+    assert_eq!(actual, PRESTODELTA_XML);
+
+    Ok(())
+}
+
+#[test]
+fn test_prestodelta_missing_attribute() {
+    let prestodelta_xml = PRESTODELTA_XML.replace(r#" oldversion="4.9.1""#, "");
+
+    let mut repo = Repository::new();
+    assert!(matches!(
+        repo.load_metadata_str::<PrestodeltaXml>(&prestodelta_xml),
+        Err(MetadataError::MissingAttributeError("oldversion"))
+    ));
+}
+
+#[test]
+fn test_prestodelta_xml_read_truncated() {
+    // the file ends inside of a <delta>, and then inside of a <newpackage>
+    for end_tag in &["</delta>", "</newpackage>"] {
+        let truncated = &PRESTODELTA_XML[..PRESTODELTA_XML.find(end_tag).unwrap()];
+
+        let mut repo = Repository::new();
+        assert!(matches!(
+            repo.load_metadata_str::<PrestodeltaXml>(truncated),
+            Err(MetadataError::InconsistentMetadataError(_))
+        ));
+    }
+}
+
+#[test]
+fn test_prestodelta_write_to_directory() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<PrestodeltaXml>(PRESTODELTA_XML)?;
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    let record = repo.get_record("prestodelta").unwrap();
+    assert!(record.location_href.ends_with("-prestodelta.xml.gz"));
+
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(reloaded.deltas, repo.deltas);
+
+    Ok(())
+}
@@ -175,3 +175,43 @@ fn test_primary_xml_writer_file() -> Result<(), MetadataError> {
 
     Ok(())
 }
+
+#[test]
+fn test_primary_xml_read_truncated() -> Result<(), MetadataError> {
+    // the file ends inside of a requirement list, inside of <format> and inside of <package>
+    for end_marker in &["</rpm:requires>", "</format>", "</package>"] {
+        let truncated = &COMPLEX_PRIMARY[..COMPLEX_PRIMARY.find(end_marker).unwrap()];
+
+        let mut repo = Repository::new();
+        assert!(matches!(
+            repo.load_metadata_str::<PrimaryXml>(truncated),
+            Err(MetadataError::InconsistentMetadataError(_))
+        ));
+
+        let mut reader =
+            PrimaryXml::new_reader(quick_xml::Reader::from_reader(truncated.as_bytes()));
+        reader.read_header()?;
+        assert!(matches!(
+            reader.read_package(&mut None),
+            Err(MetadataError::InconsistentMetadataError(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_primary_xml_read_unsupported_package_type() {
+    let primary = COMPLEX_PRIMARY.replace(r#"<package type="rpm">"#, r#"<package type="srpm">"#);
+    let mut repo = Repository::new();
+    assert!(matches!(
+        repo.load_metadata_str::<PrimaryXml>(&primary),
+        Err(MetadataError::InconsistentMetadataError(_))
+    ));
+
+    let primary = COMPLEX_PRIMARY.replace(r#"<package type="rpm">"#, "<package>");
+    assert!(matches!(
+        repo.load_metadata_str::<PrimaryXml>(&primary),
+        Err(MetadataError::MissingAttributeError("type"))
+    ));
+}
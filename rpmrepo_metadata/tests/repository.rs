@@ -1,6 +1,12 @@
 extern crate rpmrepo_metadata;
 
-use rpmrepo_metadata::{MetadataError, Package, Repository, RepositoryOptions, RepositoryWriter};
+use rpmrepo_metadata::{
+    Checksum, ChecksumType, CompressionType, MetadataError, Package, PackageIterator, RepomdXml,
+    Repository, RepositoryOptions, RepositoryWriter,
+};
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
+
 mod common;
 
 #[ignore]
@@ -29,11 +35,544 @@ fn complex_repo() -> Result<(), MetadataError> {
 fn test_repository_writer() -> Result<(), MetadataError> {
     use pretty_assertions::assert_eq;
 
-    let repo_options = RepositoryOptions::default();
-    let mut repo_writer = RepositoryWriter::new("testrepo123")?;
+    let working_dir = TempDir::new("")?;
 
+    let mut repo_writer = RepositoryWriter::new(working_dir.path())?;
     repo_writer.start(0)?;
     repo_writer.finish()?;
 
+    assert!(working_dir.path().join("repodata/repomd.xml").exists());
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    assert!(repo.packages().is_empty());
+    let mdtypes: Vec<&str> = repo.records().iter().map(|r| r.mdtype.as_str()).collect();
+    assert_eq!(mdtypes, vec!["primary", "filelists", "other"]);
+    for record in repo.records() {
+        assert!(working_dir.path().join(&record.location_href).exists());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_repository_writer_repomd() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let working_dir = TempDir::new("")?;
+
+    let mut repo_writer = RepositoryWriter::new(working_dir.path())?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(repo.packages().len(), 2);
+
+    let primary = repo.get_primary_data();
+    let (_, checksum) = primary.checksum.to_values()?;
+    assert_eq!(
+        primary.location_href,
+        format!("repodata/{}-primary.xml.gz", checksum)
+    );
+    let primary_path = working_dir.path().join(&primary.location_href);
+    assert_eq!(primary.size, Some(std::fs::metadata(&primary_path)?.len()));
+    assert!(primary.open_size > primary.size);
+    assert!(primary.open_checksum.is_some());
+
+    assert!(repo.get_record("filelists").is_some());
+    assert!(repo.get_record("other").is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_write_to_directory_records() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let source_dir = TempDir::new("")?;
+    let mut repo_writer = RepositoryWriter::new(source_dir.path())?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let mut repo = Repository::load_from_directory(source_dir.path())?;
+    let removed_pkgid = repo.packages().keys().next().unwrap().to_owned();
+    repo.packages_mut().remove(&removed_pkgid);
+    repo.revision = Some("1".to_owned());
+
+    let target_dir = TempDir::new("")?;
+    repo.write_to_directory(target_dir.path(), RepositoryOptions::default())?;
+    assert_ne!(repo.revision.as_deref(), Some("1"));
+
+    let reloaded = Repository::load_from_directory(target_dir.path())?;
+    assert_eq!(reloaded.packages().len(), 1);
+    assert!(!reloaded.packages().contains_key(&removed_pkgid));
+    assert_eq!(reloaded.records(), repo.records());
+    assert_eq!(reloaded.revision, repo.revision);
+
+    for record in reloaded.records() {
+        let file_path = target_dir.path().join(&record.location_href);
+        assert_eq!(record.size, Some(std::fs::metadata(&file_path)?.len()));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_write_to_directory_simple_filenames() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().simple_metadata_filenames(true);
+
+    let mut repo = Repository::new();
+    repo.write_to_directory(working_dir.path(), options)?;
+
+    assert_eq!(
+        repo.get_primary_data().location_href,
+        "repodata/primary.xml.gz"
+    );
+    assert_eq!(
+        repo.get_filelist_data().location_href,
+        "repodata/filelists.xml.gz"
+    );
+    assert_eq!(repo.get_other_data().location_href, "repodata/other.xml.gz");
+    assert!(working_dir.path().join("repodata/primary.xml.gz").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_repository_writer_matches_write_to_directory() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let options = RepositoryOptions::default()
+        .metadata_compression_type(CompressionType::Xz)
+        .metadata_checksum_type(ChecksumType::Sha512);
+
+    let streamed_dir = TempDir::new("")?;
+    let mut repo_writer = RepositoryWriter::new_with_options(streamed_dir.path(), options)?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let streamed = Repository::load_from_directory(streamed_dir.path())?;
+
+    let mut in_memory = Repository::load_from_directory(streamed_dir.path())?;
+    let in_memory_dir = TempDir::new("")?;
+    in_memory.write_to_directory(in_memory_dir.path(), options)?;
+
+    assert_eq!(streamed.records().len(), in_memory.records().len());
+    for (streamed_record, in_memory_record) in streamed.records().iter().zip(in_memory.records()) {
+        assert_eq!(streamed_record.mdtype, in_memory_record.mdtype);
+        assert_eq!(
+            streamed_record.location_href,
+            in_memory_record.location_href
+        );
+        assert_eq!(streamed_record.checksum, in_memory_record.checksum);
+        assert_eq!(
+            streamed_record.open_checksum,
+            in_memory_record.open_checksum
+        );
+        assert!(streamed_record.location_href.ends_with(".xml.xz"));
+        assert!(matches!(streamed_record.checksum, Checksum::Sha512(_)));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_write_to_directory_compression_types() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let compression_types = [
+        (CompressionType::None, ".xml"),
+        (CompressionType::Gzip, ".xml.gz"),
+        (CompressionType::Bz2, ".xml.bz2"),
+        (CompressionType::Xz, ".xml.xz"),
+        (CompressionType::Zstd, ".xml.zst"),
+    ];
+
+    let source_dir = TempDir::new("")?;
+    let mut repo_writer = RepositoryWriter::new(source_dir.path())?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    for (compression_type, extension) in compression_types.iter() {
+        let working_dir = TempDir::new("")?;
+        let options = RepositoryOptions::default().metadata_compression_type(*compression_type);
+
+        let mut repo = Repository::load_from_directory(source_dir.path())?;
+        repo.write_to_directory(working_dir.path(), options)?;
+
+        let primary = repo.get_primary_data();
+        assert!(primary.location_href.ends_with(extension));
+        match compression_type {
+            CompressionType::None => assert_eq!(primary.open_checksum, None),
+            _ => assert!(primary.open_checksum.is_some()),
+        }
+
+        let reloaded = Repository::load_from_directory(working_dir.path())?;
+        assert_eq!(reloaded.packages(), repo.packages());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_load_from_directory_missing_record() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let mut repo = Repository::new();
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    // drop the filelists record from repomd.xml
+    repo.remove_record("filelists");
+    std::fs::write(
+        working_dir.path().join("repodata/repomd.xml"),
+        repo.to_string::<RepomdXml>()?,
+    )?;
+
+    match Repository::load_from_directory(working_dir.path()) {
+        Err(MetadataError::MissingRecordError(mdtype)) => assert_eq!(mdtype, "filelists"),
+        other => panic!(
+            "expected a missing record error, got {:?}",
+            other.map(|_| ())
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_add_and_remove_metadata_file() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let working_dir = TempDir::new("")?;
+    let mut repo = Repository::new();
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    let source_dir = TempDir::new("")?;
+    let manifest_path = source_dir.path().join("manifest.json");
+    std::fs::write(&manifest_path, r#"{"product": "example"}"#)?;
+
+    repo.add_metadata_file(
+        working_dir.path(),
+        &manifest_path,
+        "manifest",
+        RepositoryOptions::default(),
+    )?;
+
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    let record = reloaded.get_record("manifest").unwrap();
+    assert_eq!(record, repo.get_record("manifest").unwrap());
+    let (_, checksum) = record.checksum.to_values()?;
+    assert_eq!(
+        record.location_href,
+        format!("repodata/{}-manifest.json.gz", checksum)
+    );
+    assert_eq!(record.open_size, Some(22));
+    assert!(record.open_checksum.is_some());
+
+    // adding the same type again replaces the record and the file
+    let old_path = working_dir.path().join(&record.location_href);
+    std::fs::write(&manifest_path, r#"{"product": "example", "version": 2}"#)?;
+    let options = RepositoryOptions::default().metadata_compression_type(CompressionType::None);
+    repo.add_metadata_file(working_dir.path(), &manifest_path, "manifest", options)?;
+
+    assert!(!old_path.exists());
+    let record = repo.get_record("manifest").unwrap();
+    assert!(record.location_href.ends_with("-manifest.json"));
+    assert_eq!(record.open_checksum, None);
+    assert_eq!(
+        repo.records()
+            .iter()
+            .filter(|r| r.mdtype == "manifest")
+            .count(),
+        1
+    );
+
+    let new_path = working_dir.path().join(&record.location_href);
+    repo.remove_metadata_file(working_dir.path(), "manifest")?;
+
+    assert!(!new_path.exists());
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    assert!(reloaded.get_record("manifest").is_none());
+    assert!(reloaded.get_record("primary").is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_add_metadata_file_failure() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let mut repo = Repository::new();
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    let source_dir = TempDir::new("")?;
+    let manifest_path = source_dir.path().join("manifest.json");
+    std::fs::write(&manifest_path, r#"{"product": "example"}"#)?;
+    repo.add_metadata_file(
+        working_dir.path(),
+        &manifest_path,
+        "manifest",
+        RepositoryOptions::default(),
+    )?;
+    let repodata_files = std::fs::read_dir(working_dir.path().join("repodata"))?.count();
+
+    // replacing the file with one that doesn't exist leaves the repository untouched
+    assert!(matches!(
+        repo.add_metadata_file(
+            working_dir.path(),
+            &source_dir.path().join("missing.json"),
+            "manifest",
+            RepositoryOptions::default(),
+        ),
+        Err(MetadataError::IoError(_))
+    ));
+    assert!(matches!(
+        repo.add_metadata_file(
+            working_dir.path(),
+            &source_dir.path().join(".."),
+            "manifest",
+            RepositoryOptions::default(),
+        ),
+        Err(MetadataError::IoError(_))
+    ));
+
+    let record = repo.get_record("manifest").unwrap();
+    assert!(working_dir.path().join(&record.location_href).exists());
+    assert_eq!(
+        std::fs::read_dir(working_dir.path().join("repodata"))?.count(),
+        repodata_files
+    );
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(reloaded.get_record("manifest"), Some(record));
+
+    Ok(())
+}
+
+#[test]
+fn test_package_iterator() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let working_dir = TempDir::new("")?;
+
+    {
+        let mut repo_writer = RepositoryWriter::new(working_dir.path())?;
+        repo_writer.start(2)?;
+        repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+        repo_writer.add_package(&common::RPM_EMPTY)?;
+        repo_writer.finish()?;
+    }
+
+    let mut packages = PackageIterator::from_repodata(working_dir.path())?;
+
+    assert_eq!(packages.total_packages(), 2);
+    assert_eq!(&packages.next().unwrap()?, &*common::COMPLEX_PACKAGE);
+    assert_eq!(&packages.next().unwrap()?, &*common::RPM_EMPTY);
+    assert!(packages.next().is_none());
+    assert_eq!(packages.remaining_packages(), 0);
+
+    Ok(())
+}
+
+static UNORDERED_PRIMARY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata xmlns="http://linux.duke.edu/metadata/common" xmlns:rpm="http://linux.duke.edu/metadata/rpm" packages="2">
+  <package type="rpm">
+    <name>horse</name>
+    <arch>noarch</arch>
+    <version epoch="0" ver="4.1" rel="1"/>
+    <checksum type="sha256" pkgid="YES">aaaa</checksum>
+  </package>
+  <package type="rpm">
+    <name>lion</name>
+    <arch>noarch</arch>
+    <version epoch="0" ver="0.4" rel="1"/>
+    <checksum type="sha256" pkgid="YES">bbbb</checksum>
+  </package>
+</metadata>
+"#;
+
+static UNORDERED_FILELISTS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<filelists xmlns="http://linux.duke.edu/metadata/filelists" packages="2">
+  <package pkgid="bbbb" name="lion" arch="noarch">
+    <version epoch="0" ver="0.4" rel="1"/>
+    <file>/usr/bin/lion</file>
+  </package>
+  <package pkgid="aaaa" name="horse" arch="noarch">
+    <version epoch="0" ver="4.1" rel="1"/>
+    <file>/usr/bin/horse</file>
+  </package>
+</filelists>
+"#;
+
+static UNORDERED_OTHER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<otherdata xmlns="http://linux.duke.edu/metadata/other" packages="2">
+  <package pkgid="bbbb" name="lion" arch="noarch">
+    <version epoch="0" ver="0.4" rel="1"/>
+  </package>
+  <package pkgid="aaaa" name="horse" arch="noarch">
+    <version epoch="0" ver="4.1" rel="1"/>
+    <changelog author="Bojack Horseman - 4.1-1" date="1615686706">- Neigh</changelog>
+  </package>
+</otherdata>
+"#;
+
+fn write_metadata(
+    dir: &Path,
+    primary: &str,
+    filelists: &str,
+    other: &str,
+) -> Result<(PathBuf, PathBuf, PathBuf), MetadataError> {
+    let primary_path = dir.join("primary.xml");
+    let filelists_path = dir.join("filelists.xml");
+    let other_path = dir.join("other.xml");
+
+    std::fs::write(&primary_path, primary)?;
+    std::fs::write(&filelists_path, filelists)?;
+    std::fs::write(&other_path, other)?;
+
+    Ok((primary_path, filelists_path, other_path))
+}
+
+#[test]
+fn test_package_iterator_out_of_order() -> Result<(), MetadataError> {
+    use pretty_assertions::assert_eq;
+
+    let working_dir = TempDir::new("")?;
+    let (primary_path, filelists_path, other_path) = write_metadata(
+        working_dir.path(),
+        UNORDERED_PRIMARY,
+        UNORDERED_FILELISTS,
+        UNORDERED_OTHER,
+    )?;
+
+    let packages: Vec<Package> =
+        PackageIterator::from_files(&primary_path, &filelists_path, &other_path)?
+            .collect::<Result<_, _>>()?;
+
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[0].name(), "horse");
+    assert_eq!(packages[0].files()[0].path, "/usr/bin/horse");
+    assert_eq!(packages[0].changelogs()[0].description, "- Neigh");
+    assert_eq!(packages[1].name(), "lion");
+    assert_eq!(packages[1].files()[0].path, "/usr/bin/lion");
+    assert!(packages[1].changelogs().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_package_iterator_out_of_order_buffer_limit() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let (primary_path, filelists_path, other_path) = write_metadata(
+        working_dir.path(),
+        UNORDERED_PRIMARY,
+        UNORDERED_FILELISTS,
+        UNORDERED_OTHER,
+    )?;
+
+    let mut packages = PackageIterator::from_files(&primary_path, &filelists_path, &other_path)?
+        .max_buffered_packages(0);
+    assert!(matches!(
+        packages.next(),
+        Some(Err(MetadataError::BufferLimitExceededError(0)))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_package_iterator_duplicate_entries() -> Result<(), MetadataError> {
+    let duplicate_filelists = r#"<?xml version="1.0" encoding="UTF-8"?>
+<filelists xmlns="http://linux.duke.edu/metadata/filelists" packages="2">
+  <package pkgid="bbbb" name="lion" arch="noarch">
+    <version epoch="0" ver="0.4" rel="1"/>
+    <file>/usr/bin/lion</file>
+  </package>
+  <package pkgid="bbbb" name="lion" arch="noarch">
+    <version epoch="0" ver="0.4" rel="1"/>
+    <file>/usr/bin/lion</file>
+  </package>
+  <package pkgid="aaaa" name="horse" arch="noarch">
+    <version epoch="0" ver="4.1" rel="1"/>
+    <file>/usr/bin/horse</file>
+  </package>
+</filelists>
+"#;
+
+    let working_dir = TempDir::new("")?;
+    let (primary_path, filelists_path, other_path) = write_metadata(
+        working_dir.path(),
+        UNORDERED_PRIMARY,
+        duplicate_filelists,
+        UNORDERED_OTHER,
+    )?;
+
+    let mut packages = PackageIterator::from_files(&primary_path, &filelists_path, &other_path)?;
+    match packages.next() {
+        Some(Err(MetadataError::InconsistentMetadataError(msg))) => {
+            assert!(msg.contains("multiple entries for package bbbb"), "{}", msg)
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_package_iterator_unmatched_entries() -> Result<(), MetadataError> {
+    let primary = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata xmlns="http://linux.duke.edu/metadata/common" xmlns:rpm="http://linux.duke.edu/metadata/rpm" packages="1">
+  <package type="rpm">
+    <name>horse</name>
+    <arch>noarch</arch>
+    <version epoch="0" ver="4.1" rel="1"/>
+    <checksum type="sha256" pkgid="YES">aaaa</checksum>
+  </package>
+</metadata>
+"#;
+    let filelists = r#"<?xml version="1.0" encoding="UTF-8"?>
+<filelists xmlns="http://linux.duke.edu/metadata/filelists" packages="1">
+  <package pkgid="cccc" name="zebra" arch="noarch">
+    <version epoch="0" ver="1.0" rel="1"/>
+  </package>
+  <package pkgid="aaaa" name="horse" arch="noarch">
+    <version epoch="0" ver="4.1" rel="1"/>
+    <file>/usr/bin/horse</file>
+  </package>
+</filelists>
+"#;
+    let other = r#"<?xml version="1.0" encoding="UTF-8"?>
+<otherdata xmlns="http://linux.duke.edu/metadata/other" packages="1">
+  <package pkgid="aaaa" name="horse" arch="noarch">
+    <version epoch="0" ver="4.1" rel="1"/>
+  </package>
+  <package pkgid="dddd" name="giraffe" arch="noarch">
+    <version epoch="0" ver="2.0" rel="1"/>
+  </package>
+</otherdata>
+"#;
+
+    let working_dir = TempDir::new("")?;
+    let (primary_path, filelists_path, other_path) =
+        write_metadata(working_dir.path(), primary, filelists, other)?;
+
+    let mut packages = PackageIterator::from_files(&primary_path, &filelists_path, &other_path)?;
+    assert_eq!(packages.next().unwrap()?.name(), "horse");
+    match packages.next() {
+        Some(Err(MetadataError::InconsistentMetadataError(msg))) => {
+            assert!(msg.contains("filelists.xml cccc"), "{}", msg);
+            assert!(msg.contains("other.xml dddd"), "{}", msg);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(packages.next().is_none());
+
     Ok(())
 }
@@ -0,0 +1,320 @@
+extern crate rpmrepo_metadata;
+
+use std::io::Cursor;
+use std::path::Path;
+
+use pgp::{Deserializable, SignedPublicKey, StandaloneSignature};
+use rpmrepo_metadata::*;
+use tempdir::TempDir;
+
+const TEST_KEY_PATH: &str = "./tests/assets/signing/test-key.asc";
+const TEST_PUBLIC_KEY_PATH: &str = "./tests/assets/signing/test-key.pub.asc";
+const OTHER_PUBLIC_KEY_PATH: &str = "./tests/assets/signing/other-key.pub.asc";
+
+fn signature_is_valid(repo_path: &Path, public_key_path: &str) -> bool {
+    let repomd = std::fs::read(repo_path.join("repodata/repomd.xml")).unwrap();
+    let armored_signature = std::fs::read(repo_path.join("repodata/repomd.xml.asc")).unwrap();
+    let armored_key = std::fs::read(public_key_path).unwrap();
+
+    let (signature, _) = StandaloneSignature::from_armor_single(Cursor::new(armored_signature))
+        .expect("repomd.xml.asc is not an armored signature");
+    let (key, _) = SignedPublicKey::from_armor_single(Cursor::new(armored_key)).unwrap();
+
+    signature.verify(&key, &repomd).is_ok()
+}
+
+#[test]
+fn test_write_to_directory_signed() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let signing_key = SigningKey::from_file(Path::new(TEST_KEY_PATH), None)?;
+
+    let mut repo = Repository::new();
+    repo.write_to_directory_signed(
+        working_dir.path(),
+        RepositoryOptions::default(),
+        &signing_key,
+    )?;
+
+    let signature = std::fs::read_to_string(working_dir.path().join("repodata/repomd.xml.asc"))?;
+    assert!(signature.starts_with("-----BEGIN PGP SIGNATURE-----"));
+
+    assert!(signature_is_valid(working_dir.path(), TEST_PUBLIC_KEY_PATH));
+    assert!(!signature_is_valid(
+        working_dir.path(),
+        OTHER_PUBLIC_KEY_PATH
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_repository_writer_signed() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let signing_key = SigningKey::from_file(Path::new(TEST_KEY_PATH), None)?;
+
+    let mut repo_writer = RepositoryWriter::new(working_dir.path())?;
+    repo_writer.start(0)?;
+    repo_writer.finish_signed(&signing_key)?;
+
+    assert!(signature_is_valid(working_dir.path(), TEST_PUBLIC_KEY_PATH));
+
+    Ok(())
+}
+
+#[test]
+fn test_sign_repomd() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let signing_key = SigningKey::from_file(Path::new(TEST_KEY_PATH), None)?;
+
+    let mut repo = Repository::new();
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+    assert!(!working_dir.path().join("repodata/repomd.xml.asc").exists());
+
+    let signature_path = Repository::sign_repomd(working_dir.path(), &signing_key)?;
+    assert_eq!(
+        signature_path,
+        working_dir.path().join("repodata/repomd.xml.asc")
+    );
+    assert!(signature_is_valid(working_dir.path(), TEST_PUBLIC_KEY_PATH));
+
+    Ok(())
+}
+
+#[test]
+fn test_stale_signature_removed() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let signing_key = SigningKey::from_file(Path::new(TEST_KEY_PATH), None)?;
+
+    let mut repo = Repository::new();
+    repo.write_to_directory_signed(
+        working_dir.path(),
+        RepositoryOptions::default(),
+        &signing_key,
+    )?;
+    assert!(working_dir.path().join("repodata/repomd.xml.asc").exists());
+
+    // an unsigned rewrite must not leave a signature of the old repomd.xml behind
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+    assert!(!working_dir.path().join("repodata/repomd.xml.asc").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_signing_key_invalid() {
+    assert!(matches!(
+        SigningKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH), None),
+        Err(MetadataError::PgpError(_))
+    ));
+    assert!(matches!(
+        SigningKey::from_file(Path::new("./tests/assets/signing/nonexistent.asc"), None),
+        Err(MetadataError::IoError(_))
+    ));
+}
+
+fn write_signed_repository(path: &Path) -> Result<(), MetadataError> {
+    let signing_key = SigningKey::from_file(Path::new(TEST_KEY_PATH), None)?;
+    let mut repo = Repository::new();
+    repo.add_package(Package::new(
+        "rpm-empty",
+        &EVR::new("0", "0", "0"),
+        "x86_64",
+        &Checksum::Sha256("aaaa".to_owned()),
+        "rpm-empty-0-0.x86_64.rpm",
+    ))?;
+    repo.write_to_directory_signed(path, RepositoryOptions::default(), &signing_key)
+}
+
+#[test]
+fn test_load_verified() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    write_signed_repository(working_dir.path())?;
+
+    let keys = vec![
+        VerifyingKey::from_file(Path::new(OTHER_PUBLIC_KEY_PATH))?,
+        VerifyingKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH))?,
+    ];
+    let options = RepositoryLoadOptions::default().verification_keys(keys.clone());
+    let repo = Repository::load_from_directory_with_options(working_dir.path(), options)?;
+    assert_eq!(repo.packages().len(), 1);
+
+    Repository::verify_repomd(working_dir.path(), &keys)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_load_verified_wrong_key() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    write_signed_repository(working_dir.path())?;
+
+    let keys = vec![VerifyingKey::from_file(Path::new(OTHER_PUBLIC_KEY_PATH))?];
+    let options = RepositoryLoadOptions::default().verification_keys(keys);
+    assert!(matches!(
+        Repository::load_from_directory_with_options(working_dir.path(), options),
+        Err(MetadataError::SignatureVerificationError(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_load_verified_tampered() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    write_signed_repository(working_dir.path())?;
+
+    let repomd_path = working_dir.path().join("repodata/repomd.xml");
+    let repomd = std::fs::read_to_string(&repomd_path)?;
+    std::fs::write(&repomd_path, repomd.replace("<revision>", "<revision>1"))?;
+
+    let keys = vec![VerifyingKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH))?];
+    let options = RepositoryLoadOptions::default().verification_keys(keys);
+    assert!(matches!(
+        Repository::load_from_directory_with_options(working_dir.path(), options),
+        Err(MetadataError::SignatureVerificationError(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_load_verified_unsigned() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let mut repo = Repository::new();
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    let keys = vec![VerifyingKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH))?];
+    let options = RepositoryLoadOptions::default().verification_keys(keys);
+    assert!(matches!(
+        Repository::load_from_directory_with_options(working_dir.path(), options),
+        Err(MetadataError::SignatureVerificationError(_))
+    ));
+
+    // without keys, the signature isn't looked at
+    Repository::load_from_directory(working_dir.path())?;
+
+    Ok(())
+}
+
+#[test]
+fn test_load_verified_tampered_metadata() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    write_signed_repository(working_dir.path())?;
+
+    // a primary.xml which parses fine, but isn't the one described by the signed repomd.xml
+    let other_dir = TempDir::new("")?;
+    Repository::new().write_to_directory(other_dir.path(), RepositoryOptions::default())?;
+    let other_repo = Repository::load_from_directory(other_dir.path())?;
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    let primary_href = &repo.get_primary_data().location_href;
+    std::fs::copy(
+        other_dir
+            .path()
+            .join(&other_repo.get_primary_data().location_href),
+        working_dir.path().join(primary_href),
+    )?;
+
+    let keys = vec![VerifyingKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH))?];
+    let options = RepositoryLoadOptions::default().verification_keys(keys);
+    match Repository::load_from_directory_with_options(working_dir.path(), options) {
+        Err(MetadataError::ChecksumMismatchError(location_href)) => {
+            assert_eq!(&location_href, primary_href)
+        }
+        result => panic!("expected a checksum mismatch, got {:?}", result.map(|_| ())),
+    }
+
+    // without keys, the checksums aren't looked at
+    Repository::load_from_directory(working_dir.path())?;
+
+    Ok(())
+}
+
+const SIGNED_PACKAGE_PATH: &str = "./tests/assets/signing/rpm-empty-0-0.x86_64.rpm";
+const UNSIGNED_PACKAGE_PATH: &str = "./tests/assets/packages/rpm-empty-0-0.x86_64.rpm";
+// signed by Fedora with rpmsign, whose key isn't among the test keys
+const FEDORA_PACKAGE_PATH: &str = "./tests/assets/packages/rpm-sign-4.15.1-1.fc31.x86_64.rpm";
+
+#[test]
+fn test_verify_package_signature() -> Result<(), MetadataError> {
+    let test_key = VerifyingKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH))?;
+    let other_key = VerifyingKey::from_file(Path::new(OTHER_PUBLIC_KEY_PATH))?;
+
+    assert_eq!(
+        verify_package_signature(
+            Path::new(SIGNED_PACKAGE_PATH),
+            &[other_key.clone(), test_key]
+        )?,
+        PackageSignatureStatus::Valid
+    );
+    assert!(matches!(
+        verify_package_signature(Path::new(SIGNED_PACKAGE_PATH), &[other_key.clone()])?,
+        PackageSignatureStatus::Invalid(_)
+    ));
+    assert_eq!(
+        verify_package_signature(Path::new(UNSIGNED_PACKAGE_PATH), &[other_key.clone()])?,
+        PackageSignatureStatus::Unsigned
+    );
+    assert_eq!(
+        verify_package_signature(Path::new(FEDORA_PACKAGE_PATH), &[other_key])?,
+        PackageSignatureStatus::Invalid(
+            "header signature doesn't match any of the trusted keys".to_owned()
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_package_signature_tampered() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let test_key = VerifyingKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH))?;
+
+    // flip a bit of the payload, which is only covered by the header+payload signature
+    let mut package = std::fs::read(SIGNED_PACKAGE_PATH)?;
+    *package.last_mut().unwrap() ^= 1;
+    let tampered_path = working_dir.path().join("rpm-empty-0-0.x86_64.rpm");
+    std::fs::write(&tampered_path, package)?;
+
+    assert!(matches!(
+        verify_package_signature(&tampered_path, &[test_key])?,
+        PackageSignatureStatus::Invalid(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_package_signature_not_an_rpm() -> Result<(), MetadataError> {
+    let test_key = VerifyingKey::from_file(Path::new(TEST_PUBLIC_KEY_PATH))?;
+
+    assert!(matches!(
+        verify_package_signature(Path::new(TEST_PUBLIC_KEY_PATH), &[test_key])?,
+        PackageSignatureStatus::Invalid(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_package_signature_summary() {
+    let mut summary = PackageSignatureSummary::default();
+    summary.add("Packages/a.rpm", PackageSignatureStatus::Valid);
+    assert!(summary.all_valid());
+
+    summary.add("Packages/b.rpm", PackageSignatureStatus::Unsigned);
+    summary.add(
+        "Packages/c.rpm",
+        PackageSignatureStatus::Invalid("bad signature".to_owned()),
+    );
+    assert!(!summary.all_valid());
+    assert_eq!(
+        summary.to_string(),
+        "3 packages checked, 1 unsigned, 1 with an invalid signature\n  Packages/b.rpm: unsigned\n  Packages/c.rpm: bad signature"
+    );
+
+    assert_eq!(
+        "warn".parse::<PackageSignaturePolicy>(),
+        Ok(PackageSignaturePolicy::Warn)
+    );
+    assert!("ignore".parse::<PackageSignaturePolicy>().is_err());
+}
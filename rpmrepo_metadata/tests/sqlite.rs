@@ -0,0 +1,300 @@
+#![cfg(feature = "sqlite")]
+
+extern crate rpmrepo_metadata;
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use pretty_assertions::assert_eq;
+use rpmrepo_metadata::{
+    Checksum, FileType, MetadataError, Package, PackageMetadataSource, Repository,
+    RepositoryLoadOptions, RepositoryOptions, RepositoryWriter, EVR,
+};
+use rusqlite::Connection;
+use tempdir::TempDir;
+
+mod common;
+
+/// Decompress a database next to the original so that it can be queried.
+fn open_database(path: &Path) -> Result<Connection, MetadataError> {
+    let (mut reader, _) = niffler::get_reader(Box::new(File::open(path)?))?;
+    let db_path = PathBuf::from(format!("{}.db", path.display()));
+    std::io::copy(&mut reader, &mut File::create(&db_path)?)?;
+    Ok(Connection::open(db_path)?)
+}
+
+#[test]
+fn test_write_to_directory_sqlite() -> Result<(), MetadataError> {
+    let source_dir = TempDir::new("")?;
+    let mut repo_writer = RepositoryWriter::new(source_dir.path())?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let working_dir = TempDir::new("")?;
+    let mut repo = Repository::load_from_directory(source_dir.path())?;
+    repo.write_to_directory(
+        working_dir.path(),
+        RepositoryOptions::default().sqlite_metadata(true),
+    )?;
+
+    for (mdtype, db_mdtype) in &[
+        ("primary", "primary_db"),
+        ("filelists", "filelists_db"),
+        ("other", "other_db"),
+    ] {
+        let record = repo.get_record(mdtype).unwrap();
+        let db_record = repo.get_record(db_mdtype).unwrap();
+        assert!(db_record.location_href.ends_with(".sqlite.bz2"));
+        assert_eq!(db_record.database_version, Some(10));
+
+        let db = open_database(&working_dir.path().join(&db_record.location_href))?;
+        let (dbversion, checksum): (u32, String) =
+            db.query_row("SELECT dbversion, checksum FROM db_info", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        assert_eq!(dbversion, 10);
+        assert_eq!(checksum, record.checksum.to_values()?.1);
+
+        let num_packages: u32 =
+            db.query_row("SELECT COUNT(*) FROM packages", [], |row| row.get(0))?;
+        assert_eq!(num_packages, 2);
+    }
+
+    let primary_db = open_database(
+        &working_dir
+            .path()
+            .join(&repo.get_record("primary_db").unwrap().location_href),
+    )?;
+    let (name, epoch, version, release): (String, String, String, String) = primary_db.query_row(
+        "SELECT name, epoch, version, release FROM packages WHERE name = ?",
+        [common::COMPLEX_PACKAGE.name()],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+    let evr = common::COMPLEX_PACKAGE.evr();
+    assert_eq!(
+        (
+            name.as_str(),
+            epoch.as_str(),
+            version.as_str(),
+            release.as_str()
+        ),
+        (
+            common::COMPLEX_PACKAGE.name(),
+            evr.epoch.as_str(),
+            evr.version.as_str(),
+            evr.release.as_str()
+        )
+    );
+
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(
+        reloaded.get_record("primary_db"),
+        repo.get_record("primary_db")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_repository_writer_sqlite() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().sqlite_metadata(true);
+    let mut repo_writer = RepositoryWriter::new_with_options(working_dir.path(), options)?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    let filelists_db = open_database(
+        &working_dir
+            .path()
+            .join(&repo.get_record("filelists_db").unwrap().location_href),
+    )?;
+    let num_files: u32 = filelists_db.query_row(
+        "SELECT COUNT(*) FROM filelist INNER JOIN packages USING (pkgKey) WHERE pkgId = ?",
+        [common::COMPLEX_PACKAGE.checksum().to_values()?.1],
+        |row| row.get(0),
+    )?;
+    assert!(num_files > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_sqlite_primary_files() -> Result<(), MetadataError> {
+    let mut package = Package::default();
+    package.set_name("files-package");
+    package.set_arch("noarch");
+    package.set_evr(EVR::new("0", "1.0", "1"));
+    package.set_checksum(Checksum::Sha256(
+        "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_owned(),
+    ));
+    package.set_location_href("files-package-1.0-1.noarch.rpm");
+    package.add_file(FileType::Dir, "/etc/files-package");
+    package.add_file(FileType::File, "/etc/files-package/main.conf");
+    package.add_file(FileType::Ghost, "/etc/files-package/state");
+    package.add_file(FileType::File, "/usr/bin/files-package");
+    package.add_file(FileType::Dir, "/usr/share/files-package");
+
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().sqlite_metadata(true);
+    let mut repo_writer = RepositoryWriter::new_with_options(working_dir.path(), options)?;
+    repo_writer.start(1)?;
+    repo_writer.add_package(&package)?;
+    repo_writer.finish()?;
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    let primary_db = open_database(
+        &working_dir
+            .path()
+            .join(&repo.get_record("primary_db").unwrap().location_href),
+    )?;
+    let mut statement = primary_db.prepare("SELECT name, type FROM files ORDER BY name")?;
+    let files = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+
+    assert_eq!(
+        files,
+        vec![
+            ("/etc/files-package".to_owned(), "dir".to_owned()),
+            ("/etc/files-package/main.conf".to_owned(), "file".to_owned()),
+            ("/etc/files-package/state".to_owned(), "ghost".to_owned()),
+            ("/usr/bin/files-package".to_owned(), "file".to_owned()),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_load_from_sqlite() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().sqlite_metadata(true);
+    let mut repo_writer = RepositoryWriter::new_with_options(working_dir.path(), options)?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let xml_repo = Repository::load_from_directory_with_options(
+        working_dir.path(),
+        RepositoryLoadOptions::default().package_metadata_source(PackageMetadataSource::Xml),
+    )?;
+    let sqlite_repo = Repository::load_from_directory_with_options(
+        working_dir.path(),
+        RepositoryLoadOptions::default().package_metadata_source(PackageMetadataSource::Sqlite),
+    )?;
+    assert_eq!(sqlite_repo.packages(), xml_repo.packages());
+
+    Ok(())
+}
+
+#[test]
+fn test_load_from_sqlite_fallback() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().sqlite_metadata(true);
+    let mut repo_writer = RepositoryWriter::new_with_options(working_dir.path(), options)?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let expected = Repository::load_from_directory(working_dir.path())?;
+
+    let filelists_path = working_dir
+        .path()
+        .join(&expected.get_filelist_data().location_href);
+    std::fs::remove_file(&filelists_path)?;
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(repo.packages(), expected.packages());
+
+    let xml_only = Repository::load_from_directory_with_options(
+        working_dir.path(),
+        RepositoryLoadOptions::default().package_metadata_source(PackageMetadataSource::Xml),
+    );
+    assert!(xml_only.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_load_from_sqlite_fallback_error() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().sqlite_metadata(true);
+    let mut repo_writer = RepositoryWriter::new_with_options(working_dir.path(), options)?;
+    repo_writer.start(1)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.finish()?;
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    std::fs::remove_file(
+        working_dir
+            .path()
+            .join(&repo.get_filelist_data().location_href),
+    )?;
+    std::fs::write(
+        working_dir
+            .path()
+            .join(&repo.get_record("primary_db").unwrap().location_href),
+        "not a database",
+    )?;
+
+    // when neither works, the error is about the XML rather than the databases
+    let xml_error = Repository::load_from_directory_with_options(
+        working_dir.path(),
+        RepositoryLoadOptions::default().package_metadata_source(PackageMetadataSource::Xml),
+    )
+    .unwrap_err();
+    let auto_error = Repository::load_from_directory(working_dir.path()).unwrap_err();
+    assert_eq!(auto_error.to_string(), xml_error.to_string());
+
+    Ok(())
+}
+
+#[test]
+fn test_load_from_sqlite_weak_dependency_tables() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().sqlite_metadata(true);
+    let mut repo_writer = RepositoryWriter::new_with_options(working_dir.path(), options)?;
+    repo_writer.start(1)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.finish()?;
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    let primary_db_path = working_dir
+        .path()
+        .join(&repo.get_record("primary_db").unwrap().location_href);
+    // the databases are read whether they are compressed or not
+    let modify_primary_db = |sql: &str| -> Result<(), MetadataError> {
+        open_database(&primary_db_path)?.execute_batch(sql)?;
+        std::fs::rename(
+            format!("{}.db", primary_db_path.display()),
+            &primary_db_path,
+        )?;
+        Ok(())
+    };
+    let sqlite_options =
+        || RepositoryLoadOptions::default().package_metadata_source(PackageMetadataSource::Sqlite);
+
+    // databases written by createrepo don't have the weak dependency tables at all
+    modify_primary_db("DROP TABLE suggests; DROP TABLE enhances;")?;
+    let sqlite_repo =
+        Repository::load_from_directory_with_options(working_dir.path(), sqlite_options())?;
+    let package = sqlite_repo.packages().values().next().unwrap();
+    assert!(package.suggests().is_empty());
+    assert!(package.enhances().is_empty());
+    assert_eq!(package.recommends(), common::COMPLEX_PACKAGE.recommends());
+
+    // but a table which exists has to be readable
+    modify_primary_db("CREATE TABLE suggests (pkgKey INTEGER);")?;
+    assert!(
+        Repository::load_from_directory_with_options(working_dir.path(), sqlite_options()).is_err()
+    );
+
+    Ok(())
+}
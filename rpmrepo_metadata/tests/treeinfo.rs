@@ -0,0 +1,167 @@
+extern crate rpmrepo_metadata;
+
+use pretty_assertions::assert_eq;
+
+use rpmrepo_metadata::*;
+
+static TREEINFO: &str = r#"[header]
+type = productmd.treeinfo
+version = 1.2
+
+[addon-Server-HighAvailability]
+id = HighAvailability
+name = High Availability
+packages = addons/HighAvailability
+parent = Server
+repository = addons/HighAvailability
+type = addon
+uid = Server-HighAvailability
+
+[checksums]
+images/boot.iso = sha256:bc3a7a1a3cdc5ad20e3ab3e4ce4fb7c5d57e63a3ebd1fa4a57a88e7ca2a14a6a
+images/install.img = sha256:0c1ba4cbe4d9a9e1ba4f33ab6b22b3e0bd49df5c15d0c5f0b1fbc6a1c1b0cbd9
+images/pxeboot/vmlinuz = sha256:f6a9c1ee0bd0dc5b6b7e0d2c7ef9dbe3a2f8db11bc2afb69d0f14a1b53c6a3a5
+
+[general]
+arch = x86_64
+family = Red Hat Enterprise Linux
+version = 7.9
+
+[images-x86_64]
+boot.iso = images/boot.iso
+kernel = images/pxeboot/vmlinuz
+
+[images-xen]
+kernel = images/pxeboot/vmlinuz
+
+[media]
+discnum = 1
+totaldiscs = 1
+
+[release]
+name = Red Hat Enterprise Linux
+short = RHEL
+type = ga
+version = 7.9
+
+[stage2]
+mainimage = images/install.img
+
+[tree]
+arch = x86_64
+build_timestamp = 1598894452
+platforms = x86_64,xen
+variants = Server
+
+[variant-Server]
+addons = Server-HighAvailability
+id = Server
+name = Server
+packages = Packages
+repository = .
+type = variant
+uid = Server
+"#;
+
+#[test]
+fn test_treeinfo_read() -> Result<(), MetadataError> {
+    let treeinfo: Treeinfo = TREEINFO.parse()?;
+
+    assert_eq!(treeinfo.format_version, "1.2");
+    assert_eq!(
+        treeinfo.release,
+        TreeinfoProduct {
+            name: "Red Hat Enterprise Linux".to_owned(),
+            short: "RHEL".to_owned(),
+            version: "7.9".to_owned(),
+            product_type: Some("ga".to_owned()),
+        }
+    );
+    assert_eq!(treeinfo.base_product, None);
+    assert_eq!(
+        treeinfo.tree,
+        TreeinfoTree {
+            arch: "x86_64".to_owned(),
+            build_timestamp: 1598894452,
+            platforms: vec!["x86_64".to_owned(), "xen".to_owned()],
+        }
+    );
+    assert_eq!(
+        treeinfo.media,
+        Some(TreeinfoMedia {
+            discnum: 1,
+            totaldiscs: 1,
+        })
+    );
+
+    assert_eq!(treeinfo.variants.len(), 1);
+    let server = &treeinfo.variants[0];
+    assert_eq!(server.uid, "Server");
+    assert_eq!(server.repository.as_deref(), Some("."));
+    assert_eq!(server.addons.len(), 1);
+    assert_eq!(server.addons[0].id, "HighAvailability");
+    assert_eq!(server.addons[0].variant_type, "addon");
+
+    assert_eq!(
+        treeinfo.images["xen"].get("kernel").map(String::as_str),
+        Some("images/pxeboot/vmlinuz")
+    );
+    assert_eq!(
+        treeinfo.checksums["images/boot.iso"],
+        Checksum::Sha256(
+            "bc3a7a1a3cdc5ad20e3ab3e4ce4fb7c5d57e63a3ebd1fa4a57a88e7ca2a14a6a".to_owned()
+        )
+    );
+    assert!(treeinfo.other_sections.contains_key("general"));
+
+    assert_eq!(
+        treeinfo.repository_paths(),
+        vec![".", "addons/HighAvailability"]
+    );
+    assert_eq!(
+        treeinfo.image_paths(),
+        vec![
+            "images/boot.iso",
+            "images/install.img",
+            "images/pxeboot/vmlinuz"
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_treeinfo_roundtrip() -> Result<(), MetadataError> {
+    let treeinfo: Treeinfo = TREEINFO.parse()?;
+    assert_eq!(treeinfo.to_string(), TREEINFO);
+
+    Ok(())
+}
+
+#[test]
+fn test_treeinfo_write_unknown_checksum() -> Result<(), MetadataError> {
+    let mut treeinfo: Treeinfo = TREEINFO.parse()?;
+    treeinfo
+        .checksums
+        .insert("images/efiboot.img".to_owned(), Checksum::Unknown);
+
+    // the file is left out rather than failing to write the whole .treeinfo
+    assert_eq!(treeinfo.to_string(), TREEINFO);
+
+    Ok(())
+}
+
+#[test]
+fn test_treeinfo_missing_section() {
+    let treeinfo = TREEINFO.replace("[variant-Server]", "[variant-Client]");
+    assert!(matches!(
+        treeinfo.parse::<Treeinfo>(),
+        Err(MetadataError::TreeinfoParseError(_))
+    ));
+
+    let treeinfo = TREEINFO.replace("arch = x86_64\nbuild_timestamp", "build_timestamp");
+    assert!(matches!(
+        treeinfo.parse::<Treeinfo>(),
+        Err(MetadataError::MissingFieldError("arch"))
+    ));
+}
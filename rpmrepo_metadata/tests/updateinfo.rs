@@ -0,0 +1,257 @@
+extern crate rpmrepo_metadata;
+
+use pretty_assertions::assert_eq;
+
+use rpmrepo_metadata::*;
+use tempdir::TempDir;
+
+static FEDORA_UPDATEINFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<updates>
+  <update from="updates@fedoraproject.org" status="stable" type="bugfix" version="2.0">
+    <id>FEDORA-2020-15f9382449</id>
+    <title>nano-4.9.3-1.fc32</title>
+    <issued date="2020-05-27 04:10:31"/>
+    <updated date="2020-05-28 01:02:03"/>
+    <rights>Copyright (C) 2020 Red Hat, Inc. and others.</rights>
+    <release>Fedora 32</release>
+    <severity>None</severity>
+    <summary>nano-4.9.3-1.fc32 bugfix update</summary>
+    <description>- update to the latest upstream bugfix release</description>
+    <solution/>
+    <references>
+      <reference href="https://bugzilla.redhat.com/show_bug.cgi?id=1839351" id="1839351" type="bugzilla" title="nano-4.9.3 is available"/>
+    </references>
+    <pkglist>
+      <collection short="F32">
+        <name>Fedora 32</name>
+        <package name="nano" version="4.9.3" release="1.fc32" epoch="0" arch="x86_64" src="https://download.fedoraproject.org/pub/fedora/linux/updates/32/x86_64/n/nano-4.9.3-1.fc32.x86_64.rpm">
+          <filename>nano-4.9.3-1.fc32.x86_64.rpm</filename>
+        </package>
+        <package name="nano" version="4.9.3" release="1.fc32" epoch="0" arch="src" src="https://download.fedoraproject.org/pub/fedora/linux/updates/32/SRPMS/n/nano-4.9.3-1.fc32.src.rpm">
+          <filename>nano-4.9.3-1.fc32.src.rpm</filename>
+        </package>
+      </collection>
+    </pkglist>
+  </update>
+  <update from="updates@fedoraproject.org" status="stable" type="enhancement" version="2.0">
+    <id>FEDORA-MODULAR-2020-0b3c8c8d2a</id>
+    <title>nodejs-12-3220200626153814.e1ab9396</title>
+    <issued date="2020-07-01 01:21:35"/>
+    <rights>Copyright (C) 2020 Red Hat, Inc. and others.</rights>
+    <release>Fedora Modular 32</release>
+    <severity>None</severity>
+    <summary>nodejs-12-3220200626153814.e1ab9396 enhancement update</summary>
+    <description>Update to 12.18.2</description>
+    <solution/>
+    <references/>
+    <pkglist>
+      <collection short="F32M">
+        <name>Fedora Modular 32</name>
+        <module name="nodejs" stream="12" version="3220200626153814" context="e1ab9396" arch="x86_64"/>
+        <package name="nodejs" version="12.18.2" release="1.module_f32+9495+4d7a4e4c" epoch="1" arch="x86_64" src="https://download.fedoraproject.org/pub/fedora/linux/updates/32/x86_64/n/nodejs-12.18.2-1.module_f32+9495+4d7a4e4c.x86_64.rpm">
+          <filename>nodejs-12.18.2-1.module_f32+9495+4d7a4e4c.x86_64.rpm</filename>
+        </package>
+      </collection>
+    </pkglist>
+  </update>
+</updates>
+"#;
+
+// A made-up advisory in the format used by Red Hat, for the rpm-empty test package
+// (tests/assets/packages/rpm-empty-0-0.x86_64.rpm), so that the checksum is a real one.
+static RHEL_UPDATEINFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<updates>
+  <update from="release-engineering@example.com" status="final" type="security" version="4">
+    <id>EXSA-2021:0001</id>
+    <title>Important: rpm-empty security update</title>
+    <issued date="2021-07-20 00:00:00"/>
+    <updated date="2021-07-20 00:00:00"/>
+    <rights>Copyright 2021 Example Inc</rights>
+    <release>Example Linux 8</release>
+    <pushcount>1</pushcount>
+    <severity>Important</severity>
+    <summary>An update for rpm-empty is now available for Example Linux 8.</summary>
+    <description>The rpm-empty package contains no files.</description>
+    <solution>For details on how to apply this update, refer to the Example Linux documentation.</solution>
+    <references>
+      <reference href="https://errata.example.com/EXSA-2021:0001" id="EXSA-2021:0001" type="self" title="EXSA-2021:0001"/>
+      <reference href="https://cve.example.com/CVE-0000-0000" id="CVE-0000-0000" type="cve" title="CVE-0000-0000"/>
+    </references>
+    <pkglist>
+      <collection short="example-8-for-x86_64-baseos-rpms__8">
+        <name>example-8-for-x86_64-baseos-rpms__8</name>
+        <package name="rpm-empty" version="0" release="0" epoch="0" arch="x86_64" src="rpm-empty-0-0.src.rpm">
+          <filename>rpm-empty-0-0.x86_64.rpm</filename>
+          <sum type="sha256">5fb9cced22e49bac85329333cf9048610dbeb31e2531e96debf761a6e6e75609</sum>
+          <reboot_suggested>1</reboot_suggested>
+          <restart_suggested>True</restart_suggested>
+          <relogin_suggested>0</relogin_suggested>
+        </package>
+      </collection>
+    </pkglist>
+  </update>
+</updates>
+"#;
+
+#[test]
+fn test_updateinfo_xml_read_fedora() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<UpdateinfoXml>(FEDORA_UPDATEINFO)?;
+
+    assert_eq!(repo.advisories.len(), 2);
+
+    let bugfix = &repo.advisories[0];
+    assert_eq!(bugfix.from, "updates@fedoraproject.org");
+    assert_eq!(bugfix.status, "stable");
+    assert_eq!(bugfix.update_type, "bugfix");
+    assert_eq!(bugfix.version, "2.0");
+    assert_eq!(bugfix.id, "FEDORA-2020-15f9382449");
+    assert_eq!(bugfix.title, "nano-4.9.3-1.fc32");
+    assert_eq!(bugfix.issued_date.as_deref(), Some("2020-05-27 04:10:31"));
+    assert_eq!(bugfix.updated_date.as_deref(), Some("2020-05-28 01:02:03"));
+    assert_eq!(
+        bugfix.rights,
+        "Copyright (C) 2020 Red Hat, Inc. and others."
+    );
+    assert_eq!(bugfix.release, "Fedora 32");
+    assert_eq!(bugfix.pushcount, None);
+    assert_eq!(bugfix.severity, "None");
+    assert_eq!(bugfix.solution.as_deref(), Some(""));
+    assert_eq!(
+        bugfix.references,
+        vec![UpdateReference {
+            href: "https://bugzilla.redhat.com/show_bug.cgi?id=1839351".to_owned(),
+            id: "1839351".to_owned(),
+            title: "nano-4.9.3 is available".to_owned(),
+            reftype: "bugzilla".to_owned(),
+        }]
+    );
+    assert_eq!(bugfix.pkglist.len(), 1);
+    assert_eq!(bugfix.pkglist[0].name, "Fedora 32");
+    assert_eq!(bugfix.pkglist[0].shortname, "F32");
+    assert_eq!(bugfix.pkglist[0].module, None);
+    assert_eq!(bugfix.pkglist[0].packages.len(), 2);
+    assert_eq!(bugfix.pkglist[0].packages[1].arch, "src");
+    assert_eq!(
+        bugfix.pkglist[0].packages[1].filename,
+        "nano-4.9.3-1.fc32.src.rpm"
+    );
+    assert_eq!(bugfix.pkglist[0].packages[1].checksum, Checksum::Unknown);
+
+    let modular = &repo.advisories[1];
+    assert_eq!(modular.updated_date, None);
+    assert!(modular.references.is_empty());
+    assert_eq!(
+        modular.pkglist[0].module,
+        Some(UpdateCollectionModule {
+            name: "nodejs".to_owned(),
+            stream: "12".to_owned(),
+            version: 3220200626153814,
+            context: "e1ab9396".to_owned(),
+            arch: "x86_64".to_owned(),
+        })
+    );
+    assert_eq!(modular.pkglist[0].packages[0].epoch, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_updateinfo_xml_read_rhel() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<UpdateinfoXml>(RHEL_UPDATEINFO)?;
+
+    assert_eq!(repo.advisories.len(), 1);
+
+    let advisory = &repo.advisories[0];
+    assert_eq!(advisory.id, "EXSA-2021:0001");
+    assert_eq!(advisory.update_type, "security");
+    assert_eq!(advisory.pushcount.as_deref(), Some("1"));
+    assert_eq!(advisory.severity, "Important");
+    assert_eq!(advisory.references.len(), 2);
+    assert_eq!(advisory.references[1].reftype, "cve");
+
+    let expected_package = UpdateCollectionPackage {
+        arch: "x86_64".to_owned(),
+        epoch: 0,
+        filename: "rpm-empty-0-0.x86_64.rpm".to_owned(),
+        name: "rpm-empty".to_owned(),
+        reboot_suggested: true,
+        restart_suggested: true,
+        relogin_suggested: false,
+        release: "0".to_owned(),
+        src: "rpm-empty-0-0.src.rpm".to_owned(),
+        checksum: Checksum::Sha256(
+            "5fb9cced22e49bac85329333cf9048610dbeb31e2531e96debf761a6e6e75609".to_owned(),
+        ),
+        version: "0".to_owned(),
+    };
+    assert_eq!(advisory.pkglist[0].packages, vec![expected_package]);
+
+    Ok(())
+}
+
+#[test]
+fn test_updateinfo_xml_read_truncated() {
+    // the file ends in the middle of the package list of the first advisory
+    let truncated = &FEDORA_UPDATEINFO[..FEDORA_UPDATEINFO.find("</pkglist>").unwrap()];
+
+    let mut repo = Repository::new();
+    assert!(matches!(
+        repo.load_metadata_str::<UpdateinfoXml>(truncated),
+        Err(MetadataError::InconsistentMetadataError(_))
+    ));
+}
+
+#[test]
+fn test_updateinfo_xml_roundtrip() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<UpdateinfoXml>(FEDORA_UPDATEINFO)?;
+
+    let actual = repo.to_string::<UpdateinfoXml>()?;
+    assert_eq!(actual, FEDORA_UPDATEINFO);
+
+    Ok(())
+}
+
+#[test]
+fn test_updateinfo_xml_writer_collection_package() -> Result<(), MetadataError> {
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<UpdateinfoXml>(RHEL_UPDATEINFO)?;
+
+    let actual = repo.to_string::<UpdateinfoXml>()?;
+    let expected = r#"      <collection short="example-8-for-x86_64-baseos-rpms__8">
+        <name>example-8-for-x86_64-baseos-rpms__8</name>
+        <package name="rpm-empty" version="0" release="0" epoch="0" arch="x86_64" src="rpm-empty-0-0.src.rpm">
+          <filename>rpm-empty-0-0.x86_64.rpm</filename>
+          <sum type="sha256">5fb9cced22e49bac85329333cf9048610dbeb31e2531e96debf761a6e6e75609</sum>
+          <reboot_suggested>True</reboot_suggested>
+          <restart_suggested>True</restart_suggested>
+        </package>
+      </collection>
+"#;
+    assert!(actual.contains(expected));
+    assert!(actual.contains("    <pushcount>1</pushcount>\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_updateinfo_write_to_directory() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+
+    let mut repo = Repository::new();
+    repo.load_metadata_str::<UpdateinfoXml>(FEDORA_UPDATEINFO)?;
+    repo.write_to_directory(working_dir.path(), RepositoryOptions::default())?;
+
+    let record = repo.get_record("updateinfo").unwrap();
+    let (_, checksum) = record.checksum.to_values()?;
+    assert_eq!(
+        record.location_href,
+        format!("repodata/{}-updateinfo.xml.gz", checksum)
+    );
+    assert_eq!(record.open_size, Some(FEDORA_UPDATEINFO.len() as u64));
+    assert!(working_dir.path().join(&record.location_href).exists());
+
+    Ok(())
+}
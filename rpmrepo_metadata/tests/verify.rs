@@ -0,0 +1,234 @@
+extern crate rpmrepo_metadata;
+
+use std::path::Path;
+use std::time::Duration;
+
+use pretty_assertions::assert_eq;
+use ring::digest;
+use rpmrepo_metadata::*;
+use tempdir::TempDir;
+
+/// Write a repository with two (fake) packages to `path`.
+fn write_test_repository(path: &Path) -> Result<Repository, MetadataError> {
+    let mut repo = Repository::new();
+
+    for name in &["foo", "bar"] {
+        let location_href = format!("Packages/{}-0-0.x86_64.rpm", name);
+        let contents = format!("contents of {}", name);
+        let sha256: String = digest::digest(&digest::SHA256, contents.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        std::fs::create_dir_all(path.join("Packages"))?;
+        std::fs::write(path.join(&location_href), &contents)?;
+
+        let mut package = Package::new(
+            name,
+            &EVR::new("0", "0", "0"),
+            "x86_64",
+            &Checksum::Sha256(sha256),
+            &location_href,
+        );
+        package.set_size(contents.len() as u64, 0, 0);
+        repo.add_package(package)?;
+    }
+
+    repo.write_to_directory(path, RepositoryOptions::default())?;
+    Ok(repo)
+}
+
+#[test]
+fn test_verify_repository() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    write_test_repository(working_dir.path())?;
+
+    let report = verify_repository(working_dir.path())?;
+    assert_eq!(report, RepositoryVerificationReport::default());
+    assert!(report.is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_repository_problems() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let path = working_dir.path();
+    let repo = write_test_repository(path)?;
+
+    // missing package
+    std::fs::remove_file(path.join("Packages/foo-0-0.x86_64.rpm"))?;
+    // corrupt package of the same size
+    std::fs::write(path.join("Packages/bar-0-0.x86_64.rpm"), "contents of BAR")?;
+    // corrupt metadata file
+    let other_href = &repo.get_other_data().location_href;
+    std::fs::write(path.join(other_href), "garbage")?;
+    // stray file
+    std::fs::write(path.join("Packages/stray.rpm"), "stray")?;
+
+    let report = verify_repository(path)?;
+    assert!(!report.is_ok());
+    assert_eq!(report.missing, vec!["Packages/foo-0-0.x86_64.rpm"]);
+    assert_eq!(
+        report.corrupt,
+        vec![
+            CorruptFile {
+                location_href: "Packages/bar-0-0.x86_64.rpm".to_owned(),
+                reason: "checksum mismatch".to_owned(),
+            },
+            CorruptFile {
+                location_href: other_href.clone(),
+                reason: format!(
+                    "size is 7, expected {}",
+                    repo.get_other_data().size.unwrap()
+                ),
+            },
+        ]
+    );
+    assert_eq!(report.unreferenced, vec!["Packages/stray.rpm"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_repository_no_repomd() {
+    let working_dir = TempDir::new("").unwrap();
+    assert!(matches!(
+        verify_repository(working_dir.path()),
+        Err(MetadataError::IoError(_))
+    ));
+}
+
+#[test]
+fn test_find_orphaned_files() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let path = working_dir.path();
+    let mut repo = write_test_repository(path)?;
+
+    std::fs::write(path.join("Packages/old-0-0.x86_64.rpm"), "old")?;
+    std::fs::write(path.join("repodata/stale-primary.xml.gz"), "stale")?;
+    // only packages and metadata are ever orphaned
+    std::fs::write(path.join("README"), "readme")?;
+    // the package is dropped from the metadata, but its file stays behind, and so do the
+    // previous primary.xml, filelists.xml and other.xml
+    let mut expected: Vec<String> = repo
+        .records()
+        .iter()
+        .map(|record| record.location_href.clone())
+        .collect();
+    repo.packages_mut()
+        .retain(|_, package| package.name() != "foo");
+    repo.write_to_directory(path, RepositoryOptions::default())?;
+
+    let hour = Duration::from_secs(60 * 60);
+    assert!(repo.find_orphaned_files(path, hour)?.is_empty());
+
+    expected.extend(vec![
+        "Packages/foo-0-0.x86_64.rpm".to_owned(),
+        "Packages/old-0-0.x86_64.rpm".to_owned(),
+        "repodata/stale-primary.xml.gz".to_owned(),
+    ]);
+    expected.sort();
+    assert_eq!(
+        repo.find_orphaned_files(path, Duration::from_secs(0))?,
+        expected
+    );
+    assert!(path.join("Packages/old-0-0.x86_64.rpm").exists());
+
+    assert_eq!(
+        repo.remove_orphaned_files(path, Duration::from_secs(0))?,
+        expected
+    );
+    assert!(!path.join("Packages/old-0-0.x86_64.rpm").exists());
+    assert!(path.join("Packages/bar-0-0.x86_64.rpm").exists());
+    assert!(path.join("README").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_find_orphaned_files_packages_not_loaded() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let path = working_dir.path();
+    write_test_repository(path)?;
+
+    // only repomd.xml, the packages are unknown rather than gone
+    let mut repo = Repository::new();
+    repo.load_metadata_file::<RepomdXml>(&path.join("repodata/repomd.xml"))?;
+    assert!(matches!(
+        repo.remove_orphaned_files(path, Duration::from_secs(0)),
+        Err(MetadataError::PackagesNotLoadedError)
+    ));
+    assert!(path.join("Packages/foo-0-0.x86_64.rpm").exists());
+
+    let repo = Repository::load_from_directory(path)?;
+    assert!(repo
+        .find_orphaned_files(path, Duration::from_secs(0))?
+        .is_empty());
+
+    Ok(())
+}
+
+static TREEINFO: &str = r#"[header]
+type = productmd.treeinfo
+version = 1.2
+
+[release]
+name = Fedora
+short = Fedora
+version = 33
+
+[tree]
+arch = x86_64
+build_timestamp = 1598894452
+platforms = x86_64
+variants = Everything
+
+[images-x86_64]
+boot.iso = images/boot.iso
+
+[variant-Everything]
+addons = Everything-Extras
+id = Everything
+name = Everything
+packages = Packages
+repository = .
+type = variant
+uid = Everything
+
+[addon-Everything-Extras]
+id = Extras
+name = Extras
+packages = addons/Extras/Packages
+parent = Everything
+repository = addons/Extras
+type = addon
+uid = Everything-Extras
+"#;
+
+#[test]
+fn test_find_orphaned_files_tree() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let path = working_dir.path();
+    let repo = write_test_repository(path)?;
+
+    std::fs::write(path.join(".treeinfo"), TREEINFO)?;
+    std::fs::create_dir_all(path.join("images"))?;
+    std::fs::write(path.join("images/boot.iso"), "boot")?;
+    std::fs::create_dir_all(path.join("addons/Extras/Packages"))?;
+    std::fs::create_dir_all(path.join("addons/Extras/repodata"))?;
+    std::fs::write(
+        path.join("addons/Extras/Packages/extra-0-0.x86_64.rpm"),
+        "extra",
+    )?;
+    std::fs::write(path.join("addons/Extras/repodata/repomd.xml"), "repomd")?;
+    std::fs::write(path.join("Packages/old-0-0.x86_64.rpm"), "old")?;
+
+    assert_eq!(
+        repo.find_orphaned_files(path, Duration::from_secs(0))?,
+        vec!["Packages/old-0-0.x86_64.rpm".to_owned()]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,155 @@
+extern crate rpmrepo_metadata;
+
+use std::io::{Read, Write};
+
+use pretty_assertions::assert_eq;
+use rpmrepo_metadata::{
+    MetadataError, PackageIterator, Repository, RepositoryOptions, RepositoryWriter, ZchunkReader,
+    ZchunkWriter,
+};
+use tempdir::TempDir;
+
+mod common;
+
+fn write_chunks(chunks: &[&[u8]]) -> std::io::Result<Vec<u8>> {
+    let mut writer = ZchunkWriter::new(Vec::new());
+    for chunk in chunks {
+        writer.write_all(chunk)?;
+        writer.end_chunk()?;
+    }
+    writer.finish()
+}
+
+#[test]
+fn test_zchunk_roundtrip() -> Result<(), MetadataError> {
+    let chunks: &[&[u8]] = &[
+        b"<metadata>",
+        b"<package>foo</package>",
+        b"",
+        b"</metadata>\n",
+    ];
+    let bytes = write_chunks(chunks)?;
+
+    assert_eq!(&bytes[..5], b"\0ZCK1");
+
+    let mut contents = Vec::new();
+    ZchunkReader::new(bytes.as_slice())?.read_to_end(&mut contents)?;
+    assert_eq!(contents, chunks.concat());
+
+    let mut contents = Vec::new();
+    ZchunkReader::new(write_chunks(&[])?.as_slice())?.read_to_end(&mut contents)?;
+    assert!(contents.is_empty());
+
+    Ok(())
+}
+
+/// The fixture was created by an independent implementation (the zchunk crate), which splits the
+/// data into content-defined chunks.
+#[test]
+fn test_zchunk_read_foreign_file() -> Result<(), MetadataError> {
+    let expected = std::fs::read("./tests/assets/zchunk/other.xml")?;
+    let file = std::fs::File::open("./tests/assets/zchunk/other.xml.zck")?;
+
+    let mut contents = Vec::new();
+    ZchunkReader::new(std::io::BufReader::new(file))?.read_to_end(&mut contents)?;
+    assert_eq!(contents, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_zchunk_corruption() -> Result<(), MetadataError> {
+    let bytes = write_chunks(&[b"<metadata>", b"<package>foo</package>", b"</metadata>\n"])?;
+
+    // flip a bit in the last byte of the data section
+    let mut corrupted_data = bytes.clone();
+    *corrupted_data.last_mut().unwrap() ^= 1;
+    let mut contents = Vec::new();
+    let mut reader = ZchunkReader::new(corrupted_data.as_slice())?;
+    assert!(reader.read_to_end(&mut contents).is_err());
+
+    // flip a bit in the last byte of the header
+    let header_size = ZchunkReader::new(bytes.as_slice())?.header().len();
+    let mut corrupted_header = bytes.clone();
+    corrupted_header[header_size - 1] ^= 1;
+    assert!(ZchunkReader::new(corrupted_header.as_slice()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_write_to_directory_zchunk() -> Result<(), MetadataError> {
+    let source_dir = TempDir::new("")?;
+    let mut repo_writer = RepositoryWriter::new(source_dir.path())?;
+    repo_writer.start(2)?;
+    repo_writer.add_package(&common::COMPLEX_PACKAGE)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let working_dir = TempDir::new("")?;
+    let mut repo = Repository::load_from_directory(source_dir.path())?;
+    repo.write_to_directory(
+        working_dir.path(),
+        RepositoryOptions::default().zchunk_metadata(true),
+    )?;
+
+    for (mdtype, zck_mdtype) in &[
+        ("primary", "primary_zck"),
+        ("filelists", "filelists_zck"),
+        ("other", "other_zck"),
+    ] {
+        let record = repo.get_record(mdtype).unwrap();
+        let zck_record = repo.get_record(zck_mdtype).unwrap();
+
+        assert!(zck_record.location_href.ends_with(".xml.zck"));
+        assert_eq!(zck_record.open_checksum, record.open_checksum);
+        assert_eq!(zck_record.open_size, record.open_size);
+        assert!(zck_record.header_checksum.is_some());
+        assert!(zck_record.header_size.is_some());
+    }
+
+    let reloaded = Repository::load_from_directory(working_dir.path())?;
+    assert_eq!(
+        reloaded.get_record("primary_zck"),
+        repo.get_record("primary_zck")
+    );
+
+    let zck_packages = PackageIterator::from_files(
+        &working_dir
+            .path()
+            .join(&repo.get_record("primary_zck").unwrap().location_href),
+        &working_dir
+            .path()
+            .join(&repo.get_record("filelists_zck").unwrap().location_href),
+        &working_dir
+            .path()
+            .join(&repo.get_record("other_zck").unwrap().location_href),
+    )?
+    .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(
+        zck_packages.iter().collect::<Vec<_>>(),
+        reloaded.packages().values().collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_repository_writer_zchunk() -> Result<(), MetadataError> {
+    let working_dir = TempDir::new("")?;
+    let options = RepositoryOptions::default().zchunk_metadata(true);
+    let mut repo_writer = RepositoryWriter::new_with_options(working_dir.path(), options)?;
+    repo_writer.start(1)?;
+    repo_writer.add_package(&common::RPM_EMPTY)?;
+    repo_writer.finish()?;
+
+    let repo = Repository::load_from_directory(working_dir.path())?;
+    let zck_record = repo.get_record("primary_zck").unwrap();
+    assert_eq!(
+        zck_record.open_checksum,
+        repo.get_primary_data().open_checksum
+    );
+    assert!(working_dir.path().join(&zck_record.location_href).exists());
+
+    Ok(())
+}